@@ -0,0 +1,339 @@
+use super::container::{ContainerInfo, ContainerReader, ContainerType, Phase, CONTAINER_INFO_SIZE};
+use super::{
+    AsyncTransport, CommandCode, DeviceInfo, Error, MtpCommandCode, PtpEvent, StandardCommandCode,
+    StandardResponseCode, MAX_DATA_LEN,
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+
+/// Size of the first read of each phase.
+const RECEIVE_BUFFER_SIZE: usize = 8 * 1024;
+/// Largest read of the remaining payload of a container.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Async flavour of [`Camera`](crate::Camera), driven by any [`AsyncTransport`].
+///
+/// Only `std` futures are used, so it runs on whichever executor the transport was written
+/// for. There are no timeout parameters, wrap the returned futures in the runtime's timeout
+/// facility instead.
+pub struct AsyncCamera<T: AsyncTransport> {
+    current_tid: u32,
+    session_id: u32,
+    pending_events: Vec<PtpEvent>,
+    max_data_len: usize,
+    transport: T,
+}
+
+impl<T: AsyncTransport> AsyncCamera<T> {
+    pub fn with_transport(transport: T) -> AsyncCamera<T> {
+        AsyncCamera {
+            current_tid: 0,
+            session_id: 1,
+            pending_events: vec![],
+            max_data_len: MAX_DATA_LEN,
+            transport,
+        }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// execute a PTP transaction, see [`Camera::command`](crate::Camera::command).
+    pub async fn command(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+    ) -> Result<Vec<u8>, Error> {
+        let tid = self.current_tid;
+        self.current_tid += 1;
+
+        let mut request_payload = Vec::with_capacity(params.len() * 4);
+        for p in params {
+            request_payload.write_u32::<LittleEndian>(*p).ok();
+        }
+
+        self.write_txn_phase(ContainerType::Command, code, tid, &request_payload)
+            .await?;
+
+        if let Some(data) = data {
+            self.write_txn_phase(ContainerType::Data, code, tid, data)
+                .await?;
+        }
+
+        let mut data_phase_payload = vec![];
+        loop {
+            let (container, payload) = self.read_txn_phase().await?;
+            match container.phase(&payload, tid)? {
+                // keep interleaved events around for drain_pending_events()
+                Phase::Event(event) => {
+                    debug!("event during transaction {}: {:?}", tid, event);
+                    self.pending_events.push(event);
+                }
+                Phase::Data => data_phase_payload = payload,
+                Phase::Response(StandardResponseCode::Ok, _) => return Ok(data_phase_payload),
+                Phase::Response(code, _) => return Err(Error::Response(code)),
+            }
+        }
+    }
+
+    /// Take the events that arrived on the bulk pipe while a transaction was running.
+    pub fn drain_pending_events(&mut self) -> Vec<PtpEvent> {
+        std::mem::take(&mut self.pending_events)
+    }
+
+    /// The id used by `open_session`, 1 unless changed with `set_session_id`.
+    pub fn session_id(&self) -> u32 {
+        self.session_id
+    }
+
+    /// Set the id used by the next `open_session`. Must not be 0.
+    pub fn set_session_id(&mut self, session_id: u32) {
+        self.session_id = session_id;
+    }
+
+    /// Refuse data phases larger than `max` bytes, `MAX_DATA_LEN` by default, see
+    /// `CameraConfig::max_data_len`.
+    pub fn set_max_data_len(&mut self, max: usize) {
+        self.max_data_len = max;
+    }
+
+    async fn write_txn_phase(
+        &mut self,
+        kind: ContainerType,
+        code: CommandCode,
+        tid: u32,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        trace!(
+            "Write {:?} - 0x{:04x} ({}), tid:{}",
+            kind,
+            code,
+            StandardCommandCode::name(code)
+                .or_else(|| MtpCommandCode::name(code))
+                .unwrap_or("unknown"),
+            tid
+        );
+
+        let mut buf = Vec::with_capacity(payload.len() + CONTAINER_INFO_SIZE);
+        ContainerInfo {
+            payload_len: payload.len(),
+            kind,
+            code,
+            tid,
+        }
+        .write(&mut buf);
+        buf.extend_from_slice(payload);
+        self.transport.write(&buf).await
+    }
+
+    async fn read_txn_phase(&mut self) -> Result<(ContainerInfo, Vec<u8>), Error> {
+        let mut buf = vec![0u8; RECEIVE_BUFFER_SIZE];
+        let n = self.transport.read(&mut buf).await?;
+        let mut reader =
+            ContainerReader::start(&buf[..n], buf.len(), CHUNK_SIZE, self.max_data_len)?;
+        let mut payload = buf[CONTAINER_INFO_SIZE..n].to_vec();
+
+        // grown a chunk at a time, the length is announced by the device
+        while let Some(len) = reader.next_len() {
+            let start = payload.len();
+            payload.resize(start + len, 0);
+            let n = self.transport.read(&mut payload[start..]).await?;
+            payload.truncate(start + n);
+            reader.advance(n, len)?;
+        }
+
+        Ok((reader.finish()?, payload))
+    }
+
+    pub async fn get_device_info(&mut self) -> Result<DeviceInfo, Error> {
+        let data = self
+            .command(StandardCommandCode::GetDeviceInfo, &[0, 0, 0], None)
+            .await?;
+        DeviceInfo::decode(&data)
+    }
+
+    pub async fn open_session(&mut self) -> Result<(), Error> {
+        // OpenSession is transaction 0, the first operation of the session 1
+        self.current_tid = 0;
+        self.command(
+            StandardCommandCode::OpenSession,
+            &[self.session_id, 0, 0],
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn close_session(&mut self) -> Result<(), Error> {
+        self.command(StandardCommandCode::CloseSession, &[], None)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_object(&mut self, handle: u32) -> Result<Vec<u8>, Error> {
+        self.command(StandardCommandCode::GetObject, &[handle], None)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StandardEventCode;
+    use std::collections::VecDeque;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    // runs a future whose IO is always ready
+    fn block_on<F: Future>(future: F) -> F::Output {
+        fn raw() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                raw()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        let waker = unsafe { Waker::from_raw(raw()) };
+        let mut future = pin!(future);
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("transport future is pending"),
+        }
+    }
+
+    // replays scripted containers, recording those written
+    #[derive(Default)]
+    struct Script {
+        written: Vec<ContainerInfo>,
+        replies: VecDeque<Vec<u8>>,
+    }
+
+    impl Script {
+        fn reply(&mut self, kind: ContainerType, code: u16, tid: u32, params: &[u32]) {
+            let mut buf = vec![];
+            ContainerInfo {
+                payload_len: params.len() * 4,
+                kind,
+                code,
+                tid,
+            }
+            .write(&mut buf);
+            for p in params {
+                buf.extend_from_slice(&p.to_le_bytes());
+            }
+            self.replies.push_back(buf);
+        }
+    }
+
+    impl AsyncTransport for Script {
+        async fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+            self.written.push(ContainerInfo::parse(buf)?);
+            Ok(())
+        }
+
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let mut reply = self.replies.pop_front().ok_or(rusb::Error::Timeout)?;
+            // the rest of a transfer larger than the buffer is left for the next read
+            if reply.len() > buf.len() {
+                self.replies.push_front(reply.split_off(buf.len()));
+            }
+            buf[..reply.len()].copy_from_slice(&reply);
+            Ok(reply.len())
+        }
+    }
+
+    #[test]
+    fn interleaved_event() {
+        let mut script = Script::default();
+        script.reply(ContainerType::Response, StandardResponseCode::Ok, 0, &[]);
+        script.reply(
+            ContainerType::Event,
+            StandardEventCode::ObjectAdded,
+            0,
+            &[42],
+        );
+        script.reply(ContainerType::Response, StandardResponseCode::Ok, 1, &[]);
+        let mut camera = AsyncCamera::with_transport(script);
+
+        block_on(camera.open_session()).unwrap();
+        block_on(camera.command(StandardCommandCode::GetStorageIDs, &[], None)).unwrap();
+
+        let events = camera.drain_pending_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].params, vec![42]);
+        let tids: Vec<u32> = camera.transport().written.iter().map(|c| c.tid).collect();
+        assert_eq!(tids, vec![0, 1]);
+    }
+
+    #[test]
+    fn open_session_after_device_info() {
+        let mut script = Script::default();
+        script.reply(ContainerType::Response, StandardResponseCode::Ok, 0, &[]);
+        script.reply(ContainerType::Response, StandardResponseCode::Ok, 0, &[]);
+        let mut camera = AsyncCamera::with_transport(script);
+        camera.set_session_id(7);
+
+        block_on(camera.command(StandardCommandCode::GetDeviceInfo, &[], None)).unwrap();
+        block_on(camera.open_session()).unwrap();
+
+        let open = &camera.transport().written[1];
+        assert_eq!(open.code, StandardCommandCode::OpenSession);
+        assert_eq!(open.tid, 0);
+    }
+
+    #[test]
+    fn zlp_after_exact_chunk() {
+        // the first transfer fills the receive buffer, the rest is exactly one chunk and
+        // so ends with a zero-length packet
+        let len = RECEIVE_BUFFER_SIZE - CONTAINER_INFO_SIZE + CHUNK_SIZE;
+        let mut data = vec![];
+        ContainerInfo {
+            payload_len: len,
+            kind: ContainerType::Data,
+            code: StandardCommandCode::GetObject,
+            tid: 0,
+        }
+        .write(&mut data);
+        data.resize(CONTAINER_INFO_SIZE + len, 0xAB);
+
+        let mut script = Script::default();
+        script
+            .replies
+            .push_back(data[..RECEIVE_BUFFER_SIZE].to_vec());
+        script
+            .replies
+            .push_back(data[RECEIVE_BUFFER_SIZE..].to_vec());
+        script.replies.push_back(vec![]);
+        script.reply(ContainerType::Response, StandardResponseCode::Ok, 0, &[]);
+        script.reply(ContainerType::Response, StandardResponseCode::Ok, 1, &[]);
+        let mut camera = AsyncCamera::with_transport(script);
+
+        assert_eq!(block_on(camera.get_object(1)).unwrap().len(), len);
+        block_on(camera.close_session()).unwrap();
+    }
+
+    #[test]
+    fn data_phase_over_the_limit() {
+        let mut script = Script::default();
+        script.reply(
+            ContainerType::Data,
+            StandardCommandCode::GetObject,
+            0,
+            &[0; 5],
+        );
+        let mut camera = AsyncCamera::with_transport(script);
+        camera.set_max_data_len(16);
+
+        assert!(matches!(
+            block_on(camera.get_object(1)),
+            Err(Error::Malformed(_))
+        ));
+    }
+}
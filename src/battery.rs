@@ -0,0 +1,58 @@
+use super::{
+    Camera, DataType, Error, FormData, SonyPropCode, StandardPropertyCode, Transport,
+    VendorExtension,
+};
+use std::time::Duration;
+
+/// Power state of the camera, see `Camera::battery_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatteryStatus {
+    /// Charge left, 0 to 100.
+    pub percent: u8,
+    /// Whether the camera runs off a USB or AC supply, for the vendors reporting it.
+    pub external_power: Option<bool>,
+}
+
+// place `value` within the form of BatteryLevel, which is a Range or an Enumeration of
+// levels with 0 for empty; without a form the value is taken as a percentage
+fn battery_percent(value: &DataType, form: &FormData) -> Option<u8> {
+    let value = value.to_i128()?;
+    let levels: Vec<i128> = form.iter().filter_map(|v| v.to_i128()).collect();
+    match (levels.iter().min(), levels.iter().max()) {
+        (Some(&min), Some(&max)) if max > min => {
+            Some(((value.clamp(min, max) - min) * 100 / (max - min)) as u8)
+        }
+        _ => Some(value.clamp(0, 100) as u8),
+    }
+}
+
+/// Sony BatteryLevel reading when running off external power.
+const SONY_BATTERY_EXTERNAL_POWER: i8 = -1;
+
+impl<T: Transport> Camera<T> {
+    /// Charge left in percent, from the standard BatteryLevel property.
+    pub fn battery_level(&mut self, timeout: Option<Duration>) -> Result<u8, Error> {
+        let info = self.get_device_prop_desc(StandardPropertyCode::BatteryLevel, timeout)?;
+        battery_percent(&info.current, &info.form).ok_or_else(|| {
+            Error::Malformed(format!("BatteryLevel {:?} is not an integer", info.current))
+        })
+    }
+
+    /// Like `battery_level`, adding what the vendor extension tells about the power
+    /// source. Only Sony bodies report it, through their own BatteryLevel property.
+    pub fn battery_status(&mut self, timeout: Option<Duration>) -> Result<BatteryStatus, Error> {
+        let mut status = BatteryStatus {
+            percent: self.battery_level(timeout)?,
+            external_power: None,
+        };
+        if self.get_device_info(timeout)?.vendor_extension() == VendorExtension::Sony {
+            let level = self
+                .sony_get_all_device_prop_data(timeout)?
+                .into_iter()
+                .find(|p| p.property_code == SonyPropCode::BatteryLevel)
+                .and_then(|p| p.current.as_i8());
+            status.external_power = level.map(|l| l == SONY_BATTERY_EXTERNAL_POWER);
+        }
+        Ok(status)
+    }
+}
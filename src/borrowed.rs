@@ -0,0 +1,204 @@
+use super::{DeviceInfo, Error, ObjectInfo, Read};
+use byteorder::{ByteOrder, LittleEndian};
+use std::fmt;
+use std::io::Cursor;
+
+/// A PTP string borrowed from the dataset it was read from.
+///
+/// PTP strings are UTF-16 on the wire, so they can't be borrowed as `&str`; the code
+/// units are kept as-is and only decoded when asked for.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct PtpStr<'a>(&'a [u8]);
+
+impl<'a> PtpStr<'a> {
+    /// The little-endian UTF-16 code units, without the terminator.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn units(&self) -> impl Iterator<Item = u16> + 'a {
+        self.0.chunks_exact(2).map(LittleEndian::read_u16)
+    }
+
+    pub fn to_string(&self) -> Result<String, Error> {
+        String::from_utf16(&self.units().collect::<Vec<_>>())
+            .map_err(|_| Error::Malformed(format!("Invalid UTF16 data: {:?}", self)))
+    }
+
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(&self.units().collect::<Vec<_>>())
+    }
+
+    /// Compare with `s` without allocating.
+    pub fn eq_str(&self, s: &str) -> bool {
+        self.units().eq(s.encode_utf16())
+    }
+}
+
+impl fmt::Debug for PtpStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.to_string_lossy())
+    }
+}
+
+fn read_ptp_str_ref<'a>(cur: &mut Cursor<&'a [u8]>) -> Result<PtpStr<'a>, Error> {
+    let len = cur.read_ptp_u8()? as usize;
+    if len == 0 {
+        return Ok(PtpStr(&[]));
+    }
+    // len includes the trailing null u16
+    let buf: &'a [u8] = cur.get_ref();
+    let start = cur.position() as usize;
+    let end = start + len * 2;
+    if end > buf.len() {
+        return Err(Error::Malformed("Unexpected end of message".to_string()));
+    }
+    cur.set_position(end as u64);
+    Ok(PtpStr(&buf[start..end - 2]))
+}
+
+/// ObjectInfo borrowing its strings from the received dataset, avoiding the String
+/// allocations of `ObjectInfo::decode` when enumerating many objects.
+#[allow(non_snake_case)]
+#[derive(Debug, Clone)]
+pub struct ObjectInfoRef<'a> {
+    pub StorageID: u32,
+    pub ObjectFormat: u16,
+    pub ProtectionStatus: u16,
+    pub ObjectCompressedSize: u32,
+    pub ThumbFormat: u16,
+    pub ThumbCompressedSize: u32,
+    pub ThumbPixWidth: u32,
+    pub ThumbPixHeight: u32,
+    pub ImagePixWidth: u32,
+    pub ImagePixHeight: u32,
+    pub ImageBitDepth: u32,
+    pub ParentObject: u32,
+    pub AssociationType: u16,
+    pub AssociationDesc: u32,
+    pub SequenceNumber: u32,
+    pub Filename: PtpStr<'a>,
+    pub CaptureDate: PtpStr<'a>,
+    pub ModificationDate: PtpStr<'a>,
+    pub Keywords: PtpStr<'a>,
+}
+
+impl<'a> ObjectInfoRef<'a> {
+    pub fn decode(buf: &'a [u8]) -> Result<ObjectInfoRef<'a>, Error> {
+        let mut cur = Cursor::new(buf);
+
+        Ok(ObjectInfoRef {
+            StorageID: cur.read_ptp_u32()?,
+            ObjectFormat: cur.read_ptp_u16()?,
+            ProtectionStatus: cur.read_ptp_u16()?,
+            ObjectCompressedSize: cur.read_ptp_u32()?,
+            ThumbFormat: cur.read_ptp_u16()?,
+            ThumbCompressedSize: cur.read_ptp_u32()?,
+            ThumbPixWidth: cur.read_ptp_u32()?,
+            ThumbPixHeight: cur.read_ptp_u32()?,
+            ImagePixWidth: cur.read_ptp_u32()?,
+            ImagePixHeight: cur.read_ptp_u32()?,
+            ImageBitDepth: cur.read_ptp_u32()?,
+            ParentObject: cur.read_ptp_u32()?,
+            AssociationType: cur.read_ptp_u16()?,
+            AssociationDesc: cur.read_ptp_u32()?,
+            SequenceNumber: cur.read_ptp_u32()?,
+            Filename: read_ptp_str_ref(&mut cur)?,
+            CaptureDate: read_ptp_str_ref(&mut cur)?,
+            ModificationDate: read_ptp_str_ref(&mut cur)?,
+            Keywords: read_ptp_str_ref(&mut cur)?,
+        })
+    }
+
+    /// Decode the strings into an owned ObjectInfo.
+    pub fn to_object_info(&self) -> Result<ObjectInfo, Error> {
+        Ok(ObjectInfo {
+            StorageID: self.StorageID,
+            ObjectFormat: self.ObjectFormat,
+            ProtectionStatus: self.ProtectionStatus,
+            ObjectCompressedSize: self.ObjectCompressedSize,
+            ThumbFormat: self.ThumbFormat,
+            ThumbCompressedSize: self.ThumbCompressedSize,
+            ThumbPixWidth: self.ThumbPixWidth,
+            ThumbPixHeight: self.ThumbPixHeight,
+            ImagePixWidth: self.ImagePixWidth,
+            ImagePixHeight: self.ImagePixHeight,
+            ImageBitDepth: self.ImageBitDepth,
+            ParentObject: self.ParentObject,
+            AssociationType: self.AssociationType,
+            AssociationDesc: self.AssociationDesc,
+            SequenceNumber: self.SequenceNumber,
+            Filename: self.Filename.to_string()?,
+            CaptureDate: self.CaptureDate.to_string()?,
+            ModificationDate: self.ModificationDate.to_string()?,
+            Keywords: self.Keywords.to_string()?,
+        })
+    }
+}
+
+/// DeviceInfo borrowing its strings from the received dataset.
+#[allow(non_snake_case)]
+#[derive(Debug, Clone)]
+pub struct DeviceInfoRef<'a> {
+    pub Version: u16,
+    pub VendorExID: u32,
+    pub VendorExVersion: u16,
+    pub VendorExtensionDesc: PtpStr<'a>,
+    pub FunctionalMode: u16,
+    pub OperationsSupported: Vec<u16>,
+    pub EventsSupported: Vec<u16>,
+    pub DevicePropertiesSupported: Vec<u16>,
+    pub CaptureFormats: Vec<u16>,
+    pub ImageFormats: Vec<u16>,
+    pub Manufacturer: PtpStr<'a>,
+    pub Model: PtpStr<'a>,
+    pub DeviceVersion: PtpStr<'a>,
+    pub SerialNumber: PtpStr<'a>,
+}
+
+impl<'a> DeviceInfoRef<'a> {
+    pub fn decode(buf: &'a [u8]) -> Result<DeviceInfoRef<'a>, Error> {
+        let mut cur = Cursor::new(buf);
+
+        Ok(DeviceInfoRef {
+            Version: cur.read_ptp_u16()?,
+            VendorExID: cur.read_ptp_u32()?,
+            VendorExVersion: cur.read_ptp_u16()?,
+            VendorExtensionDesc: read_ptp_str_ref(&mut cur)?,
+            FunctionalMode: cur.read_ptp_u16()?,
+            OperationsSupported: cur.read_ptp_u16_vec()?,
+            EventsSupported: cur.read_ptp_u16_vec()?,
+            DevicePropertiesSupported: cur.read_ptp_u16_vec()?,
+            CaptureFormats: cur.read_ptp_u16_vec()?,
+            ImageFormats: cur.read_ptp_u16_vec()?,
+            Manufacturer: read_ptp_str_ref(&mut cur)?,
+            Model: read_ptp_str_ref(&mut cur)?,
+            DeviceVersion: read_ptp_str_ref(&mut cur)?,
+            SerialNumber: read_ptp_str_ref(&mut cur)?,
+        })
+    }
+
+    /// Decode the strings into an owned DeviceInfo.
+    pub fn to_device_info(&self) -> Result<DeviceInfo, Error> {
+        Ok(DeviceInfo {
+            Version: self.Version,
+            VendorExID: self.VendorExID,
+            VendorExVersion: self.VendorExVersion,
+            VendorExtensionDesc: self.VendorExtensionDesc.to_string()?,
+            FunctionalMode: self.FunctionalMode,
+            OperationsSupported: self.OperationsSupported.clone(),
+            EventsSupported: self.EventsSupported.clone(),
+            DevicePropertiesSupported: self.DevicePropertiesSupported.clone(),
+            CaptureFormats: self.CaptureFormats.clone(),
+            ImageFormats: self.ImageFormats.clone(),
+            Manufacturer: self.Manufacturer.to_string()?,
+            Model: self.Model.to_string()?,
+            DeviceVersion: self.DeviceVersion.to_string()?,
+            SerialNumber: self.SerialNumber.to_string()?,
+        })
+    }
+}
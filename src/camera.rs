@@ -1,53 +1,624 @@
+use super::container::{ContainerInfo, ContainerReader, ContainerType, Phase, CONTAINER_INFO_SIZE};
 use super::{
-    CommandCode, DeviceInfo, Error, ObjectInfo, Read, StandardCommandCode, StandardResponseCode,
-    StorageInfo,
+    capture_date_in_range, has_data_in, is_destructive, lookup_quirks, AssociationType, CameraFs,
+    CancelToken, CommandCode, Danger, DataType, DatasetReader, DeviceInfo, DeviceStatus,
+    EditSession, Error, EventCode, MtpCommandCode, MtpObjectPropCode, ObjectInfo, ObjectQuery,
+    ObjectTree, Objects, PropInfo, PropertyCode, ProtectionStatus, PtpEvent, Quirks, Read,
+    ResponseCode, StandardCommandCode, StandardEventCode, StandardObjectFormatCode,
+    StandardResponseCode, StorageInfo, Transport, UsbTransport, MAX_ARRAY_LEN,
 };
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use rusb::{constants, UsbContext};
-use std::sync::{Arc, RwLock};
-use std::{cmp::min, io::Cursor, slice, time::Duration};
-
-pub struct Camera<T: UsbContext> {
-    iface: u8,
-    ep_in: u8,
-    ep_out: u8,
-    _ep_int: u8,
+use byteorder::{LittleEndian, WriteBytesExt};
+use rusb::UsbContext;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::{
+    cmp::{max, min},
+    io,
+    io::Cursor,
+    thread,
+};
+
+/// Smallest bulk transfer size of a `Camera`, one high-speed packet. Smaller chunks
+/// couldn't hold the header of a container.
+pub const MIN_CHUNK_SIZE: usize = 512;
+
+/// Default limit on the length of a data phase, 2GB. Larger objects are downloaded in
+/// windows with `ResumableDownload`, or the limit raised.
+pub const MAX_DATA_LEN: usize = 0x8000_0000;
+
+/// Tunables of a `Camera`, see `Camera::with_config` and `CameraBuilder`.
+#[derive(Debug, Clone)]
+pub struct CameraConfig {
+    /// Size of the bulk transfers used for large data phases, 1MB by default.
+    /// Must be a multiple of the endpoint packet size, and at least `MIN_CHUNK_SIZE`;
+    /// smaller values are raised to it.
+    pub chunk_size: usize,
+    /// Size of the buffer receiving the first transfer of each phase, 8KB by default.
+    /// Large enough for most cmd/ctrl data, larger media payloads are read in chunks.
+    /// Must be a multiple of the endpoint packet size.
+    pub receive_buffer_size: usize,
+    /// Recover stalled pipes automatically, see `Camera::set_auto_recover`.
+    pub auto_recover: bool,
+    /// Retry a transaction after recovery, see `Camera::set_retry_after_recovery`.
+    pub retry_after_recovery: bool,
+    /// Timeout used when a method is passed `None`, unlimited if unset.
+    pub timeout: Option<Duration>,
+    /// Overrides `timeout` for object transfers (GetObject, SendObject, ...), which may
+    /// legitimately take much longer than other operations.
+    pub transfer_timeout: Option<Duration>,
+    /// Overrides `timeout` for class control requests (cancel, recovery, device status).
+    pub control_timeout: Option<Duration>,
+    /// Overrides the timeout of a transaction for individual phases.
+    pub phase_timeouts: PhaseTimeouts,
+    /// Decode strings in datasets leniently, see `DatasetReader`. Disabled by default.
+    pub lenient_strings: bool,
+    /// Largest array accepted in a dataset, `MAX_ARRAY_LEN` by default.
+    pub max_array_len: usize,
+    /// Largest data phase accepted from the device, `MAX_DATA_LEN` by default. Larger
+    /// ones fail with `Error::Malformed` before their payload is read.
+    pub max_data_len: usize,
+    /// Fail operations missing from the OperationsSupported of the DeviceInfo with
+    /// `Error::NotAdvertised` instead of sending them. Only applies once the DeviceInfo
+    /// was fetched with `get_device_info`. Disabled by default.
+    pub check_operations: bool,
+    /// Fail the operations that change the contents or settings of the device with
+    /// `Error::ReadOnly` instead of sending them, see `is_destructive`. Disabled by default.
+    pub read_only: bool,
+}
+
+/// Timeouts of the individual phases of a transaction, unset phases use the timeout of
+/// the whole transaction.
+///
+/// Waiting for the device to start the next container, e.g. the response to a capture,
+/// counts as the response phase; `data` covers the remaining bulk chunks of a data phase
+/// and the data sent to the device.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTimeouts {
+    pub command: Option<Duration>,
+    pub data: Option<Duration>,
+    pub response: Option<Duration>,
+}
+
+impl PhaseTimeouts {
+    /// The same timeout for every phase.
+    pub fn uniform(timeout: Option<Duration>) -> PhaseTimeouts {
+        PhaseTimeouts {
+            command: timeout,
+            data: timeout,
+            response: timeout,
+        }
+    }
+}
+
+// PhaseTimeouts resolved against the CameraConfig, 0 means unlimited
+#[derive(Clone, Copy)]
+struct PhaseDurations {
+    command: Duration,
+    data: Duration,
+    response: Duration,
+}
+
+impl Default for CameraConfig {
+    fn default() -> CameraConfig {
+        CameraConfig {
+            chunk_size: 1024 * 1024,
+            receive_buffer_size: 8 * 1024,
+            auto_recover: true,
+            retry_after_recovery: false,
+            timeout: None,
+            transfer_timeout: None,
+            control_timeout: None,
+            phase_timeouts: PhaseTimeouts::default(),
+            lenient_strings: false,
+            max_array_len: MAX_ARRAY_LEN,
+            max_data_len: MAX_DATA_LEN,
+            check_operations: false,
+            read_only: false,
+        }
+    }
+}
+
+impl CameraConfig {
+    fn is_transfer(code: CommandCode) -> bool {
+        code == StandardCommandCode::GetObject
+            || code == StandardCommandCode::GetThumb
+            || code == StandardCommandCode::GetPartialObject
+            || code == StandardCommandCode::SendObject
+            || code == MtpCommandCode::GetPartialObject64
+            || code == MtpCommandCode::SendPartialObject
+    }
+
+    // the timeouts for a transaction, those given per call, or else the configured phase
+    // timeouts, or else the configured default of the operation
+    fn phase_durations(&self, code: CommandCode, timeouts: PhaseTimeouts) -> PhaseDurations {
+        let default = if Self::is_transfer(code) {
+            self.transfer_timeout.or(self.timeout)
+        } else {
+            self.timeout
+        };
+        // timeout of 0 means unlimited timeout.
+        let resolve = |call: Option<Duration>, config: Option<Duration>| {
+            call.or(config).or(default).unwrap_or_default()
+        };
+        PhaseDurations {
+            command: resolve(timeouts.command, self.phase_timeouts.command),
+            data: resolve(timeouts.data, self.phase_timeouts.data),
+            response: resolve(timeouts.response, self.phase_timeouts.response),
+        }
+    }
+
+    fn control_timeout(&self, timeout: Option<Duration>) -> Duration {
+        timeout
+            .or(self.control_timeout)
+            .or(self.timeout)
+            .unwrap_or_default()
+    }
+}
+
+/// Builds a `Camera` from a `CameraConfig` set up once, so per-call timeouts can be left
+/// as `None`, e.g. `CameraBuilder::new().timeout(Duration::from_secs(5)).open(&device)`.
+#[derive(Debug, Clone, Default)]
+pub struct CameraBuilder {
+    config: CameraConfig,
+}
+
+impl CameraBuilder {
+    pub fn new() -> CameraBuilder {
+        CameraBuilder::default()
+    }
+
+    /// Default timeout of every operation.
+    pub fn timeout(mut self, timeout: Duration) -> CameraBuilder {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Default timeout of object transfers, falls back to `timeout`.
+    pub fn transfer_timeout(mut self, timeout: Duration) -> CameraBuilder {
+        self.config.transfer_timeout = Some(timeout);
+        self
+    }
+
+    /// Per-phase timeouts of every transaction, see `PhaseTimeouts`.
+    pub fn phase_timeouts(mut self, timeouts: PhaseTimeouts) -> CameraBuilder {
+        self.config.phase_timeouts = timeouts;
+        self
+    }
+
+    /// Default timeout of class control requests, falls back to `timeout`.
+    pub fn control_timeout(mut self, timeout: Duration) -> CameraBuilder {
+        self.config.control_timeout = Some(timeout);
+        self
+    }
+
+    /// Size of the bulk transfers of large data phases, raised to `MIN_CHUNK_SIZE` if
+    /// smaller.
+    pub fn chunk_size(mut self, chunk_size: usize) -> CameraBuilder {
+        self.config.chunk_size = max(chunk_size, MIN_CHUNK_SIZE);
+        self
+    }
+
+    pub fn receive_buffer_size(mut self, size: usize) -> CameraBuilder {
+        self.config.receive_buffer_size = size;
+        self
+    }
+
+    pub fn auto_recover(mut self, auto_recover: bool) -> CameraBuilder {
+        self.config.auto_recover = auto_recover;
+        self
+    }
+
+    pub fn retry_after_recovery(mut self, retry: bool) -> CameraBuilder {
+        self.config.retry_after_recovery = retry;
+        self
+    }
+
+    pub fn lenient_strings(mut self, lenient: bool) -> CameraBuilder {
+        self.config.lenient_strings = lenient;
+        self
+    }
+
+    pub fn max_array_len(mut self, max: usize) -> CameraBuilder {
+        self.config.max_array_len = max;
+        self
+    }
+
+    pub fn max_data_len(mut self, max: usize) -> CameraBuilder {
+        self.config.max_data_len = max;
+        self
+    }
+
+    pub fn check_operations(mut self, check: bool) -> CameraBuilder {
+        self.config.check_operations = check;
+        self
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> CameraBuilder {
+        self.config.read_only = read_only;
+        self
+    }
+
+    pub fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+
+    pub fn build<T: Transport>(self, transport: T) -> Camera<T> {
+        Camera::with_config(transport, self.config)
+    }
+
+    /// Open the still image interface of `device`.
+    pub fn open<C: UsbContext>(
+        self,
+        device: &rusb::Device<C>,
+    ) -> Result<Camera<UsbTransport<C>>, Error> {
+        Ok(self.build(UsbTransport::new(device)?))
+    }
+}
+
+/// Most memory reserved up front for a payload received into a `Vec`.
+const MAX_RESERVE: usize = 16 * 1024 * 1024;
+
+/// Destination of an incoming data phase.
+pub(crate) trait DataSink {
+    /// Called once with the announced payload size before any data arrives.
+    fn reserve(&mut self, _len: usize) {}
+
+    fn write_chunk(&mut self, chunk: &[u8]) -> Result<(), Error>;
+}
+
+impl DataSink for Vec<u8> {
+    fn reserve(&mut self, len: usize) {
+        // the length is announced by the device, larger payloads grow as they arrive
+        self.reserve_exact(min(len, MAX_RESERVE));
+    }
+
+    fn write_chunk(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(chunk);
+        Ok(())
+    }
+}
+
+/// Adapts an `io::Write` to a DataSink.
+pub(crate) struct WriteSink<'a, W: io::Write + ?Sized>(pub &'a mut W);
+
+impl<W: io::Write + ?Sized> DataSink for WriteSink<'_, W> {
+    fn write_chunk(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        Ok(self.0.write_all(chunk)?)
+    }
+}
+
+/// Fills a caller-provided buffer.
+struct SliceSink<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl DataSink for SliceSink<'_> {
+    fn write_chunk(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        let end = self.len + chunk.len();
+        if end > self.buf.len() {
+            return Err(Error::Malformed(format!(
+                "Data phase exceeds the {} bytes buffer",
+                self.buf.len()
+            )));
+        }
+        self.buf[self.len..end].copy_from_slice(chunk);
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Reports the progress of the data phase flowing into another sink.
+struct ProgressSink<'a, F: FnMut(u64, u64)> {
+    inner: &'a mut dyn DataSink,
+    done: u64,
+    total: u64,
+    progress: F,
+}
+
+impl<F: FnMut(u64, u64)> DataSink for ProgressSink<'_, F> {
+    fn reserve(&mut self, len: usize) {
+        // the data phase length is exact, unlike a size from ObjectInfo capped at 4GB
+        self.total = len as u64;
+        self.inner.reserve(len);
+    }
+
+    fn write_chunk(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        self.inner.write_chunk(chunk)?;
+        self.done += chunk.len() as u64;
+        (self.progress)(self.done, self.total);
+        Ok(())
+    }
+}
+
+/// Result of deleting one object with `Camera::delete_objects`.
+#[derive(Debug)]
+pub enum DeleteOutcome {
+    Deleted,
+    /// Only part of the object was deleted, e.g. a folder with protected files in it
+    /// (PartialDeletion response).
+    Partial,
+    /// Nothing was deleted because the object is protected.
+    WriteProtected,
+    Failed(Error),
+}
+
+/// Called before each transaction with the operation code and parameters.
+/// Returning an error aborts the transaction with that error.
+pub type PreCommandHook = Box<dyn FnMut(CommandCode, &[u32]) -> Result<(), Error> + Send>;
+
+/// Called after each transaction with the operation code, parameters, the time it took
+/// and either the response code or the error that ended it.
+pub type PostCommandHook =
+    Box<dyn FnMut(CommandCode, &[u32], Duration, Result<ResponseCode, &Error>) + Send>;
+
+/// Remembers whether any data reached the wrapped sink.
+struct TrackedSink<'a> {
+    inner: &'a mut dyn DataSink,
+    touched: bool,
+}
+
+impl DataSink for TrackedSink<'_> {
+    fn reserve(&mut self, len: usize) {
+        self.inner.reserve(len);
+    }
+
+    fn write_chunk(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        self.touched |= !chunk.is_empty();
+        self.inner.write_chunk(chunk)
+    }
+}
+
+pub struct Camera<T: Transport> {
     current_tid: u32,
-    handle: Arc<RwLock<rusb::DeviceHandle<T>>>,
+    transport: T,
+    pending_events: Vec<PtpEvent>,
+    session_id: u32,
+    session_open: bool,
+    released: bool,
+    pre_command_hooks: Vec<PreCommandHook>,
+    post_command_hooks: Vec<PostCommandHook>,
+    config: CameraConfig,
+    rx_buf: Vec<u8>,
+    chunk_buf: Vec<u8>,
+    cancel: Option<CancelToken>,
+    quirks: Quirks,
+    device_info: Option<DeviceInfo>,
 }
 
-impl<T: UsbContext> Camera<T> {
-    pub fn new(device: &rusb::Device<T>) -> Result<Camera<T>, Error> {
-        let config_desc = device.active_config_descriptor()?;
+impl<C: UsbContext> Camera<UsbTransport<C>> {
+    /// Open `device`, applying the quirks the quirks database has for it.
+    pub fn new(device: &rusb::Device<C>) -> Result<Camera<UsbTransport<C>>, Error> {
+        let mut camera = Camera::with_transport(UsbTransport::new(device)?);
+        let desc = device.device_descriptor()?;
+        let model = camera
+            .transport
+            .handle()
+            .read_product_string_ascii(&desc)
+            .ok();
+        let quirks = lookup_quirks(desc.vendor_id(), desc.product_id(), model.as_deref());
+        if quirks != Quirks::default() {
+            debug!("applying quirks {:?}", quirks);
+        }
+        camera.set_quirks(quirks);
+        Ok(camera)
+    }
 
-        let interface_desc = config_desc
-            .interfaces()
-            .flat_map(|i| i.descriptors())
-            .find(|x| x.class_code() == constants::LIBUSB_CLASS_IMAGE)
-            .ok_or(rusb::Error::NotFound)?;
+    pub fn reset(&mut self) -> Result<(), Error> {
+        self.transport.reset()
+    }
 
-        debug!("Found interface {}", interface_desc.interface_number());
+    pub fn clear_halt(&mut self) -> Result<(), Error> {
+        self.transport.clear_halt()
+    }
 
-        let mut handle = device.open()?;
+    pub fn get_device_status(&mut self, timeout: Option<Duration>) -> Result<DeviceStatus, Error> {
+        let timeout = self.config.control_timeout(timeout);
+        self.transport.get_device_status(timeout)
+    }
 
-        handle.claim_interface(interface_desc.interface_number())?;
+    /// Class-level Device Reset, unlike `reset` this doesn't re-enumerate the device.
+    pub fn device_reset(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        let timeout = self.config.control_timeout(timeout);
+        self.transport.device_reset(timeout)
+    }
 
-        let find_endpoint = |direction, transfer_type| {
-            interface_desc
-                .endpoint_descriptors()
-                .find(|ep| ep.direction() == direction && ep.transfer_type() == transfer_type)
-                .map(|x| x.address())
-                .ok_or(rusb::Error::NotFound)
+    /// Issue ResetDevice and wait up to `wait` for the device to come back on the bus,
+    /// returning a freshly opened camera. The device is looked up by vendor/product id
+    /// and by `serial`, which defaults to the serial number of the current device.
+    pub fn reset_device_and_reopen(
+        mut self,
+        serial: Option<&str>,
+        wait: Duration,
+        timeout: Option<Duration>,
+    ) -> Result<Camera<UsbTransport<C>>, Error> {
+        let device = self.transport.handle().device();
+        let desc = device.device_descriptor()?;
+        let serial = match serial {
+            Some(serial) => Some(serial.to_owned()),
+            None => self
+                .transport
+                .handle()
+                .read_serial_number_string_ascii(&desc)
+                .ok(),
         };
+        let context = self.transport.handle().context().clone();
+        let config = self.config.clone();
+
+        self.reset_device(timeout)?;
+        drop(self);
+
+        let start = Instant::now();
+        while start.elapsed() < wait {
+            thread::sleep(Duration::from_millis(250));
+            for candidate in context.devices()?.iter() {
+                let candidate_desc = match candidate.device_descriptor() {
+                    Ok(d) => d,
+                    Err(_) => continue,
+                };
+                if candidate_desc.vendor_id() != desc.vendor_id()
+                    || candidate_desc.product_id() != desc.product_id()
+                {
+                    continue;
+                }
+                let camera = match UsbTransport::new(&candidate) {
+                    Ok(transport) => Camera::with_config(transport, config.clone()),
+                    Err(_) => continue,
+                };
+                let candidate_serial = camera
+                    .transport
+                    .handle()
+                    .read_serial_number_string_ascii(&candidate_desc)
+                    .ok();
+                if serial.is_none() || candidate_serial == serial {
+                    return Ok(camera);
+                }
+            }
+        }
+
+        Err(rusb::Error::NoDevice.into())
+    }
+}
 
-        Ok(Camera {
-            iface: interface_desc.interface_number(),
-            ep_in: find_endpoint(rusb::Direction::In, rusb::TransferType::Bulk)?,
-            ep_out: find_endpoint(rusb::Direction::Out, rusb::TransferType::Bulk)?,
-            _ep_int: find_endpoint(rusb::Direction::In, rusb::TransferType::Interrupt)?,
+impl<T: Transport> Camera<T> {
+    pub fn with_transport(transport: T) -> Camera<T> {
+        Camera::with_config(transport, CameraConfig::default())
+    }
+
+    pub fn with_config(transport: T, config: CameraConfig) -> Camera<T> {
+        Camera {
             current_tid: 0,
-            handle: Arc::new(RwLock::new(handle)),
-        })
+            transport,
+            pending_events: vec![],
+            session_id: 1,
+            session_open: false,
+            released: false,
+            pre_command_hooks: vec![],
+            post_command_hooks: vec![],
+            config,
+            rx_buf: vec![],
+            chunk_buf: vec![],
+            cancel: None,
+            quirks: Quirks::default(),
+            device_info: None,
+        }
+    }
+
+    pub fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: CameraConfig) {
+        self.config = config;
+    }
+
+    // the configured chunk size, which a hand-built config may have left too small
+    fn chunk_size(&self) -> usize {
+        max(self.config.chunk_size, MIN_CHUNK_SIZE)
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Work around the deviations of the device, see `Quirks`. A session id it requires
+    /// replaces the one set with `set_session_id`.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        if let Some(session_id) = quirks.session_id {
+            self.session_id = session_id;
+        }
+        self.quirks = quirks;
+    }
+
+    /// Whether a stalled pipe is recovered automatically (via `Transport::recover`) before
+    /// the error is returned. Enabled by default.
+    pub fn set_auto_recover(&mut self, auto_recover: bool) {
+        self.config.auto_recover = auto_recover;
+    }
+
+    /// Whether a transaction is retried once after a successful recovery, provided none
+    /// of its data was received yet. Disabled by default.
+    pub fn set_retry_after_recovery(&mut self, retry: bool) {
+        self.config.retry_after_recovery = retry;
+    }
+
+    /// Register a hook run before every transaction, e.g. for logging or rate-limiting.
+    pub fn add_pre_command_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(CommandCode, &[u32]) -> Result<(), Error> + Send + 'static,
+    {
+        self.pre_command_hooks.push(Box::new(hook));
+    }
+
+    /// Register a hook run after every transaction, e.g. for metrics or audit trails.
+    pub fn add_post_command_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(CommandCode, &[u32], Duration, Result<ResponseCode, &Error>) + Send + 'static,
+    {
+        self.post_command_hooks.push(Box::new(hook));
+    }
+
+    pub fn clear_command_hooks(&mut self) {
+        self.pre_command_hooks.clear();
+        self.post_command_hooks.clear();
+    }
+
+    /// Check `cancel` between the bulk chunks of every following transaction, aborting
+    /// it with `Error::Cancelled` once the token is cancelled. `None` removes the token.
+    pub fn set_cancel_token(&mut self, cancel: Option<CancelToken>) {
+        self.cancel = cancel;
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    // run `f` with `cancel` in place of the camera's own token
+    fn with_cancel<R>(
+        &mut self,
+        cancel: &CancelToken,
+        f: impl FnOnce(&mut Self) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let previous = self.cancel.replace(cancel.clone());
+        let result = f(self);
+        self.cancel = previous;
+        result
+    }
+
+    // reader for datasets received from the device, honouring the decoding options
+    fn dataset_reader<D: AsRef<[u8]>>(&self, data: D) -> DatasetReader<Cursor<D>> {
+        DatasetReader::new(Cursor::new(data))
+            .lenient(self.config.lenient_strings)
+            .max_array_len(self.config.max_array_len)
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// The id of the most recently started transaction, if any.
+    pub fn last_transaction_id(&self) -> Option<u32> {
+        self.current_tid.checked_sub(1)
+    }
+
+    /// Abort transaction `tid`, e.g. a GetObject that timed out, without resetting the device.
+    pub fn cancel_transaction(&mut self, tid: u32, timeout: Option<Duration>) -> Result<(), Error> {
+        debug!("cancelling transaction {}", tid);
+        let timeout = self.config.control_timeout(timeout);
+        self.transport.cancel(tid, timeout)
+    }
+
+    /// Recover from a failed transfer, clearing the endpoints the device reports as stalled.
+    pub fn recover(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        let timeout = self.config.control_timeout(timeout);
+        self.transport.recover(timeout)
     }
 
     /// execute a PTP transaction.
@@ -56,8 +627,10 @@ impl<T: UsbContext> Camera<T> {
     ///  - command data (optional, if `data` is Some)
     ///  - response data (optional, if response contains a payload)
     ///  - response status
+    ///
     /// NB: each phase involves a separate USB transfer, and `timeout` is used for each phase,
-    /// so the total time taken may be greater than `timeout`.
+    /// so the total time taken may be greater than `timeout`. `None` uses the timeout of
+    /// the `CameraConfig`.
     pub fn command(
         &mut self,
         code: CommandCode,
@@ -65,50 +638,296 @@ impl<T: UsbContext> Camera<T> {
         data: Option<&[u8]>,
         timeout: Option<Duration>,
     ) -> Result<Vec<u8>, Error> {
-        // timeout of 0 means unlimited timeout.
-        let timeout = timeout.unwrap_or_else(Duration::default);
+        let (response, _, data) = self.command_full(code, params, data, timeout)?;
+        if response != StandardResponseCode::Ok {
+            return Err(Error::Response(response));
+        }
+        Ok(data)
+    }
+
+    /// execute a PTP transaction like `command`, but return the response code and the
+    /// response parameters alongside the data payload instead of turning a non-Ok
+    /// response into an error.
+    ///
+    /// If the device reports that the session we opened is gone, it is reopened and the
+    /// transaction retried once.
+    pub fn command_full(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        timeout: Option<Duration>,
+    ) -> Result<(ResponseCode, Vec<u32>, Vec<u8>), Error> {
+        let mut data_phase_payload = vec![];
+        let (response, response_params) =
+            self.command_sink(code, params, data, &mut data_phase_payload, timeout)?;
+        Ok((response, response_params, data_phase_payload))
+    }
+
+    /// execute a PTP transaction like `command`, with separate timeouts for its phases,
+    /// e.g. a command phase failing fast and a long response phase for a capture.
+    pub fn command_with_timeouts(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        timeouts: PhaseTimeouts,
+    ) -> Result<Vec<u8>, Error> {
+        let mut data_phase_payload = vec![];
+        let (response, _) =
+            self.command_phases(code, params, data, &mut data_phase_payload, timeouts)?;
+        if response != StandardResponseCode::Ok {
+            return Err(Error::Response(response));
+        }
+        Ok(data_phase_payload)
+    }
+
+    /// execute a PTP transaction like `command`, but receive the data phase into `buf`
+    /// instead of a new `Vec`, returning its length. Fails if `buf` is too small.
+    pub fn command_into(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<usize, Error> {
+        let mut sink = SliceSink { buf, len: 0 };
+        let (response, _) = self.command_sink(code, params, data, &mut sink, timeout)?;
+        if response != StandardResponseCode::Ok {
+            return Err(Error::Response(response));
+        }
+        Ok(sink.len)
+    }
+
+    /// execute a PTP transaction like `command`, aborting it once `cancel` is cancelled.
+    pub fn command_with_cancel(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        cancel: &CancelToken,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, Error> {
+        self.with_cancel(cancel, |camera| camera.command(code, params, data, timeout))
+    }
+
+    // command_full() streaming the data phase into `sink`
+    fn command_sink(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        sink: &mut dyn DataSink,
+        timeout: Option<Duration>,
+    ) -> Result<(ResponseCode, Vec<u32>), Error> {
+        let timeouts = PhaseTimeouts::uniform(timeout);
+        self.command_phases(code, params, data, sink, timeouts)
+    }
+
+    fn command_phases(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        sink: &mut dyn DataSink,
+        timeouts: PhaseTimeouts,
+    ) -> Result<(ResponseCode, Vec<u32>), Error> {
+        let result = self.recovering_transaction(code, params, data, sink, timeouts)?;
+        if result.0 == StandardResponseCode::SessionNotOpen
+            && self.session_open
+            && code != StandardCommandCode::OpenSession
+            && code != StandardCommandCode::CloseSession
+        {
+            debug!(
+                "session {} was closed by the device, reopening",
+                self.session_id
+            );
+            self.session_open = false;
+            self.open_session(timeouts.response)?;
+            return self.transaction(code, params, data, sink, timeouts);
+        }
+        Ok(result)
+    }
+
+    // transaction() recovering from stalled pipes, and retrying once if enabled and
+    // no data reached the sink yet
+    fn recovering_transaction(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        sink: &mut dyn DataSink,
+        timeouts: PhaseTimeouts,
+    ) -> Result<(ResponseCode, Vec<u32>), Error> {
+        let mut tracked = TrackedSink {
+            inner: sink,
+            touched: false,
+        };
+        match self.transaction(code, params, data, &mut tracked, timeouts) {
+            Err(Error::Usb(rusb::Error::Pipe)) if self.config.auto_recover => {
+                debug!("pipe error during transaction, recovering");
+                let recover_timeout = self.config.control_timeout(timeouts.command);
+                if let Err(e) = self.transport.recover(recover_timeout) {
+                    warn!("recovery after pipe error failed: {}", e);
+                    return Err(Error::Usb(rusb::Error::Pipe));
+                }
+                if !self.config.retry_after_recovery || tracked.touched {
+                    return Err(Error::Usb(rusb::Error::Pipe));
+                }
+                self.transaction(code, params, data, tracked.inner, timeouts)
+            }
+            result => result,
+        }
+    }
+
+    // a single command/data/response round trip wrapped in the command hooks,
+    // used by command_sink()
+    fn transaction(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        sink: &mut dyn DataSink,
+        timeouts: PhaseTimeouts,
+    ) -> Result<(ResponseCode, Vec<u32>), Error> {
+        for hook in &mut self.pre_command_hooks {
+            hook(code, params)?;
+        }
+
+        let start = Instant::now();
+        let result = self.run_transaction(code, params, data, sink, timeouts);
+        let elapsed = start.elapsed();
+
+        for hook in &mut self.post_command_hooks {
+            hook(
+                code,
+                params,
+                elapsed,
+                result.as_ref().map(|(response, _)| *response),
+            );
+        }
+        result
+    }
+
+    fn run_transaction(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        sink: &mut dyn DataSink,
+        timeouts: PhaseTimeouts,
+    ) -> Result<(ResponseCode, Vec<u32>), Error> {
+        let timeouts = self.config.phase_durations(code, timeouts);
+
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        if self.config.read_only && is_destructive(code) {
+            return Err(Error::ReadOnly(code));
+        }
+        if self.config.check_operations
+            && code != StandardCommandCode::GetDeviceInfo
+            && code != StandardCommandCode::OpenSession
+            && self.supports_operation(code) == Some(false)
+        {
+            return Err(Error::NotAdvertised(code));
+        }
 
         let tid = self.current_tid;
         self.current_tid += 1;
 
+        match self.run_phases(code, params, data, sink, tid, timeouts) {
+            Err(Error::Cancelled) => {
+                debug!("transaction {} cancelled", tid);
+                let cancel_timeout = self.config.control_timeout(None);
+                if let Err(e) = self.transport.cancel(tid, cancel_timeout) {
+                    warn!("cancelling transaction {} failed: {}", tid, e);
+                }
+                Err(Error::Cancelled)
+            }
+            result => result,
+        }
+    }
+
+    fn run_phases(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        sink: &mut dyn DataSink,
+        tid: u32,
+        timeouts: PhaseDurations,
+    ) -> Result<(ResponseCode, Vec<u32>), Error> {
         // Prepare payload of the request phase, containing the parameters
         let mut request_payload = Vec::with_capacity(params.len() * 4);
         for p in params {
             request_payload.write_u32::<LittleEndian>(*p).ok();
         }
 
-        self.write_txn_phase(ContainerType::Command, code, tid, &request_payload, timeout)?;
+        self.write_txn_phase(
+            ContainerType::Command,
+            code,
+            tid,
+            &request_payload,
+            timeouts.command,
+        )?;
 
         if let Some(data) = data {
-            self.write_txn_phase(ContainerType::Data, code, tid, data, timeout)?;
+            self.write_txn_phase(ContainerType::Data, code, tid, data, timeouts.data)?;
         }
 
         // request phase is followed by data phase (optional) and response phase.
-        // read both, check the status on the response, and return the data payload, if any.
-        let mut data_phase_payload = vec![];
+        // read both, the data payload, if any, goes to the sink.
+        let mut received_data = false;
+        let mut early_response = None;
         loop {
-            let (container, payload) = self.read_txn_phase(timeout)?;
-            if !container.belongs_to(tid) {
-                return Err(Error::Malformed(format!(
-                    "mismatched txnid {}, expecting {}",
-                    container.tid, tid
-                )));
-            }
-            match container.kind {
-                ContainerType::Data => {
-                    data_phase_payload = payload;
+            let (container, payload) = self.read_txn_phase(sink, timeouts)?;
+            match container.phase(&payload, tid)? {
+                // keep interleaved events around for drain_pending_events()
+                Phase::Event(event) => {
+                    debug!("event during transaction {}: {:?}", tid, event);
+                    self.pending_events.push(event);
                 }
-                ContainerType::Response => {
-                    if container.code != StandardResponseCode::Ok {
-                        return Err(Error::Response(container.code));
+                Phase::Data => {
+                    if let Some(response) = early_response.take() {
+                        return Ok(response);
                     }
-                    return Ok(data_phase_payload);
+                    received_data = true;
+                }
+                Phase::Response(response, params) => {
+                    if self.quirks.response_before_data
+                        && response == StandardResponseCode::Ok
+                        && !received_data
+                        && early_response.is_none()
+                        && data.is_none()
+                        && has_data_in(code)
+                    {
+                        debug!("response of transaction {} ahead of its data", tid);
+                        early_response = Some((response, params));
+                        continue;
+                    }
+                    return Ok((response, params));
                 }
-                _ => {}
             }
         }
     }
 
+    // helper for ops returning response parameters, fails on a non-Ok response like `command`
+    fn command_params(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u32>, Error> {
+        let (response, response_params, _) = self.command_full(code, params, data, timeout)?;
+        if response != StandardResponseCode::Ok {
+            return Err(Error::Response(response));
+        }
+        Ok(response_params)
+    }
+
     fn write_txn_phase(
         &mut self,
         kind: ContainerType,
@@ -121,125 +940,686 @@ impl<T: UsbContext> Camera<T> {
             "Write {:?} - 0x{:04x} ({}), tid:{}",
             kind,
             code,
-            StandardCommandCode::name(code).unwrap_or("unknown"),
+            StandardCommandCode::name(code)
+                .or_else(|| MtpCommandCode::name(code))
+                .unwrap_or("unknown"),
             tid
         );
 
-        const CHUNK_SIZE: usize = 1024 * 1024; // 1MB, must be a multiple of the endpoint packet size
+        // The first chunk contains the header, and its payload must be copied into the temporary buffer
+        let chunk_size = self.chunk_size();
+        let first_chunk_payload_bytes = min(payload.len(), chunk_size - CONTAINER_INFO_SIZE);
+        let mut buf = Vec::with_capacity(first_chunk_payload_bytes + CONTAINER_INFO_SIZE);
+        ContainerInfo {
+            payload_len: payload.len(),
+            kind,
+            code,
+            tid,
+        }
+        .write(&mut buf);
+        buf.extend_from_slice(&payload[..first_chunk_payload_bytes]);
+        self.transport.write(&buf, timeout)?;
+
+        // Write any subsequent chunks, straight from the source slice
+        for chunk in payload[first_chunk_payload_bytes..].chunks(chunk_size) {
+            if self.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            self.transport.write(chunk, timeout)?;
+        }
+
+        // 512 is the high-speed bulk packet size, and a multiple of the full-speed one
+        if self.quirks.needs_zlp && (payload.len() + CONTAINER_INFO_SIZE) % 512 == 0 {
+            self.transport.write(&[], timeout)?;
+        }
+
+        Ok(())
+    }
+
+    // helper for command() above, retrieve container info and payload for the current phase.
+    // the payload of a data phase is streamed into `sink`, any other payload is returned.
+    fn read_txn_phase(
+        &mut self,
+        sink: &mut dyn DataSink,
+        timeouts: PhaseDurations,
+    ) -> Result<(ContainerInfo, Vec<u8>), Error> {
+        // buf is intended to be large enough to accomodate most cmd/ctrl data (ie, not media).
+        // payload handling below deals with larger media responses.
+        // the receive buffers live on the Camera and are reused across phases, so they are
+        // only zeroed when first allocated instead of handing uninitialized memory to the
+        // transport.
+        self.rx_buf.resize(self.config.receive_buffer_size, 0);
+        let n = self
+            .transport
+            .read(&mut self.rx_buf[..], timeouts.response)?;
+        let buf = &self.rx_buf[..n];
+        let mut reader = ContainerReader::start(
+            buf,
+            self.rx_buf.len(),
+            self.chunk_size(),
+            self.config.max_data_len,
+        )?;
+
+        let mut payload = vec![];
+        let sink: &mut dyn DataSink = if reader.info.kind == ContainerType::Data {
+            sink
+        } else {
+            &mut payload
+        };
+        sink.reserve(reader.info.payload_len);
+        sink.write_chunk(&buf[CONTAINER_INFO_SIZE..])?;
+
+        // the payload didn't fit into the first buffer, read the rest chunk by chunk
+        if let Some(chunk_len) = reader.next_len() {
+            if self.chunk_buf.len() < chunk_len {
+                self.chunk_buf.resize(chunk_len, 0);
+            }
+        }
+        while let Some(len) = reader.next_len() {
+            if self.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let chunk = &mut self.chunk_buf[..len];
+            let n = self.transport.read(chunk, timeouts.data)?;
+            reader.advance(n, len)?;
+            sink.write_chunk(&chunk[..n])?;
+        }
+
+        let cinfo = reader.finish()?;
+        Ok((cinfo, payload))
+    }
+
+    /// Take the events that arrived on the bulk pipe while a transaction was running.
+    pub fn drain_pending_events(&mut self) -> Vec<PtpEvent> {
+        std::mem::take(&mut self.pending_events)
+    }
+
+    /// Wait up to `timeout` for an event on the event channel, returns `None` if none arrived.
+    pub fn poll_event(&mut self, timeout: Option<Duration>) -> Result<Option<PtpEvent>, Error> {
+        let timeout = timeout.or(self.config.timeout).unwrap_or_default();
+        if self.quirks.no_interrupt_endpoint {
+            thread::sleep(timeout);
+            return Ok(None);
+        }
+
+        let mut buf = [0u8; 64];
+        match self.transport.read_event(&mut buf, timeout) {
+            Ok(n) => {
+                let event = PtpEvent::decode(&buf[..n])?;
+                trace!("event {:?}", event);
+                Ok(Some(event))
+            }
+            Err(Error::Usb(rusb::Error::Timeout)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_objectinfo(
+        &mut self,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<ObjectInfo, Error> {
+        let data = self.command(StandardCommandCode::GetObjectInfo, &[handle], None, timeout)?;
+        ObjectInfo::decode_from(&mut self.dataset_reader(data))
+    }
+
+    /// The size of an object in bytes. ObjectCompressedSize saturates at 0xFFFFFFFF for
+    /// objects of 4GB and more, whose size is read from the MTP ObjectSize property
+    /// instead. `None` if the device can't tell the size of such an object.
+    pub fn get_object_size(
+        &mut self,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<Option<u64>, Error> {
+        match self.get_objectinfo(handle, timeout)?.ObjectCompressedSize {
+            u32::MAX => {}
+            size => return Ok(Some(size as u64)),
+        }
+        if self.supports_operation(MtpCommandCode::GetObjectPropValue) != Some(true) {
+            return Ok(None);
+        }
+        let data = self.command(
+            MtpCommandCode::GetObjectPropValue,
+            &[handle, MtpObjectPropCode::ObjectSize as u32],
+            None,
+            timeout,
+        )?;
+        let mut cur = self.dataset_reader(data);
+        let size = cur.read_ptp_u64()?;
+        cur.expect_end()?;
+        Ok(Some(size))
+    }
+
+    pub fn get_object(&mut self, handle: u32, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
+        self.command(StandardCommandCode::GetObject, &[handle], None, timeout)
+    }
+
+    /// Like `get_object`, aborting the download once `cancel` is cancelled.
+    pub fn get_object_with_cancel(
+        &mut self,
+        handle: u32,
+        cancel: &CancelToken,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, Error> {
+        self.with_cancel(cancel, |camera| camera.get_object(handle, timeout))
+    }
+
+    /// Like `get_object`, but receive the object into `buf`, returning its length.
+    pub fn get_object_into(
+        &mut self,
+        handle: u32,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<usize, Error> {
+        self.command_into(
+            StandardCommandCode::GetObject,
+            &[handle],
+            None,
+            buf,
+            timeout,
+        )
+    }
+
+    /// Like `get_object`, but write the object to `sink` as it arrives instead of
+    /// collecting it in memory.
+    pub fn get_object_to<W: io::Write + ?Sized>(
+        &mut self,
+        handle: u32,
+        sink: &mut W,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        let (response, _) = self.command_sink(
+            StandardCommandCode::GetObject,
+            &[handle],
+            None,
+            &mut WriteSink(sink),
+            timeout,
+        )?;
+        if response != StandardResponseCode::Ok {
+            return Err(Error::Response(response));
+        }
+        Ok(())
+    }
+
+    /// Download an object into the file at `path`, calling `progress(done, total)` in bytes
+    /// as data arrives. The file is preallocated from the object's ObjectCompressedSize.
+    /// Returns the number of bytes written.
+    pub fn get_object_to_file<P: AsRef<Path>, F: FnMut(u64, u64)>(
+        &mut self,
+        handle: u32,
+        path: P,
+        progress: F,
+        timeout: Option<Duration>,
+    ) -> Result<u64, Error> {
+        let info = self.get_objectinfo(handle, timeout)?;
+        let total = info.ObjectCompressedSize as u64;
+
+        let mut file = File::create(path)?;
+        file.set_len(total)?;
+
+        let mut writer = WriteSink(&mut file);
+        let mut sink = ProgressSink {
+            inner: &mut writer,
+            done: 0,
+            total,
+            progress,
+        };
+        let (response, _) = self.command_sink(
+            StandardCommandCode::GetObject,
+            &[handle],
+            None,
+            &mut sink,
+            timeout,
+        )?;
+        if response != StandardResponseCode::Ok {
+            return Err(Error::Response(response));
+        }
+
+        let done = sink.done;
+        file.set_len(done)?;
+        Ok(done)
+    }
+
+    /// Fetch the thumbnail of an object, fails with `Error::NoThumbnail` if it has none.
+    pub fn get_thumb(&mut self, handle: u32, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
+        match self.command(StandardCommandCode::GetThumb, &[handle], None, timeout) {
+            Err(Error::Response(StandardResponseCode::NoThumbnailPresent)) => {
+                Err(Error::NoThumbnail)
+            }
+            result => result,
+        }
+    }
+
+    pub fn get_partialobject(
+        &mut self,
+        handle: u32,
+        offset: u32,
+        max: u32,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, Error> {
+        if self.quirks.broken_partial_object {
+            return Err(Error::Response(StandardResponseCode::OperationNotSupported));
+        }
+        self.command(
+            StandardCommandCode::GetPartialObject,
+            &[handle, offset, max],
+            None,
+            timeout,
+        )
+    }
+
+    /// GetPartialObject with a 64-bit offset (MTP GetPartialObject64), for objects over 4GB.
+    pub fn get_partialobject64(
+        &mut self,
+        handle: u32,
+        offset: u64,
+        max: u32,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, Error> {
+        if self.quirks.broken_partial_object {
+            return Err(Error::Response(StandardResponseCode::OperationNotSupported));
+        }
+        self.command(
+            MtpCommandCode::GetPartialObject64,
+            &[handle, offset as u32, (offset >> 32) as u32, max],
+            None,
+            timeout,
+        )
+    }
+
+    /// Iterate lazily over the objects of a store, or of all stores with 0xFFFFFFFF,
+    /// optionally only those of format `format`. See `Objects`.
+    pub fn objects(
+        &mut self,
+        storage_id: u32,
+        format: Option<u16>,
+        timeout: Option<Duration>,
+    ) -> Objects<'_, T> {
+        let query = match format {
+            Some(format) => ObjectQuery::new().with_format(format),
+            None => ObjectQuery::new(),
+        };
+        Objects::new(self, storage_id, query, timeout)
+    }
+
+    /// Iterate lazily over the objects of a store matching `query`, see `Objects`.
+    pub fn query_objects(
+        &mut self,
+        storage_id: u32,
+        query: ObjectQuery,
+        timeout: Option<Duration>,
+    ) -> Objects<'_, T> {
+        Objects::new(self, storage_id, query, timeout)
+    }
+
+    /// Access store `storage_id` by path rather than by handle, see `CameraFs`.
+    pub fn fs(&mut self, storage_id: u32, timeout: Option<Duration>) -> CameraFs<'_, T> {
+        CameraFs::new(self, storage_id, timeout)
+    }
+
+    /// Start modifying an existing object in place, see `EditSession`. Fails for objects
+    /// over 4GB if the device can't tell their size, see `get_object_size`.
+    pub fn edit_object(
+        &mut self,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<EditSession<'_, T>, Error> {
+        EditSession::begin(self, handle, timeout)
+    }
+
+    /// Open an existing object for in-place modification (MTP BeginEditObject).
+    pub fn begin_edit_object(
+        &mut self,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.command(MtpCommandCode::BeginEditObject, &[handle], None, timeout)
+            .map(|_| ())
+    }
+
+    /// Write `data` at `offset` into an object opened with `begin_edit_object`
+    /// (MTP SendPartialObject). Returns the number of bytes the device accepted.
+    pub fn send_partial_object(
+        &mut self,
+        handle: u32,
+        offset: u64,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<u32, Error> {
+        let len = u32::try_from(data.len()).map_err(|_| {
+            Error::Malformed(format!(
+                "SendPartialObject of {} bytes exceeds 4GB",
+                data.len()
+            ))
+        })?;
+        let params = self.command_params(
+            MtpCommandCode::SendPartialObject,
+            &[handle, offset as u32, (offset >> 32) as u32, len],
+            Some(data),
+            timeout,
+        )?;
+        Ok(params.first().copied().unwrap_or(len))
+    }
+
+    /// Cut an object opened with `begin_edit_object` down to `offset` bytes (MTP TruncateObject).
+    pub fn truncate_object(
+        &mut self,
+        handle: u32,
+        offset: u64,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.command(
+            MtpCommandCode::TruncateObject,
+            &[handle, offset as u32, (offset >> 32) as u32],
+            None,
+            timeout,
+        )
+        .map(|_| ())
+    }
+
+    /// Commit the changes made since `begin_edit_object` (MTP EndEditObject).
+    pub fn end_edit_object(&mut self, handle: u32, timeout: Option<Duration>) -> Result<(), Error> {
+        self.command(MtpCommandCode::EndEditObject, &[handle], None, timeout)
+            .map(|_| ())
+    }
+
+    /// Delete each of `handles`, calling `progress(done, total)` after each one, and
+    /// report the outcome per handle instead of stopping at the first failure.
+    ///
+    /// An error other than a response, e.g. the device going away, is reported for the
+    /// handle it occurred on and ends the batch, so the report then has fewer entries
+    /// than `handles`.
+    pub fn delete_objects<F: FnMut(usize, usize)>(
+        &mut self,
+        handles: &[u32],
+        mut progress: F,
+        timeout: Option<Duration>,
+    ) -> Vec<(u32, DeleteOutcome)> {
+        let mut report = Vec::with_capacity(handles.len());
+        for (i, &handle) in handles.iter().enumerate() {
+            let (outcome, fatal) = match self.delete_object(handle, timeout) {
+                Ok(()) => (DeleteOutcome::Deleted, false),
+                Err(Error::WriteProtected) => (DeleteOutcome::WriteProtected, false),
+                Err(Error::Response(StandardResponseCode::PartialDeletion)) => {
+                    (DeleteOutcome::Partial, false)
+                }
+                Err(e @ Error::Response(_)) => (DeleteOutcome::Failed(e), false),
+                Err(e) => (DeleteOutcome::Failed(e), true),
+            };
+            report.push((handle, outcome));
+            progress(i + 1, handles.len());
+            if fatal {
+                break;
+            }
+        }
+        report
+    }
+
+    /// Delete an object and, for an association, everything below it, deepest objects
+    /// first, as many cameras refuse to delete a folder that isn't empty.
+    ///
+    /// Objects the device removed on its own in the meantime, e.g. along with their
+    /// folder, are skipped, whether announced by ObjectRemoved or found gone on deletion.
+    pub fn delete_recursive(
+        &mut self,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        let mut removed = HashSet::new();
+        self.delete_subtree(handle, &mut removed, timeout)
+    }
+
+    fn delete_subtree(
+        &mut self,
+        handle: u32,
+        removed: &mut HashSet<u32>,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        // pick up the ObjectRemoved events of the previous deletions
+        for event in self.drain_pending_events() {
+            if event.code == StandardEventCode::ObjectRemoved {
+                removed.extend(event.object_handle());
+            } else {
+                self.pending_events.push(event);
+            }
+        }
+        if removed.contains(&handle) {
+            return Ok(());
+        }
+
+        let info = match self.get_objectinfo(handle, timeout) {
+            Err(Error::Response(StandardResponseCode::InvalidObjectHandle)) => return Ok(()),
+            result => result?,
+        };
+        if StandardObjectFormatCode::is_association(info.ObjectFormat) {
+            for child in self.get_objecthandles(info.StorageID, handle, None, timeout)? {
+                self.delete_subtree(child, removed, timeout)?;
+            }
+        }
+
+        match self.delete_object(handle, timeout) {
+            Err(Error::Response(StandardResponseCode::InvalidObjectHandle)) => Ok(()),
+            result => result,
+        }?;
+        removed.insert(handle);
+        Ok(())
+    }
+
+    pub fn delete_object(&mut self, handle: u32, timeout: Option<Duration>) -> Result<(), Error> {
+        match self.command(StandardCommandCode::DeleteObject, &[handle], None, timeout) {
+            Err(Error::Response(StandardResponseCode::ObjectWriteProtected)) => {
+                Err(Error::WriteProtected)
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    pub fn set_object_protection(
+        &mut self,
+        handle: u32,
+        status: ProtectionStatus,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        match self.command(
+            StandardCommandCode::SetObjectProtection,
+            &[handle, status as u32],
+            None,
+            timeout,
+        ) {
+            Err(Error::Response(StandardResponseCode::ObjectWriteProtected)) => {
+                Err(Error::WriteProtected)
+            }
+            result => result.map(|_| ()),
+        }
+    }
 
-        // The first chunk contains the header, and its payload must be copied into the temporary buffer
-        let first_chunk_payload_bytes = min(payload.len(), CHUNK_SIZE - CONTAINER_INFO_SIZE);
-        let mut buf = Vec::with_capacity(first_chunk_payload_bytes + CONTAINER_INFO_SIZE);
-        buf.write_u32::<LittleEndian>((payload.len() + CONTAINER_INFO_SIZE) as u32)
-            .ok();
-        buf.write_u16::<LittleEndian>(kind as u16).ok();
-        buf.write_u16::<LittleEndian>(code).ok();
-        buf.write_u32::<LittleEndian>(tid).ok();
-        buf.extend_from_slice(&payload[..first_chunk_payload_bytes]);
-        self.handle
-            .read()
-            .unwrap()
-            .write_bulk(self.ep_out, &buf, timeout)?;
+    /// Announce an object to be uploaded with `send_object` and return the handle the
+    /// device assigned to it. The object is stored under `parent` on `info.StorageID`.
+    pub fn send_object_info(
+        &mut self,
+        parent: u32,
+        info: &ObjectInfo,
+        timeout: Option<Duration>,
+    ) -> Result<u32, Error> {
+        let params = self.command_params(
+            StandardCommandCode::SendObjectInfo,
+            &[info.StorageID, parent],
+            Some(&info.encode()?),
+            timeout,
+        )?;
 
-        // Write any subsequent chunks, straight from the source slice
-        for chunk in payload[first_chunk_payload_bytes..].chunks(CHUNK_SIZE) {
-            self.handle
-                .read()
-                .unwrap()
-                .write_bulk(self.ep_out, chunk, timeout)?;
-        }
+        // response parameters are the storage id, the parent handle and the new object handle
+        params.get(2).copied().ok_or_else(|| {
+            Error::Malformed(format!(
+                "SendObjectInfo response has {} parameters, expected 3",
+                params.len()
+            ))
+        })
+    }
 
-        Ok(())
+    /// Upload the object data announced by the preceding `send_object_info`.
+    pub fn send_object(&mut self, data: &[u8], timeout: Option<Duration>) -> Result<(), Error> {
+        self.command(StandardCommandCode::SendObject, &[], Some(data), timeout)
+            .map(|_| ())
     }
 
-    // helper for command() above, retrieve container info and payload for the current phase
-    fn read_txn_phase(&mut self, timeout: Duration) -> Result<(ContainerInfo, Vec<u8>), Error> {
-        // buf is stack allocated and intended to be large enough to accomodate most
-        // cmd/ctrl data (ie, not media) without allocating. payload handling below
-        // deals with larger media responses. mark it as uninitalized to avoid paying
-        // for zeroing out 8k of memory, since rust doesn't know what rusb does with this memory.
-        let mut unintialized_buf: [u8; 8 * 1024];
-        let buf = unsafe {
-            unintialized_buf = ::std::mem::uninitialized();
-            let n = self.handle.read().unwrap().read_bulk(
-                self.ep_in,
-                &mut unintialized_buf[..],
-                timeout,
-            )?;
-            &unintialized_buf[..n]
+    /// Create a folder named `name` under `parent` and return its handle.
+    pub fn create_folder(
+        &mut self,
+        storage_id: u32,
+        parent: u32,
+        name: &str,
+        timeout: Option<Duration>,
+    ) -> Result<u32, Error> {
+        let info = ObjectInfo {
+            StorageID: storage_id,
+            ObjectFormat: StandardObjectFormatCode::Association,
+            ParentObject: parent,
+            AssociationType: AssociationType::GenericFolder as u16,
+            Filename: name.to_owned(),
+            ..Default::default()
         };
+        self.send_object_info(parent, &info, timeout)
+    }
 
-        let cinfo = ContainerInfo::parse(&buf[..])?;
-        trace!("container {:?}", cinfo);
-
-        // no payload? we're done
-        if cinfo.payload_len == 0 {
-            return Ok((cinfo, vec![]));
-        }
-
-        // allocate one extra to avoid a separate read for trailing short packet
-        let mut payload = Vec::with_capacity(cinfo.payload_len + 1);
-        payload.extend_from_slice(&buf[CONTAINER_INFO_SIZE..]);
-
-        // response didn't fit into our original buf? read the rest
-        // or if our original read were satisfied exactly, so there is still a ZLP to read
-        if payload.len() < cinfo.payload_len || buf.len() == unintialized_buf.len() {
-            unsafe {
-                let p = payload.as_mut_ptr().add(payload.len());
-                let pslice = slice::from_raw_parts_mut(p, payload.capacity() - payload.len());
-                let mut n = 0;
-                for chunk in pslice.chunks_mut(1024 * 1024) {
-                    n += self
-                        .handle
-                        .read()
-                        .unwrap()
-                        .read_bulk(self.ep_in, chunk, timeout)?;
-                }
-                let sz = payload.len();
-                payload.set_len(sz + n);
-                trace!(
-                    "  bulk rx {}, ({}/{})",
-                    n,
-                    payload.len(),
-                    payload.capacity()
-                );
-            }
-        }
-
-        Ok((cinfo, payload))
+    /// Move an object to `parent` on `storage_id`, the handle stays valid.
+    pub fn move_object(
+        &mut self,
+        handle: u32,
+        storage_id: u32,
+        parent: u32,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.command(
+            StandardCommandCode::MoveObject,
+            &[handle, storage_id, parent],
+            None,
+            timeout,
+        )
+        .map(|_| ())
     }
 
-    pub fn get_objectinfo(
+    /// Copy an object to `parent` on `storage_id` and return the handle of the copy.
+    pub fn copy_object(
         &mut self,
         handle: u32,
+        storage_id: u32,
+        parent: u32,
         timeout: Option<Duration>,
-    ) -> Result<ObjectInfo, Error> {
-        let data = self.command(StandardCommandCode::GetObjectInfo, &[handle], None, timeout)?;
-        Ok(ObjectInfo::decode(&data)?)
+    ) -> Result<u32, Error> {
+        let params = self.command_params(
+            StandardCommandCode::CopyObject,
+            &[handle, storage_id, parent],
+            None,
+            timeout,
+        )?;
+        params.first().copied().ok_or_else(|| {
+            Error::Malformed("CopyObject response is missing the new object handle".to_string())
+        })
     }
 
-    pub fn get_object(&mut self, handle: u32, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
-        self.command(StandardCommandCode::GetObject, &[handle], None, timeout)
+    /// Trigger a capture into `storage_id` (0 lets the device choose) using `format`
+    /// (0 for the device default). Returns the transaction id the resulting ObjectAdded
+    /// and CaptureComplete events will refer to.
+    pub fn initiate_capture(
+        &mut self,
+        storage_id: u32,
+        format: u16,
+        timeout: Option<Duration>,
+    ) -> Result<u32, Error> {
+        self.command(
+            StandardCommandCode::InitiateCapture,
+            &[storage_id, format as u32],
+            None,
+            timeout,
+        )?;
+        Ok(self.last_transaction_id().unwrap_or(0))
     }
 
-    pub fn get_partialobject(
+    /// Start an open-ended capture (e.g. bulb or movie) that runs until
+    /// `terminate_open_capture` is called with the returned transaction id.
+    pub fn initiate_open_capture(
         &mut self,
-        handle: u32,
-        offset: u32,
-        max: u32,
+        storage_id: u32,
+        format: u16,
         timeout: Option<Duration>,
-    ) -> Result<Vec<u8>, Error> {
+    ) -> Result<u32, Error> {
         self.command(
-            StandardCommandCode::GetPartialObject,
-            &[handle, offset, max],
+            StandardCommandCode::InitiateOpenCapture,
+            &[storage_id, format as u32],
+            None,
+            timeout,
+        )?;
+        Ok(self.last_transaction_id().unwrap_or(0))
+    }
+
+    pub fn terminate_open_capture(
+        &mut self,
+        tid: u32,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.command(
+            StandardCommandCode::TerminateOpenCapture,
+            &[tid],
             None,
             timeout,
         )
+        .map(|_| ())
     }
 
-    pub fn delete_object(&mut self, handle: u32, timeout: Option<Duration>) -> Result<(), Error> {
-        self.command(StandardCommandCode::DeleteObject, &[handle], None, timeout)
-            .map(|_| ())
+    /// Erase a store, optionally asking for a specific FilesystemType. After the operation
+    /// is acknowledged this waits for the store to become available again, as devices
+    /// keep reporting DeviceBusy or StoreNotAvailable while they format.
+    pub fn format_store(
+        &mut self,
+        storage_id: u32,
+        filesystem_type: Option<u16>,
+        _confirm: Danger,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        const SETTLE_TIME: Duration = Duration::from_secs(120);
+
+        self.command(
+            StandardCommandCode::FormatStore,
+            &[storage_id, filesystem_type.unwrap_or(0) as u32],
+            None,
+            timeout,
+        )?;
+
+        let start = Instant::now();
+        loop {
+            match self.get_storage_info(storage_id, timeout) {
+                Err(Error::Response(StandardResponseCode::DeviceBusy))
+                | Err(Error::Response(StandardResponseCode::StoreNotAvailable))
+                    if start.elapsed() < SETTLE_TIME =>
+                {
+                    thread::sleep(Duration::from_millis(500));
+                }
+                result => return result.map(|_| ()),
+            }
+        }
+    }
+
+    /// Run the device self test, `self_test_type` 0 being the default test.
+    pub fn self_test(
+        &mut self,
+        self_test_type: u16,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.command(
+            StandardCommandCode::SelfTest,
+            &[self_test_type as u32],
+            None,
+            timeout,
+        )
+        .map(|_| ())
+    }
+
+    /// Reset the device to its default state. This closes the current session.
+    pub fn reset_device(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        self.command(StandardCommandCode::ResetDevice, &[], None, timeout)?;
+        self.session_open = false;
+        Ok(())
     }
 
     pub fn power_down(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
@@ -261,7 +1641,7 @@ impl<T: UsbContext> Camera<T> {
             timeout,
         )?;
         // Parse ObjectHandleArrray
-        let mut cur = Cursor::new(data);
+        let mut cur = self.dataset_reader(data);
         let value = cur.read_ptp_u32_vec()?;
         cur.expect_end()?;
 
@@ -286,6 +1666,248 @@ impl<T: UsbContext> Camera<T> {
         self.get_objecthandles(storage_id, 0x0, filter, timeout)
     }
 
+    /// The objects under `parent` (0xFFFFFFFF for the root of the store) with their
+    /// ObjectInfo, like `get_objecthandles` followed by `get_objectinfo` for each handle.
+    ///
+    /// Devices supporting MTP GetObjectPropList are asked for the whole folder in one
+    /// round trip; the ObjectInfos built from it lack the thumbnail and image fields.
+    /// Other devices, or a failing GetObjectPropList, fall back to one GetObjectInfo per
+    /// object.
+    pub fn list_fast(
+        &mut self,
+        storage_id: u32,
+        parent: u32,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<(u32, ObjectInfo)>, Error> {
+        if self.supports_operation(MtpCommandCode::GetObjectPropList) != Some(false) {
+            match self.object_prop_list(storage_id, parent, timeout) {
+                Ok(objects) => return Ok(objects),
+                Err(Error::Response(code)) => {
+                    debug!("GetObjectPropList failed with 0x{:04x}, falling back", code)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut objects = vec![];
+        for handle in self.get_objecthandles(storage_id, parent, None, timeout)? {
+            objects.push((handle, self.get_objectinfo(handle, timeout)?));
+        }
+        Ok(objects)
+    }
+
+    /// The objects of a store captured at or after `after` and before `before`, PTP
+    /// DateTime strings compared as in `capture_date_in_range`. Folders are left out.
+    ///
+    /// Devices supporting MTP GetObjectPropList are asked for the DateCreated of all
+    /// objects at once, so only the ObjectInfos of the matches are fetched; otherwise
+    /// the ObjectInfo of every object is read.
+    pub fn objects_captured(
+        &mut self,
+        storage_id: u32,
+        after: &str,
+        before: Option<&str>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<(u32, ObjectInfo)>, Error> {
+        let in_range = |info: &ObjectInfo| {
+            !StandardObjectFormatCode::is_association(info.ObjectFormat)
+                && (storage_id == 0xFFFF_FFFF || info.StorageID == storage_id)
+                && capture_date_in_range(&info.CaptureDate, Some(after), before)
+        };
+
+        if self.supports_operation(MtpCommandCode::GetObjectPropList) != Some(false) {
+            match self.created_dates(timeout) {
+                Ok(dates) => {
+                    let mut objects = vec![];
+                    for (handle, date) in dates {
+                        if capture_date_in_range(&date, Some(after), before) {
+                            let info = self.get_objectinfo(handle, timeout)?;
+                            if in_range(&info) {
+                                objects.push((handle, info));
+                            }
+                        }
+                    }
+                    return Ok(objects);
+                }
+                Err(Error::Response(code)) => {
+                    debug!("GetObjectPropList failed with 0x{:04x}, falling back", code)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut objects = vec![];
+        for handle in self.get_objecthandles_all(storage_id, None, timeout)? {
+            let info = self.get_objectinfo(handle, timeout)?;
+            if in_range(&info) {
+                objects.push((handle, info));
+            }
+        }
+        Ok(objects)
+    }
+
+    // the DateCreated of every object on the device, from a single GetObjectPropList
+    fn created_dates(&mut self, timeout: Option<Duration>) -> Result<Vec<(u32, String)>, Error> {
+        let data = self.command(
+            MtpCommandCode::GetObjectPropList,
+            &[
+                0xFFFF_FFFF,
+                0,
+                MtpObjectPropCode::DateCreated as u32,
+                0,
+                0xFFFF_FFFF,
+            ],
+            None,
+            timeout,
+        )?;
+
+        let mut reader = self.dataset_reader(data);
+        let count = reader.read_ptp_u32()?;
+        let mut dates = vec![];
+        for _ in 0..count {
+            let handle = reader.read_ptp_u32()?;
+            let prop = reader.read_ptp_u16()?;
+            let data_type = reader.read_ptp_u16()?;
+            match DataType::read_type(data_type, &mut reader)? {
+                DataType::STR(date) if prop == MtpObjectPropCode::DateCreated => {
+                    dates.push((handle, date))
+                }
+                _ => {}
+            }
+        }
+        reader.expect_end()?;
+        Ok(dates)
+    }
+
+    // the children of `parent` from GetObjectPropList with all properties and depth 1
+    fn object_prop_list(
+        &mut self,
+        storage_id: u32,
+        parent: u32,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<(u32, ObjectInfo)>, Error> {
+        // GetObjectPropList takes 0 for the root, and the datasets refer to it as 0 too
+        let parent = if parent == 0xFFFF_FFFF { 0 } else { parent };
+        let data = self.command(
+            MtpCommandCode::GetObjectPropList,
+            &[parent, 0, 0xFFFF_FFFF, 0, 1],
+            None,
+            timeout,
+        )?;
+
+        let mut reader = self.dataset_reader(data);
+        let count = reader.read_ptp_u32()?;
+        let mut objects: Vec<(u32, ObjectInfo)> = vec![];
+        let mut index_of = HashMap::new();
+        for _ in 0..count {
+            let handle = reader.read_ptp_u32()?;
+            let prop = reader.read_ptp_u16()?;
+            let data_type = reader.read_ptp_u16()?;
+            let value = DataType::read_type(data_type, &mut reader)?;
+
+            let index = *index_of.entry(handle).or_insert_with(|| {
+                objects.push((handle, ObjectInfo::default()));
+                objects.len() - 1
+            });
+            let info = &mut objects[index].1;
+            let int = value.to_i128().unwrap_or_default();
+            match (prop, value) {
+                (MtpObjectPropCode::StorageID, _) => info.StorageID = int as u32,
+                (MtpObjectPropCode::ObjectFormat, _) => info.ObjectFormat = int as u16,
+                (MtpObjectPropCode::ProtectionStatus, _) => info.ProtectionStatus = int as u16,
+                (MtpObjectPropCode::ObjectSize, _) => {
+                    info.ObjectCompressedSize = int.min(u32::MAX as i128) as u32
+                }
+                (MtpObjectPropCode::AssociationType, _) => info.AssociationType = int as u16,
+                (MtpObjectPropCode::AssociationDesc, _) => info.AssociationDesc = int as u32,
+                (MtpObjectPropCode::ParentObject, _) => info.ParentObject = int as u32,
+                (MtpObjectPropCode::ObjectFileName, DataType::STR(s)) => info.Filename = s,
+                (MtpObjectPropCode::DateCreated, DataType::STR(s)) => info.CaptureDate = s,
+                (MtpObjectPropCode::DateModified, DataType::STR(s)) => info.ModificationDate = s,
+                (MtpObjectPropCode::Keywords, DataType::STR(s)) => info.Keywords = s,
+                _ => {}
+            }
+        }
+        reader.expect_end()?;
+
+        // the root listing covers all stores, and some devices include the folder itself
+        objects.retain(|(handle, info)| {
+            *handle != parent
+                && (storage_id == 0xFFFF_FFFF || info.StorageID == storage_id)
+                && info.ParentObject == parent
+        });
+        Ok(objects)
+    }
+
+    /// Read the hierarchy below `root`, or the root of the store if `None`, returning its
+    /// children. Folders are descended `depth` levels deep, without limit if `None`;
+    /// folders past that have `children` set to `None`.
+    ///
+    /// With a `format` filter, only folders and objects of that format are kept. The
+    /// filter is applied here rather than by the device, as GetObjectHandles would also
+    /// drop the folders. `progress` is called with the number of objects read so far.
+    pub fn object_tree<F: FnMut(usize)>(
+        &mut self,
+        storage_id: u32,
+        root: Option<u32>,
+        depth: Option<usize>,
+        format: Option<u16>,
+        mut progress: F,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<ObjectTree>, Error> {
+        let mut count = 0;
+        self.object_subtree(
+            storage_id,
+            root.unwrap_or(0xFFFF_FFFF),
+            depth,
+            format,
+            &mut || {
+                count += 1;
+                progress(count)
+            },
+            timeout,
+        )
+    }
+
+    fn object_subtree(
+        &mut self,
+        storage_id: u32,
+        parent: u32,
+        depth: Option<usize>,
+        format: Option<u16>,
+        object_read: &mut dyn FnMut(),
+        timeout: Option<Duration>,
+    ) -> Result<Vec<ObjectTree>, Error> {
+        let mut nodes = vec![];
+        for handle in self.get_objecthandles(storage_id, parent, None, timeout)? {
+            let info = self.get_objectinfo(handle, timeout)?;
+            object_read();
+
+            let is_folder = info.is_folder();
+            if !is_folder && format.is_some_and(|f| f != info.ObjectFormat) {
+                continue;
+            }
+            let children = match depth {
+                _ if !is_folder => None,
+                Some(0) => None,
+                depth => Some(self.object_subtree(
+                    storage_id,
+                    handle,
+                    depth.map(|d| d - 1),
+                    format,
+                    object_read,
+                    timeout,
+                )?),
+            };
+            nodes.push(ObjectTree {
+                handle,
+                info,
+                children,
+            });
+        }
+        Ok(nodes)
+    }
+
     // handle_id: None == root of store
     pub fn get_numobjects(
         &mut self,
@@ -302,7 +1924,7 @@ impl<T: UsbContext> Camera<T> {
         )?;
 
         // Parse ObjectHandleArrray
-        let mut cur = Cursor::new(data);
+        let mut cur = self.dataset_reader(data);
         let value = cur.read_ptp_u32()?;
         cur.expect_end()?;
 
@@ -322,7 +1944,7 @@ impl<T: UsbContext> Camera<T> {
         )?;
 
         // Parse ObjectHandleArrray
-        let mut cur = Cursor::new(data);
+        let mut cur = self.dataset_reader(data);
         let res = StorageInfo::decode(&mut cur)?;
         cur.expect_end()?;
 
@@ -333,7 +1955,7 @@ impl<T: UsbContext> Camera<T> {
         let data = self.command(StandardCommandCode::GetStorageIDs, &[], None, timeout)?;
 
         // Parse ObjectHandleArrray
-        let mut cur = Cursor::new(data);
+        let mut cur = self.dataset_reader(data);
         let value = cur.read_ptp_u32_vec()?;
         cur.expect_end()?;
 
@@ -358,115 +1980,208 @@ impl<T: UsbContext> Camera<T> {
         self.get_numobjects(storage_id, 0x0, filter, timeout)
     }
 
-    pub fn get_device_info(&mut self, timeout: Option<Duration>) -> Result<DeviceInfo, Error> {
+    pub fn get_device_prop_desc(
+        &mut self,
+        property_code: u16,
+        timeout: Option<Duration>,
+    ) -> Result<PropInfo, Error> {
         let data = self.command(
-            StandardCommandCode::GetDeviceInfo,
-            &[0, 0, 0],
+            StandardCommandCode::GetDevicePropDesc,
+            &[property_code as u32],
             None,
             timeout,
         )?;
 
-        let device_info = DeviceInfo::decode(&data)?;
-        debug!("device_info {:?}", device_info);
-        Ok(device_info)
+        let mut cur = self.dataset_reader(data);
+        PropInfo::decode(&mut cur)
     }
 
-    pub fn open_session(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
-        let session_id = 1;
+    /// Read the current value of a property, `data_type` being its Datatype Code as
+    /// reported in the property descriptor.
+    pub fn get_device_prop_value(
+        &mut self,
+        property_code: u16,
+        data_type: u16,
+        timeout: Option<Duration>,
+    ) -> Result<DataType, Error> {
+        let data = self.command(
+            StandardCommandCode::GetDevicePropValue,
+            &[property_code as u32],
+            None,
+            timeout,
+        )?;
+
+        let mut cur = self.dataset_reader(data);
+        let value = DataType::read_type(data_type, &mut cur)?;
+        cur.expect_end()?;
+
+        Ok(value)
+    }
+
+    pub fn set_device_prop_value(
+        &mut self,
+        property_code: u16,
+        value: &DataType,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.command(
+            StandardCommandCode::SetDevicePropValue,
+            &[property_code as u32],
+            Some(&value.encode()?),
+            timeout,
+        )
+        .map(|_| ())
+    }
 
+    /// Restore the factory default of a property.
+    pub fn reset_device_prop_value(
+        &mut self,
+        property_code: u16,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
         self.command(
-            StandardCommandCode::OpenSession,
-            &[session_id, 0, 0],
+            StandardCommandCode::ResetDevicePropValue,
+            &[property_code as u32],
             None,
             timeout,
-        )?;
+        )
+        .map(|_| ())
+    }
 
-        Ok(())
+    /// Restore the factory default of every property.
+    pub fn reset_all_device_prop_values(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        self.command(
+            StandardCommandCode::ResetDevicePropValue,
+            &[0xFFFF_FFFF],
+            None,
+            timeout,
+        )
+        .map(|_| ())
     }
 
-    pub fn close_session(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
-        self.command(StandardCommandCode::CloseSession, &[], None, timeout)?;
+    pub fn get_device_info(&mut self, timeout: Option<Duration>) -> Result<DeviceInfo, Error> {
+        let data = self.command(
+            StandardCommandCode::GetDeviceInfo,
+            &[0, 0, 0],
+            None,
+            timeout,
+        )?;
 
-        Ok(())
+        let device_info = DeviceInfo::decode_from(&mut self.dataset_reader(data))?;
+        debug!("device_info {:?}", device_info);
+        self.device_info = Some(device_info.clone());
+        Ok(device_info)
     }
 
-    pub fn disconnect(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
-        self.close_session(timeout)?;
-        self.handle.write().unwrap().release_interface(self.iface)?;
-        Ok(())
+    /// The DeviceInfo last fetched with `get_device_info`, if any.
+    pub fn device_info(&self) -> Option<&DeviceInfo> {
+        self.device_info.as_ref()
     }
 
-    pub fn reset(&mut self) -> Result<(), Error> {
-        self.handle.write().unwrap().reset()?;
-        Ok(())
+    // add the events and properties a vendor reports outside of DeviceInfo, so the
+    // supports_* methods know about them
+    pub(crate) fn add_capabilities(&mut self, events: &[EventCode], properties: &[PropertyCode]) {
+        if let Some(ref mut info) = self.device_info {
+            for &code in events {
+                if !info.EventsSupported.contains(&code) {
+                    info.EventsSupported.push(code);
+                }
+            }
+            for &code in properties {
+                if !info.DevicePropertiesSupported.contains(&code) {
+                    info.DevicePropertiesSupported.push(code);
+                }
+            }
+        }
     }
 
-    pub fn clear_halt(&mut self) -> Result<(), Error> {
-        self.handle.write().unwrap().clear_halt(self.ep_in)?;
-        self.handle.write().unwrap().clear_halt(self.ep_out)?;
-        self.handle.write().unwrap().clear_halt(self._ep_int)?;
-        Ok(())
+    /// Whether the device advertises operation `code`, `None` until `get_device_info`
+    /// was called.
+    pub fn supports_operation(&self, code: CommandCode) -> Option<bool> {
+        let info = self.device_info.as_ref()?;
+        Some(info.OperationsSupported.contains(&code))
     }
-}
 
-#[derive(Debug, PartialEq)]
-#[repr(u16)]
-enum ContainerType {
-    Command = 1,
-    Data = 2,
-    Response = 3,
-    Event = 4,
-}
+    /// Whether the device advertises event `code`, `None` until `get_device_info` was called.
+    pub fn supports_event(&self, code: EventCode) -> Option<bool> {
+        let info = self.device_info.as_ref()?;
+        Some(info.EventsSupported.contains(&code))
+    }
 
-impl ContainerType {
-    fn from_u16(v: u16) -> Option<ContainerType> {
-        use self::ContainerType::*;
-        match v {
-            1 => Some(Command),
-            2 => Some(Data),
-            3 => Some(Response),
-            4 => Some(Event),
-            _ => None,
-        }
+    /// Whether the device advertises device property `code`, `None` until
+    /// `get_device_info` was called.
+    pub fn supports_property(&self, code: PropertyCode) -> Option<bool> {
+        let info = self.device_info.as_ref()?;
+        Some(info.DevicePropertiesSupported.contains(&code))
     }
-}
 
-#[derive(Debug)]
-struct ContainerInfo {
-    /// payload len in bytes, usually relevant for data phases
-    payload_len: usize,
+    /// The id used by `open_session`, 1 unless changed with `set_session_id`.
+    pub fn session_id(&self) -> u32 {
+        self.session_id
+    }
 
-    /// Container kind
-    kind: ContainerType,
+    /// Set the id used by the next `open_session`. Must not be 0.
+    pub fn set_session_id(&mut self, session_id: u32) {
+        self.session_id = session_id;
+    }
 
-    /// StandardCommandCode or ResponseCode, depending on 'kind'
-    code: u16,
+    pub fn is_session_open(&self) -> bool {
+        self.session_open
+    }
 
-    /// transaction ID that this container belongs to
-    tid: u32,
-}
+    /// Open a session, closing and reopening it if the device reports one is already open.
+    pub fn open_session(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        let params = [self.session_id, 0, 0];
+
+        // OpenSession is transaction 0, the first operation of the session 1
+        self.current_tid = 0;
+        match self.command(StandardCommandCode::OpenSession, &params, None, timeout) {
+            Err(Error::Response(StandardResponseCode::SessionAlreadyOpen)) => {
+                debug!("session already open, reopening");
+                self.close_session(timeout)?;
+                self.current_tid = 0;
+                self.command(StandardCommandCode::OpenSession, &params, None, timeout)?;
+            }
+            result => {
+                result?;
+            }
+        }
 
-const CONTAINER_INFO_SIZE: usize = 12;
+        self.session_open = true;
+        Ok(())
+    }
 
-impl ContainerInfo {
-    pub fn parse<R: ReadBytesExt>(mut r: R) -> Result<ContainerInfo, Error> {
-        let len = r.read_u32::<LittleEndian>()?;
-        let kind_u16 = r.read_u16::<LittleEndian>()?;
-        let kind = ContainerType::from_u16(kind_u16)
-            .ok_or_else(|| Error::Malformed(format!("Invalid message type {:x}.", kind_u16)))?;
-        let code = r.read_u16::<LittleEndian>()?;
-        let tid = r.read_u32::<LittleEndian>()?;
+    pub fn close_session(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        match self.command(StandardCommandCode::CloseSession, &[], None, timeout) {
+            Ok(_) | Err(Error::Response(StandardResponseCode::SessionNotOpen)) => {
+                self.session_open = false;
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
 
-        Ok(ContainerInfo {
-            payload_len: len as usize - CONTAINER_INFO_SIZE,
-            kind,
-            tid,
-            code,
-        })
+    /// Close the session and release the transport. Calling it again is a no-op.
+    pub fn disconnect(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        // release the transport even if the device didn't answer CloseSession
+        let closed = if self.session_open {
+            self.close_session(timeout)
+        } else {
+            Ok(())
+        };
+        if !self.released {
+            self.transport.release()?;
+            self.released = true;
+        }
+        closed
     }
+}
 
-    // does this container belong to the given transaction?
-    pub fn belongs_to(&self, tid: u32) -> bool {
-        self.tid == tid
+impl<T: Transport> Drop for Camera<T> {
+    fn drop(&mut self) {
+        // best effort, there is nobody left to report errors to
+        if let Err(e) = self.disconnect(Some(Duration::from_secs(1))) {
+            debug!("disconnect on drop failed: {}", e);
+        }
     }
 }
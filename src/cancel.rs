@@ -0,0 +1,29 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag aborting a transfer in progress, e.g. from a UI thread.
+///
+/// Clones share the same flag. The camera checks it between bulk chunks, and once it is
+/// set the running transaction is aborted with the class Cancel request and fails with
+/// `Error::Cancelled`.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Clear the flag so the token can be used for another transfer.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,218 @@
+use super::{Error, PtpEvent, ResponseCode};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::cmp::min;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[repr(u16)]
+pub(crate) enum ContainerType {
+    Command = 1,
+    Data = 2,
+    Response = 3,
+    Event = 4,
+}
+
+impl ContainerType {
+    fn from_u16(v: u16) -> Option<ContainerType> {
+        use self::ContainerType::*;
+        match v {
+            1 => Some(Command),
+            2 => Some(Data),
+            3 => Some(Response),
+            4 => Some(Event),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ContainerInfo {
+    /// payload len in bytes, usually relevant for data phases
+    pub payload_len: usize,
+
+    /// Container kind
+    pub kind: ContainerType,
+
+    /// StandardCommandCode or ResponseCode, depending on 'kind'
+    pub code: u16,
+
+    /// transaction ID that this container belongs to
+    pub tid: u32,
+}
+
+/// A container received during a transaction, see `ContainerInfo::phase`.
+#[derive(Debug)]
+pub(crate) enum Phase {
+    /// An event some cameras interleave with the transaction on the bulk pipe.
+    Event(PtpEvent),
+    Data,
+    /// The response code and parameters, ending the transaction.
+    Response(ResponseCode, Vec<u32>),
+}
+
+pub(crate) const CONTAINER_INFO_SIZE: usize = 12;
+
+/// Maximum number of parameters of operation, response and event containers.
+pub(crate) const MAX_PARAMS: usize = 5;
+
+impl ContainerInfo {
+    pub fn parse<R: ReadBytesExt>(mut r: R) -> Result<ContainerInfo, Error> {
+        let len = r.read_u32::<LittleEndian>()?;
+        let kind_u16 = r.read_u16::<LittleEndian>()?;
+        let kind = ContainerType::from_u16(kind_u16)
+            .ok_or_else(|| Error::Malformed(format!("Invalid message type {:x}.", kind_u16)))?;
+        let code = r.read_u16::<LittleEndian>()?;
+        let tid = r.read_u32::<LittleEndian>()?;
+
+        let len = len as usize;
+        if len < CONTAINER_INFO_SIZE {
+            return Err(Error::Malformed(format!(
+                "Container length {} is shorter than its header",
+                len
+            )));
+        }
+        // only data containers carry more than the 5 parameters allowed to the others
+        if kind != ContainerType::Data && len > CONTAINER_INFO_SIZE + MAX_PARAMS * 4 {
+            return Err(Error::Malformed(format!(
+                "{:?} container length {} exceeds {} parameters",
+                kind, len, MAX_PARAMS
+            )));
+        }
+
+        Ok(ContainerInfo {
+            payload_len: len - CONTAINER_INFO_SIZE,
+            kind,
+            tid,
+            code,
+        })
+    }
+
+    /// Serialize the container header, to be followed by `payload_len` bytes of payload.
+    pub fn write<W: WriteBytesExt>(&self, mut w: W) {
+        w.write_u32::<LittleEndian>((self.payload_len + CONTAINER_INFO_SIZE) as u32)
+            .ok();
+        w.write_u16::<LittleEndian>(self.kind as u16).ok();
+        w.write_u16::<LittleEndian>(self.code).ok();
+        w.write_u32::<LittleEndian>(self.tid).ok();
+    }
+
+    // does this container belong to the given transaction?
+    pub fn belongs_to(&self, tid: u32) -> bool {
+        self.tid == tid
+    }
+
+    // what this container and its payload mean to transaction `tid`, shared by the
+    // transaction loops of Camera and AsyncCamera
+    pub fn phase(&self, payload: &[u8], tid: u32) -> Result<Phase, Error> {
+        // events carry the id of the transaction they relate to, if any
+        if self.kind == ContainerType::Event {
+            return Ok(Phase::Event(PtpEvent::from_container(self, payload)?));
+        }
+        if !self.belongs_to(tid) {
+            return Err(Error::Malformed(format!(
+                "mismatched txnid {}, expecting {}",
+                self.tid, tid
+            )));
+        }
+        match self.kind {
+            ContainerType::Data => Ok(Phase::Data),
+            ContainerType::Response => {
+                let params = payload.chunks_exact(4);
+                if !params.remainder().is_empty() {
+                    return Err(Error::Malformed(format!(
+                        "Response payload of {} bytes isn't made of parameters",
+                        payload.len()
+                    )));
+                }
+                let params = params
+                    .map(|p| u32::from_le_bytes([p[0], p[1], p[2], p[3]]))
+                    .collect();
+                Ok(Phase::Response(self.code, params))
+            }
+            kind => Err(Error::Malformed(format!(
+                "Unexpected {:?} container in transaction {}",
+                kind, tid
+            ))),
+        }
+    }
+}
+
+/// Receives one container over the bulk pipe, for the transaction loops of Camera and
+/// AsyncCamera, which only do the reads.
+///
+/// The first transfer holds the header. The rest of the payload is read in chunks, asking
+/// for one extra byte at the end so the last transfer ends on the short packet. Whenever
+/// a transfer fills its buffer exactly at the end of the container, the device follows up
+/// with a zero-length packet, which is read too so it doesn't end up in the next phase.
+pub(crate) struct ContainerReader {
+    pub info: ContainerInfo,
+    received: usize,
+    zlp_pending: bool,
+    chunk_size: usize,
+}
+
+impl ContainerReader {
+    /// Start with the first transfer `buf`, read into a buffer of `capacity` bytes.
+    /// Data phases announcing more than `max_data_len` bytes are refused.
+    pub fn start(
+        buf: &[u8],
+        capacity: usize,
+        chunk_size: usize,
+        max_data_len: usize,
+    ) -> Result<ContainerReader, Error> {
+        let info = ContainerInfo::parse(buf)?;
+        trace!("container {:?}", info);
+        if info.kind == ContainerType::Data && info.payload_len > max_data_len {
+            return Err(Error::Malformed(format!(
+                "Data phase of {} bytes exceeds the limit of {} bytes",
+                info.payload_len, max_data_len
+            )));
+        }
+        let received = buf.len() - CONTAINER_INFO_SIZE;
+        Ok(ContainerReader {
+            zlp_pending: buf.len() == capacity && received == info.payload_len,
+            received,
+            info,
+            chunk_size,
+        })
+    }
+
+    /// Number of bytes to ask for in the next read, None once the container is complete.
+    pub fn next_len(&self) -> Option<usize> {
+        if self.received < self.info.payload_len || self.zlp_pending {
+            let left = self.info.payload_len.saturating_sub(self.received) + 1;
+            Some(min(left, self.chunk_size))
+        } else {
+            None
+        }
+    }
+
+    /// Account for a read of `n` bytes that asked for `len`.
+    pub fn advance(&mut self, n: usize, len: usize) -> Result<(), Error> {
+        if n == 0 && self.received < self.info.payload_len {
+            return Err(Error::Malformed(format!(
+                "Data phase ended after {} bytes, expected {} bytes",
+                self.received, self.info.payload_len
+            )));
+        }
+        self.received += n;
+        self.zlp_pending = n > 0 && n == len && self.received == self.info.payload_len;
+        trace!(
+            "  bulk rx {}, ({}/{})",
+            n,
+            self.received,
+            self.info.payload_len
+        );
+        Ok(())
+    }
+
+    /// Check that exactly the announced payload arrived.
+    pub fn finish(self) -> Result<ContainerInfo, Error> {
+        if self.received != self.info.payload_len {
+            return Err(Error::Malformed(format!(
+                "Container payload of {} bytes, expected {} bytes",
+                self.received, self.info.payload_len
+            )));
+        }
+        Ok(self.info)
+    }
+}
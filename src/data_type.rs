@@ -1,9 +1,9 @@
-use super::{Error, Read};
-use byteorder::{LittleEndian, WriteBytesExt};
-use std::io::Write;
+use super::{Error, PtpWrite, Read};
+use std::convert::TryFrom;
 
 #[allow(non_snake_case)]
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataType {
     UNDEF,
     INT8(i8),
@@ -30,113 +30,150 @@ pub enum DataType {
 }
 
 impl DataType {
-    pub fn encode(&self) -> Vec<u8> {
+    /// Serialize the value as used in the data phase of SetDevicePropValue.
+    ///
+    /// Fails for strings that aren't valid PTP strings, see `PtpWrite::write_ptp_str`.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
         use self::DataType::*;
         let mut out = vec![];
         match self {
-            // UNDEF => {},
-            INT8(val) => {
-                out.write_i8(*val).ok();
-            }
-            UINT8(val) => {
-                out.write_u8(*val).ok();
-            }
-            INT16(val) => {
-                out.write_i16::<LittleEndian>(*val).ok();
-            }
-            UINT16(val) => {
-                out.write_u16::<LittleEndian>(*val).ok();
-            }
-            INT32(val) => {
-                out.write_i32::<LittleEndian>(*val).ok();
-            }
-            UINT32(val) => {
-                out.write_u32::<LittleEndian>(*val).ok();
-            }
-            INT64(val) => {
-                out.write_i64::<LittleEndian>(*val).ok();
-            }
-            UINT64(val) => {
-                out.write_u64::<LittleEndian>(*val).ok();
-            }
-            INT128(val) => {
-                out.write_i128::<LittleEndian>(*val).ok();
-            }
-            UINT128(val) => {
-                out.write_u128::<LittleEndian>(*val).ok();
-            }
-            AINT8(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_i8(*item).ok();
-                }
-            }
-            AUINT8(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_u8(*item).ok();
-                }
-            }
-            AINT16(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_i16::<LittleEndian>(*item).ok();
-                }
-            }
-            AUINT16(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_u16::<LittleEndian>(*item).ok();
-                }
-            }
-            AINT32(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_i32::<LittleEndian>(*item).ok();
-                }
-            }
-            AUINT32(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_u32::<LittleEndian>(*item).ok();
-                }
-            }
-            AINT64(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_i64::<LittleEndian>(*item).ok();
-                }
-            }
-            AUINT64(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_u64::<LittleEndian>(*item).ok();
-                }
-            }
-            AINT128(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_i128::<LittleEndian>(*item).ok();
-                }
-            }
-            AUINT128(val) => {
-                out.write_u32::<LittleEndian>(val.len() as u32).ok();
-                for item in val {
-                    out.write_u128::<LittleEndian>(*item).ok();
-                }
-            }
-            STR(val) => {
-                out.write_u8(((val.len() as u8) * 2) + 1).ok();
-                if !val.is_empty() {
-                    for e in val.encode_utf16() {
-                        out.write_u16::<LittleEndian>(e).ok();
-                    }
-                    out.write_all(b"\0\0").ok();
-                }
-            }
-            _ => {}
+            UNDEF => {}
+            INT8(val) => out.write_ptp_i8(*val)?,
+            UINT8(val) => out.write_ptp_u8(*val)?,
+            INT16(val) => out.write_ptp_i16(*val)?,
+            UINT16(val) => out.write_ptp_u16(*val)?,
+            INT32(val) => out.write_ptp_i32(*val)?,
+            UINT32(val) => out.write_ptp_u32(*val)?,
+            INT64(val) => out.write_ptp_i64(*val)?,
+            UINT64(val) => out.write_ptp_u64(*val)?,
+            INT128(val) => out.write_ptp_i128(*val)?,
+            UINT128(val) => out.write_ptp_u128(*val)?,
+            AINT8(val) => out.write_ptp_vec(val, |cur, item| cur.write_ptp_i8(*item))?,
+            AUINT8(val) => out.write_ptp_vec(val, |cur, item| cur.write_ptp_u8(*item))?,
+            AINT16(val) => out.write_ptp_vec(val, |cur, item| cur.write_ptp_i16(*item))?,
+            AUINT16(val) => out.write_ptp_u16_vec(val)?,
+            AINT32(val) => out.write_ptp_vec(val, |cur, item| cur.write_ptp_i32(*item))?,
+            AUINT32(val) => out.write_ptp_u32_vec(val)?,
+            AINT64(val) => out.write_ptp_vec(val, |cur, item| cur.write_ptp_i64(*item))?,
+            AUINT64(val) => out.write_ptp_vec(val, |cur, item| cur.write_ptp_u64(*item))?,
+            AINT128(val) => out.write_ptp_vec(val, |cur, item| cur.write_ptp_i128(*item))?,
+            AUINT128(val) => out.write_ptp_vec(val, |cur, item| cur.write_ptp_u128(*item))?,
+            STR(val) => out.write_ptp_str(val)?,
         }
-        out
+        Ok(out)
+    }
+
+    pub fn as_i8(&self) -> Option<i8> {
+        match *self {
+            DataType::INT8(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> Option<u8> {
+        match *self {
+            DataType::UINT8(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i16(&self) -> Option<i16> {
+        match *self {
+            DataType::INT16(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u16(&self) -> Option<u16> {
+        match *self {
+            DataType::UINT16(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match *self {
+            DataType::INT32(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match *self {
+            DataType::UINT32(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            DataType::INT64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            DataType::UINT64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i128(&self) -> Option<i128> {
+        match *self {
+            DataType::INT128(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u128(&self) -> Option<u128> {
+        match *self {
+            DataType::UINT128(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            DataType::STR(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // integer value of the scalar variants, for comparisons
+    pub(crate) fn to_i128(&self) -> Option<i128> {
+        use self::DataType::*;
+        Some(match *self {
+            INT8(v) => v as i128,
+            UINT8(v) => v as i128,
+            INT16(v) => v as i128,
+            UINT16(v) => v as i128,
+            INT32(v) => v as i128,
+            UINT32(v) => v as i128,
+            INT64(v) => v as i128,
+            UINT64(v) => v as i128,
+            INT128(v) => v,
+            UINT128(v) => return i128::try_from(v).ok(),
+            _ => return None,
+        })
+    }
+
+    // `v` as the same variant as self, if it fits
+    pub(crate) fn with_i128(&self, v: i128) -> Option<DataType> {
+        use self::DataType::*;
+        Some(match *self {
+            INT8(_) => INT8(i8::try_from(v).ok()?),
+            UINT8(_) => UINT8(u8::try_from(v).ok()?),
+            INT16(_) => INT16(i16::try_from(v).ok()?),
+            UINT16(_) => UINT16(u16::try_from(v).ok()?),
+            INT32(_) => INT32(i32::try_from(v).ok()?),
+            UINT32(_) => UINT32(u32::try_from(v).ok()?),
+            INT64(_) => INT64(i64::try_from(v).ok()?),
+            UINT64(_) => UINT64(u64::try_from(v).ok()?),
+            INT128(_) => INT128(v),
+            UINT128(_) => UINT128(u128::try_from(v).ok()?),
+            _ => return None,
+        })
     }
 
     pub fn read_type<T: Read>(kind: u16, reader: &mut T) -> Result<DataType, Error> {
@@ -229,7 +266,52 @@ impl From<String> for DataType {
     }
 }
 
-#[derive(Debug)]
+// TryFrom<DataType> for the type held by each variant, failing on any other variant
+macro_rules! impl_try_from_data_type {
+    ($($variant:ident($ty:ty)),* $(,)?) => {$(
+        impl TryFrom<DataType> for $ty {
+            type Error = Error;
+
+            fn try_from(value: DataType) -> Result<$ty, Error> {
+                match value {
+                    DataType::$variant(v) => Ok(v),
+                    other => Err(Error::Malformed(format!(
+                        "Expected {}, got {:?}",
+                        stringify!($variant),
+                        other
+                    ))),
+                }
+            }
+        }
+    )*};
+}
+
+impl_try_from_data_type!(
+    INT8(i8),
+    UINT8(u8),
+    INT16(i16),
+    UINT16(u16),
+    INT32(i32),
+    UINT32(u32),
+    INT64(i64),
+    UINT64(u64),
+    INT128(i128),
+    UINT128(u128),
+    AINT8(Vec<i8>),
+    AUINT8(Vec<u8>),
+    AINT16(Vec<i16>),
+    AUINT16(Vec<u16>),
+    AINT32(Vec<i32>),
+    AUINT32(Vec<u32>),
+    AINT64(Vec<i64>),
+    AUINT64(Vec<u64>),
+    AINT128(Vec<i128>),
+    AUINT128(Vec<u128>),
+    STR(String),
+);
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FormData {
     None,
     Range {
@@ -241,3 +323,108 @@ pub enum FormData {
         array: Vec<DataType>,
     },
 }
+
+impl FormData {
+    /// Whether the form allows `value`. Anything is allowed without a form.
+    pub fn contains(&self, value: &DataType) -> bool {
+        match self {
+            FormData::None => true,
+            FormData::Range {
+                min_value,
+                max_value,
+                step,
+            } => match (
+                min_value.to_i128(),
+                max_value.to_i128(),
+                step.to_i128(),
+                value.to_i128(),
+            ) {
+                (Some(min), Some(max), Some(step), Some(v)) => {
+                    min <= v
+                        && v <= max
+                        && (step <= 0 || v.checked_sub(min).is_some_and(|d| d % step == 0))
+                }
+                _ => false,
+            },
+            FormData::Enumeration { array } => array.contains(value),
+        }
+    }
+
+    /// `value` brought within the bounds of a Range, ignoring its step, or the nearest
+    /// value of an Enumeration. `None` if `value` can't be compared with the form.
+    pub fn clamp(&self, value: &DataType) -> Option<DataType> {
+        match self {
+            FormData::Range {
+                min_value,
+                max_value,
+                ..
+            } => {
+                let v = value.to_i128()?;
+                let clamped = v.max(min_value.to_i128()?).min(max_value.to_i128()?);
+                value.with_i128(clamped)
+            }
+            _ => self.nearest(value),
+        }
+    }
+
+    /// The allowed value closest to `value`, snapped to the step of a Range. On a tie
+    /// the lower value wins. `None` if `value` can't be compared with the form.
+    pub fn nearest(&self, value: &DataType) -> Option<DataType> {
+        match self {
+            FormData::None => Some(value.clone()),
+            FormData::Range {
+                min_value,
+                max_value,
+                step,
+            } => {
+                let (min, max, step) =
+                    (min_value.to_i128()?, max_value.to_i128()?, step.to_i128()?);
+                let v = value.to_i128()?.max(min).min(max);
+                if step <= 0 {
+                    return value.with_i128(v);
+                }
+                let below = min + (v - min) / step * step;
+                let above = below + step;
+                let nearest = if above <= max && above - v < v - below {
+                    above
+                } else {
+                    below
+                };
+                value.with_i128(nearest)
+            }
+            FormData::Enumeration { array } => {
+                if array.contains(value) {
+                    return Some(value.clone());
+                }
+                let v = value.to_i128()?;
+                array
+                    .iter()
+                    .filter_map(|x| Some((x, x.to_i128()?.checked_sub(v)?.checked_abs()?)))
+                    .min_by(|(a, da), (b, db)| da.cmp(db).then(a.to_i128().cmp(&b.to_i128())))
+                    .map(|(x, _)| x.clone())
+            }
+        }
+    }
+
+    /// Iterate over the allowed values, every step of a Range or the values of an
+    /// Enumeration. Yields nothing without a form.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = DataType> + '_> {
+        match self {
+            FormData::None => Box::new(std::iter::empty()),
+            FormData::Range {
+                min_value,
+                max_value,
+                step,
+            } => match (min_value.to_i128(), max_value.to_i128(), step.to_i128()) {
+                (Some(min), Some(max), Some(step)) if step > 0 => Box::new(
+                    (0..)
+                        .map(move |i| min + i * step)
+                        .take_while(move |v| *v <= max)
+                        .filter_map(move |v| min_value.with_i128(v)),
+                ),
+                _ => Box::new(std::iter::once(min_value.clone())),
+            },
+            FormData::Enumeration { array } => Box::new(array.iter().cloned()),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use super::Error;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, Offset, TimeZone, Utc};
+
+/// Parse a PTP DateTime string, "YYYYMMDDThhmmss" optionally followed by tenths of a
+/// second (".s") and a UTC offset ("Z" or "±hhmm"). An empty string yields `None`.
+///
+/// Strings without an offset are in the device's local time, which isn't known; they
+/// are returned as UTC, so `naive_local()` gives back the wall clock time of the device.
+pub fn parse_ptp_datetime(s: &str) -> Result<Option<DateTime<FixedOffset>>, Error> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    let malformed = || Error::Malformed(format!("Invalid PTP DateTime {:?}", s));
+
+    let (base, mut rest) = (s.get(..15).ok_or_else(malformed)?, &s[15..]);
+    let mut naive =
+        NaiveDateTime::parse_from_str(base, "%Y%m%dT%H%M%S").map_err(|_| malformed())?;
+
+    if let Some(tenths) = rest.strip_prefix('.') {
+        let digit = tenths.get(..1).ok_or_else(malformed)?;
+        let digit: i64 = digit.parse().map_err(|_| malformed())?;
+        naive += Duration::milliseconds(digit * 100);
+        rest = &tenths[1..];
+    }
+
+    let offset = match rest {
+        "" | "Z" => Utc.fix(),
+        _ if rest.len() == 5 && (rest.starts_with('+') || rest.starts_with('-')) => {
+            let hours = rest.get(1..3).and_then(number).ok_or_else(malformed)?;
+            let minutes = rest.get(3..5).and_then(number).ok_or_else(malformed)?;
+            let seconds = (hours * 60 + minutes) * 60;
+            let offset = if rest.starts_with('-') {
+                FixedOffset::west_opt(seconds)
+            } else {
+                FixedOffset::east_opt(seconds)
+            };
+            offset.ok_or_else(malformed)?
+        }
+        _ => return Err(malformed()),
+    };
+
+    offset
+        .from_local_datetime(&naive)
+        .single()
+        .map(Some)
+        .ok_or_else(malformed)
+}
+
+// digits only, the sign of the offset is read separately
+fn number(digits: &str) -> Option<i32> {
+    if digits.bytes().all(|b| b.is_ascii_digit()) {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Format a date as a PTP DateTime string, "YYYYMMDDThhmmss±hhmm".
+pub fn format_ptp_datetime(datetime: &DateTime<FixedOffset>) -> String {
+    datetime.format("%Y%m%dT%H%M%S%z").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_ptp_datetime("").unwrap(), None);
+
+        let utc = parse_ptp_datetime("20240131T235958").unwrap().unwrap();
+        assert_eq!(utc.to_rfc3339(), "2024-01-31T23:59:58+00:00");
+        let tenths = parse_ptp_datetime("20240131T235958.5Z").unwrap().unwrap();
+        assert_eq!(tenths.timestamp_millis() - utc.timestamp_millis(), 500);
+
+        let east = parse_ptp_datetime("20240131T235958+0130").unwrap().unwrap();
+        assert_eq!(east.to_rfc3339(), "2024-01-31T23:59:58+01:30");
+        let west = parse_ptp_datetime("20240131T235958.1-0800")
+            .unwrap()
+            .unwrap();
+        assert_eq!(west.offset().local_minus_utc(), -8 * 3600);
+    }
+
+    #[test]
+    fn parse_malformed() {
+        for s in &[
+            "2024",
+            "2024013lT235958",
+            "20240131T235958.",
+            "20240131T235958+01",
+            "20240131T235958+1h30",
+            "20240131T235958++130",
+            "20240131T235958+é30",
+            "20240131T235958+0130Z",
+            "20240131T235958+9900",
+        ] {
+            assert!(
+                matches!(parse_ptp_datetime(s), Err(Error::Malformed(_))),
+                "{:?}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let date = parse_ptp_datetime("20240131T235958-0800").unwrap().unwrap();
+        assert_eq!(format_ptp_datetime(&date), "20240131T235958-0800");
+        assert_eq!(
+            parse_ptp_datetime(&format_ptp_datetime(&date)).unwrap(),
+            Some(date)
+        );
+    }
+}
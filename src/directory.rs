@@ -0,0 +1,321 @@
+use super::{
+    AccessCapability, AssociationType, CommandCode, DeviceInfo, Error, FilesystemType, Handler,
+    ObjectFormatCode, ObjectInfo, Reply, StandardCommandCode, StandardObjectFormatCode,
+    StandardResponseCode, StorageInfo, StorageType,
+};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// StorageID of the single store of a `DirectoryDevice`.
+pub const DIRECTORY_STORAGE_ID: u32 = 0x0001_0001;
+
+const OPERATIONS: &[CommandCode] = &[
+    StandardCommandCode::GetDeviceInfo,
+    StandardCommandCode::OpenSession,
+    StandardCommandCode::CloseSession,
+    StandardCommandCode::GetStorageIDs,
+    StandardCommandCode::GetStorageInfo,
+    StandardCommandCode::GetNumObjects,
+    StandardCommandCode::GetObjectHandles,
+    StandardCommandCode::GetObjectInfo,
+    StandardCommandCode::GetObject,
+    StandardCommandCode::GetPartialObject,
+];
+
+struct Node {
+    path: PathBuf,
+    info: ObjectInfo,
+}
+
+/// A virtual camera exposing a directory on disk, read-only, as its single store.
+///
+/// Files and folders are numbered when the device is created, in name order, and their
+/// contents are read on demand. Symbolic links are skipped. Serve it with a `Responder`,
+/// e.g. through `serve_directory`.
+pub struct DirectoryDevice {
+    root: PathBuf,
+    device_info: DeviceInfo,
+    nodes: BTreeMap<u32, Node>,
+    session_open: bool,
+}
+
+impl DirectoryDevice {
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<DirectoryDevice, Error> {
+        let mut device = DirectoryDevice {
+            root: root.as_ref().to_owned(),
+            device_info: DeviceInfo {
+                Version: 100,
+                OperationsSupported: OPERATIONS.to_vec(),
+                Manufacturer: "libptp".to_owned(),
+                Model: "Directory".to_owned(),
+                DeviceVersion: env!("CARGO_PKG_VERSION").to_owned(),
+                ..Default::default()
+            },
+            nodes: BTreeMap::new(),
+            session_open: false,
+        };
+        device.rescan()?;
+        Ok(device)
+    }
+
+    /// The DeviceInfo served, e.g. to set the Model initiators see.
+    pub fn device_info_mut(&mut self) -> &mut DeviceInfo {
+        &mut self.device_info
+    }
+
+    /// Number the contents of the directory again, e.g. after files were added. Handles
+    /// given out before may refer to other objects afterwards.
+    pub fn rescan(&mut self) -> Result<(), Error> {
+        self.nodes.clear();
+        let root = self.root.clone();
+        self.scan(&root, 0)?;
+
+        let mut formats: Vec<_> = self
+            .nodes
+            .values()
+            .map(|n| n.info.ObjectFormat)
+            .filter(|&f| f != StandardObjectFormatCode::Association)
+            .collect();
+        formats.sort_unstable();
+        formats.dedup();
+        self.device_info.ImageFormats = formats;
+        Ok(())
+    }
+
+    fn scan(&mut self, dir: &Path, parent: u32) -> Result<(), Error> {
+        let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|e| e.file_name());
+
+        for entry in entries {
+            let meta = entry.metadata()?;
+            if meta.file_type().is_symlink() {
+                continue;
+            }
+            let path = entry.path();
+            let format = if meta.is_dir() {
+                StandardObjectFormatCode::Association
+            } else {
+                format_of(&path)
+            };
+            let modified = meta.modified().map(ptp_datetime).unwrap_or_default();
+            let info = ObjectInfo {
+                StorageID: DIRECTORY_STORAGE_ID,
+                ObjectFormat: format,
+                ProtectionStatus: 0x0001,
+                ObjectCompressedSize: if meta.is_dir() {
+                    0
+                } else {
+                    meta.len().min(u32::MAX as u64) as u32
+                },
+                ParentObject: parent,
+                AssociationType: if meta.is_dir() {
+                    AssociationType::GenericFolder as u16
+                } else {
+                    0
+                },
+                Filename: entry.file_name().to_string_lossy().into_owned(),
+                CaptureDate: modified.clone(),
+                ModificationDate: modified,
+                ..Default::default()
+            };
+
+            let handle = self.nodes.len() as u32 + 1;
+            self.nodes.insert(
+                handle,
+                Node {
+                    path: path.clone(),
+                    info,
+                },
+            );
+            if meta.is_dir() {
+                self.scan(&path, handle)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn storage_info(&self) -> StorageInfo {
+        let used = self
+            .nodes
+            .values()
+            .map(|n| n.info.ObjectCompressedSize as u64)
+            .sum();
+        StorageInfo {
+            StorageType: StorageType::FixedRam as u16,
+            FilesystemType: FilesystemType::GenericHierarchical as u16,
+            AccessCapability: AccessCapability::ReadOnlyWithoutDeletion as u16,
+            MaxCapacity: used,
+            FreeSpaceInBytes: 0,
+            FreeSpaceInImages: 0,
+            StorageDescription: self.root.display().to_string(),
+            VolumeLabel: self
+                .root
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn read_file(&self, node: &Node, offset: u64, max: Option<u64>) -> Result<Vec<u8>, Error> {
+        let mut file = File::open(&node.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = vec![];
+        match max {
+            Some(max) => file.take(max).read_to_end(&mut data)?,
+            None => file.read_to_end(&mut data)?,
+        };
+        Ok(data)
+    }
+}
+
+impl Handler for DirectoryDevice {
+    fn operation(&mut self, code: CommandCode, params: &[u32], _data: Option<&[u8]>) -> Reply {
+        let param = |i: usize| params.get(i).copied().unwrap_or(0);
+        let encoded = |result: Result<Vec<u8>, Error>| match result {
+            Ok(data) => Reply::data(data),
+            Err(e) => {
+                warn!("Operation 0x{:04x} failed: {}", code, e);
+                Reply::error(StandardResponseCode::GeneralError)
+            }
+        };
+
+        match code {
+            StandardCommandCode::GetDeviceInfo => return encoded(self.device_info.encode()),
+            StandardCommandCode::OpenSession if self.session_open => {
+                return Reply::error(StandardResponseCode::SessionAlreadyOpen)
+            }
+            StandardCommandCode::OpenSession => {
+                self.session_open = true;
+                return Reply::ok();
+            }
+            _ if !self.session_open => return Reply::error(StandardResponseCode::SessionNotOpen),
+            _ => {}
+        }
+
+        match code {
+            StandardCommandCode::CloseSession => {
+                self.session_open = false;
+                Reply::ok()
+            }
+            StandardCommandCode::GetStorageIDs => {
+                let mut out = vec![];
+                out.extend_from_slice(&1u32.to_le_bytes());
+                out.extend_from_slice(&DIRECTORY_STORAGE_ID.to_le_bytes());
+                Reply::data(out)
+            }
+            StandardCommandCode::GetStorageInfo if param(0) == DIRECTORY_STORAGE_ID => {
+                encoded(self.storage_info().encode())
+            }
+            StandardCommandCode::GetStorageInfo => {
+                Reply::error(StandardResponseCode::InvalidStorageId)
+            }
+            StandardCommandCode::GetObjectHandles | StandardCommandCode::GetNumObjects => {
+                if param(0) != DIRECTORY_STORAGE_ID && param(0) != 0xFFFF_FFFF {
+                    return Reply::error(StandardResponseCode::InvalidStorageId);
+                }
+                let handles: Vec<u32> = self
+                    .nodes
+                    .iter()
+                    .filter(|(_, n)| param(1) == 0 || n.info.ObjectFormat as u32 == param(1))
+                    .filter(|(_, n)| match param(2) {
+                        0 => true,
+                        0xFFFF_FFFF => n.info.ParentObject == 0,
+                        parent => n.info.ParentObject == parent,
+                    })
+                    .map(|(&h, _)| h)
+                    .collect();
+                if code == StandardCommandCode::GetNumObjects {
+                    return Reply::ok().with_params(&[handles.len() as u32]);
+                }
+                let mut out = (handles.len() as u32).to_le_bytes().to_vec();
+                for handle in handles {
+                    out.extend_from_slice(&handle.to_le_bytes());
+                }
+                Reply::data(out)
+            }
+            StandardCommandCode::GetObjectInfo
+            | StandardCommandCode::GetObject
+            | StandardCommandCode::GetPartialObject => {
+                let node = match self.nodes.get(&param(0)) {
+                    Some(node) => node,
+                    None => return Reply::error(StandardResponseCode::InvalidObjectHandle),
+                };
+                match code {
+                    StandardCommandCode::GetObjectInfo => encoded(node.info.encode()),
+                    _ if StandardObjectFormatCode::is_association(node.info.ObjectFormat) => {
+                        Reply::error(StandardResponseCode::InvalidObjectHandle)
+                    }
+                    StandardCommandCode::GetObject => encoded(self.read_file(node, 0, None)),
+                    _ => match self.read_file(node, param(1) as u64, Some(param(2) as u64)) {
+                        Ok(data) => {
+                            let len = data.len() as u32;
+                            Reply::data(data).with_params(&[len])
+                        }
+                        Err(e) => encoded(Err(e)),
+                    },
+                }
+            }
+            _ => Reply::error(StandardResponseCode::OperationNotSupported),
+        }
+    }
+}
+
+// the format of a file, guessed from its extension
+fn format_of(path: &Path) -> ObjectFormatCode {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jpg" | "jpeg" => StandardObjectFormatCode::ExifJpeg,
+        "tif" | "tiff" => StandardObjectFormatCode::Tiff,
+        "png" => StandardObjectFormatCode::Png,
+        "gif" => StandardObjectFormatCode::Gif,
+        "bmp" => StandardObjectFormatCode::Bmp,
+        "dng" => StandardObjectFormatCode::Dng,
+        "txt" => StandardObjectFormatCode::Text,
+        "wav" => StandardObjectFormatCode::Wav,
+        "mp3" => StandardObjectFormatCode::Mp3,
+        "avi" => StandardObjectFormatCode::Avi,
+        "mov" => StandardObjectFormatCode::QuickTime,
+        "mp4" => StandardObjectFormatCode::Mp4,
+        // raw formats of the camera vendors have no code of their own
+        "cr2" | "cr3" | "nef" | "arw" | "raf" | "orf" | "rw2" => {
+            StandardObjectFormatCode::UndefinedImage
+        }
+        _ => StandardObjectFormatCode::Undefined,
+    }
+}
+
+// a PTP DateTime in UTC, "YYYYMMDDThhmmss"
+fn ptp_datetime(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => return String::new(),
+    };
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // civil date from days since the epoch, after Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,324 @@
+use super::{Camera, Error, MtpCommandCode, Transport};
+use std::fs::OpenOptions;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Downloads an object in GetPartialObject windows, keeping track of how far it got so
+/// the transfer can continue after a USB error or a reconnect instead of starting over.
+///
+/// `offset` only advances once a window has been handed to the sink, so after a failure
+/// the same `ResumableDownload` (or one rebuilt with `resume` from a persisted offset)
+/// can be run again, possibly against a freshly opened `Camera`.
+///
+/// The size of the object may be unknown, see `Camera::get_object_size`; the download is
+/// then complete once the device returns a window shorter than requested.
+#[derive(Debug, Clone)]
+pub struct ResumableDownload {
+    handle: u32,
+    size: Option<u64>,
+    offset: u64,
+    window: u32,
+    partial64: bool,
+    // a short window was received, ending an object of unknown size
+    ended: bool,
+}
+
+impl ResumableDownload {
+    pub fn new(handle: u32, size: Option<u64>) -> ResumableDownload {
+        ResumableDownload::resume(handle, size, 0)
+    }
+
+    /// Continue a download of which `offset` bytes were already received.
+    pub fn resume(handle: u32, size: Option<u64>, offset: u64) -> ResumableDownload {
+        ResumableDownload {
+            handle,
+            size,
+            offset,
+            window: 1024 * 1024,
+            partial64: false,
+            ended: false,
+        }
+    }
+
+    /// Set the number of bytes requested per GetPartialObject, 1MB by default. A window of
+    /// 0 would never make progress and is raised to 1.
+    pub fn with_window(mut self, window: u32) -> ResumableDownload {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Use MTP GetPartialObject64, required for objects over 4GB on devices supporting it.
+    pub fn with_partial64(mut self, partial64: bool) -> ResumableDownload {
+        self.partial64 = partial64;
+        self
+    }
+
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Number of bytes received so far, to be persisted for a later `resume`.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match self.size {
+            Some(size) => self.offset >= size,
+            None => self.ended,
+        }
+    }
+
+    /// Fetch the next window into `sink`, returning the number of bytes received.
+    pub fn next_window<T: Transport, W: Write + ?Sized>(
+        &mut self,
+        camera: &mut Camera<T>,
+        sink: &mut W,
+        timeout: Option<Duration>,
+    ) -> Result<usize, Error> {
+        if self.is_complete() {
+            return Ok(0);
+        }
+        let data = if self.partial64 {
+            camera.get_partialobject64(self.handle, self.offset, self.window, timeout)?
+        } else if self.offset <= u32::MAX as u64 {
+            camera.get_partialobject(self.handle, self.offset as u32, self.window, timeout)?
+        } else {
+            return Err(Error::Malformed(format!(
+                "Offset {} is out of range for GetPartialObject, use GetPartialObject64",
+                self.offset
+            )));
+        };
+        match self.size {
+            Some(size) if data.is_empty() => {
+                return Err(Error::Malformed(format!(
+                    "GetPartialObject returned no data at offset {} of {}",
+                    self.offset, size
+                )));
+            }
+            Some(_) => {}
+            None => self.ended = data.len() < self.window as usize,
+        }
+        sink.write_all(&data)?;
+        self.offset += data.len() as u64;
+        trace!(
+            "partial download of 0x{:08x}: {}/{:?}",
+            self.handle,
+            self.offset,
+            self.size
+        );
+        Ok(data.len())
+    }
+
+    /// Fetch windows until the object is complete or an error occurs.
+    pub fn run<T: Transport, W: Write + ?Sized>(
+        &mut self,
+        camera: &mut Camera<T>,
+        sink: &mut W,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        while !self.is_complete() {
+            self.next_window(camera, sink, timeout)?;
+        }
+        Ok(())
+    }
+
+    /// Download an object to `path`, resuming from whatever a previous attempt already
+    /// wrote to the file. GetPartialObject64 is used if the device supports it, so objects
+    /// over 4GB download whole.
+    pub fn download_to_file<T: Transport, P: AsRef<Path>>(
+        camera: &mut Camera<T>,
+        handle: u32,
+        path: P,
+        timeout: Option<Duration>,
+    ) -> Result<ResumableDownload, Error> {
+        let size = camera.get_object_size(handle, timeout)?;
+        let partial64 = camera.supports_operation(MtpCommandCode::GetPartialObject64) == Some(true);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        let mut offset = file.seek(SeekFrom::End(0))?;
+        if let Some(size) = size {
+            offset = offset.min(size);
+        }
+        file.set_len(offset)?;
+        file.seek(SeekFrom::Start(offset))?;
+
+        let mut download =
+            ResumableDownload::resume(handle, size, offset).with_partial64(partial64);
+        download.run(camera, &mut file, timeout)?;
+        Ok(download)
+    }
+}
+
+/// A hash computed over an object as it downloads, see `VerifiedDownload`.
+///
+/// Implement it for the hasher of choice, e.g. a SHA-256 wrapped in a newtype. `()`
+/// computes nothing.
+pub trait StreamDigest {
+    type Output;
+
+    fn update(&mut self, data: &[u8]);
+
+    fn finish(self) -> Self::Output;
+}
+
+impl StreamDigest for () {
+    type Output = ();
+
+    fn update(&mut self, _data: &[u8]) {}
+
+    fn finish(self) {}
+}
+
+/// Outcome of a `VerifiedDownload`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verified<D> {
+    /// Number of bytes received.
+    pub size: u64,
+    /// Number of ranges read again with GetPartialObject and found identical.
+    pub samples_checked: usize,
+    pub digest: D,
+}
+
+/// Downloads an object with GetObject while checking it arrived intact: the byte count
+/// has to match ObjectCompressedSize, and optionally a few ranges picked at random are
+/// read again with GetPartialObject and compared against what was received.
+///
+/// A mismatch fails the download with `Error::Malformed`; the sink has received the data
+/// regardless, so the caller is responsible for discarding it.
+#[derive(Debug, Clone)]
+pub struct VerifiedDownload {
+    handle: u32,
+    samples: usize,
+    sample_len: u32,
+}
+
+impl VerifiedDownload {
+    pub fn new(handle: u32) -> VerifiedDownload {
+        VerifiedDownload {
+            handle,
+            samples: 0,
+            sample_len: 4096,
+        }
+    }
+
+    /// Cross-check `samples` ranges of `sample_len` bytes, none by default.
+    pub fn with_samples(mut self, samples: usize, sample_len: u32) -> VerifiedDownload {
+        self.samples = samples;
+        self.sample_len = sample_len;
+        self
+    }
+
+    /// Download into `sink`, feeding the data through `digest` on the way.
+    pub fn run<T: Transport, W: Write + ?Sized, D: StreamDigest>(
+        &self,
+        camera: &mut Camera<T>,
+        sink: &mut W,
+        digest: D,
+        timeout: Option<Duration>,
+    ) -> Result<Verified<D::Output>, Error> {
+        let info = camera.get_objectinfo(self.handle, timeout)?;
+        // ObjectCompressedSize saturates for objects of 4GB and more
+        let expected = match info.ObjectCompressedSize {
+            u32::MAX => None,
+            size => Some(size as u64),
+        };
+
+        let samples = match expected {
+            Some(size) if size > 0 => self.pick_samples(size),
+            _ => vec![],
+        };
+        let mut writer = VerifyingWriter {
+            inner: sink,
+            digest,
+            offset: 0,
+            samples: samples.into_iter().map(|s| (s, vec![])).collect(),
+        };
+        camera.get_object_to(self.handle, &mut writer, timeout)?;
+
+        let size = writer.offset;
+        if expected.is_some_and(|expected| expected != size) {
+            return Err(Error::Malformed(format!(
+                "Received {} bytes of object 0x{:08x}, its ObjectCompressedSize is {}",
+                size, self.handle, info.ObjectCompressedSize
+            )));
+        }
+
+        for ((offset, len), received) in &writer.samples {
+            let reread = camera.get_partialobject(self.handle, *offset as u32, *len, timeout)?;
+            if &reread != received {
+                return Err(Error::Malformed(format!(
+                    "Object 0x{:08x} differs from GetPartialObject at offset {}",
+                    self.handle, offset
+                )));
+            }
+        }
+
+        Ok(Verified {
+            size,
+            samples_checked: writer.samples.len(),
+            digest: writer.digest.finish(),
+        })
+    }
+
+    // (offset, len) of the ranges to cross-check, within an object of `size` bytes
+    fn pick_samples(&self, size: u64) -> Vec<(u64, u32)> {
+        let len = (self.sample_len as u64).min(size);
+        // not worth a dependency, an xorshift seeded from the clock is random enough
+        let mut state = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+            | 1;
+        (0..self.samples)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % (size - len + 1), len as u32)
+            })
+            .collect()
+    }
+}
+
+// passes the data through to the sink, hashing it and keeping the sampled ranges
+struct VerifyingWriter<'a, W: Write + ?Sized, D> {
+    inner: &'a mut W,
+    digest: D,
+    offset: u64,
+    samples: Vec<((u64, u32), Vec<u8>)>,
+}
+
+impl<W: Write + ?Sized, D: StreamDigest> Write for VerifyingWriter<'_, W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        let data = &buf[..n];
+        self.digest.update(data);
+
+        let end = self.offset + n as u64;
+        for ((offset, len), received) in &mut self.samples {
+            let start = (*offset).max(self.offset);
+            let stop = (*offset + *len as u64).min(end);
+            if start < stop {
+                received.extend_from_slice(
+                    &data[(start - self.offset) as usize..(stop - self.offset) as usize],
+                );
+            }
+        }
+        self.offset = end;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
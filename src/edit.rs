@@ -0,0 +1,92 @@
+use super::{Camera, Error, Transport};
+use std::time::Duration;
+
+/// In-place modification of an existing object on an MTP device, see `Camera::edit_object`.
+///
+/// Wraps BeginEditObject, SendPartialObject, TruncateObject and EndEditObject. The changes
+/// are committed by `commit`; MTP has no way to discard them, so dropping the session
+/// commits as well, ignoring any error.
+pub struct EditSession<'a, T: Transport> {
+    camera: &'a mut Camera<T>,
+    handle: u32,
+    len: u64,
+    timeout: Option<Duration>,
+    open: bool,
+}
+
+impl<'a, T: Transport> EditSession<'a, T> {
+    pub(crate) fn begin(
+        camera: &'a mut Camera<T>,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<EditSession<'a, T>, Error> {
+        // appending at a saturated ObjectCompressedSize would overwrite the object
+        let len = camera.get_object_size(handle, timeout)?.ok_or_else(|| {
+            Error::Malformed(format!(
+                "Size of object 0x{:08x} is unknown, it can't be edited",
+                handle
+            ))
+        })?;
+        camera.begin_edit_object(handle, timeout)?;
+        Ok(EditSession {
+            camera,
+            handle,
+            len,
+            timeout,
+            open: true,
+        })
+    }
+
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+
+    /// Size of the object as far as this session knows, starting from
+    /// `Camera::get_object_size`.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Overwrite the object from `offset` with `data`, extending it if needed.
+    /// Returns the number of bytes the device accepted.
+    pub fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<u32, Error> {
+        let written = self
+            .camera
+            .send_partial_object(self.handle, offset, data, self.timeout)?;
+        self.len = self.len.max(offset + written as u64);
+        Ok(written)
+    }
+
+    /// Append `data` at the end of the object.
+    pub fn append(&mut self, data: &[u8]) -> Result<u32, Error> {
+        self.write_at(self.len, data)
+    }
+
+    /// Cut the object down to `len` bytes.
+    pub fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        self.camera
+            .truncate_object(self.handle, len, self.timeout)?;
+        self.len = len;
+        Ok(())
+    }
+
+    /// End the edit, committing the changes.
+    pub fn commit(mut self) -> Result<(), Error> {
+        self.open = false;
+        self.camera.end_edit_object(self.handle, self.timeout)
+    }
+}
+
+impl<T: Transport> Drop for EditSession<'_, T> {
+    fn drop(&mut self) {
+        if self.open {
+            if let Err(e) = self.camera.end_edit_object(self.handle, self.timeout) {
+                warn!("ending edit of 0x{:08x} failed: {}", self.handle, e);
+            }
+        }
+    }
+}
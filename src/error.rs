@@ -1,4 +1,4 @@
-use super::StandardResponseCode;
+use super::{MtpCommandCode, MtpResponseCode, StandardCommandCode, StandardResponseCode};
 use std::{fmt, io};
 
 /// An error in a PTP command
@@ -7,6 +7,23 @@ pub enum Error {
     /// PTP Responder returned a status code other than Ok, either a constant in StandardResponseCode or a vendor-defined code
     Response(u16),
 
+    /// The object has no thumbnail, the full object has to be fetched instead
+    NoThumbnail,
+
+    /// The object is write-protected (ObjectWriteProtected response)
+    WriteProtected,
+
+    /// The transfer was aborted through a `CancelToken`
+    Cancelled,
+
+    /// The operation isn't in the OperationsSupported of the device, and wasn't sent
+    /// because `CameraConfig::check_operations` is enabled
+    NotAdvertised(u16),
+
+    /// The operation would modify the device, and wasn't sent because
+    /// `CameraConfig::read_only` is enabled
+    ReadOnly(u16),
+
     /// Data received was malformed
     Malformed(String),
 
@@ -23,9 +40,30 @@ impl fmt::Display for Error {
             Error::Response(r) => write!(
                 f,
                 "{} (0x{:04x})",
-                StandardResponseCode::name(r).unwrap_or("Unknown"),
+                StandardResponseCode::name(r)
+                    .or_else(|| MtpResponseCode::name(r))
+                    .unwrap_or("Unknown"),
                 r
             ),
+            Error::NoThumbnail => write!(f, "No thumbnail present"),
+            Error::WriteProtected => write!(f, "Object is write-protected"),
+            Error::Cancelled => write!(f, "Transfer cancelled"),
+            Error::ReadOnly(code) => write!(
+                f,
+                "Operation 0x{:04x} ({}) refused in read-only mode",
+                code,
+                StandardCommandCode::name(code)
+                    .or_else(|| MtpCommandCode::name(code))
+                    .unwrap_or("unknown")
+            ),
+            Error::NotAdvertised(code) => write!(
+                f,
+                "Operation 0x{:04x} ({}) is not supported by the device",
+                code,
+                StandardCommandCode::name(code)
+                    .or_else(|| MtpCommandCode::name(code))
+                    .unwrap_or("unknown")
+            ),
             Error::Usb(ref e) => write!(f, "USB error: {}", e),
             Error::Io(ref e) => write!(f, "IO error: {}", e),
             Error::Malformed(ref e) => write!(f, "{}", e),
@@ -34,7 +72,7 @@ impl fmt::Display for Error {
 }
 
 impl ::std::error::Error for Error {
-    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+    fn cause(&self) -> Option<&dyn ::std::error::Error> {
         match *self {
             Error::Usb(ref e) => Some(e),
             Error::Io(ref e) => Some(e),
@@ -0,0 +1,86 @@
+use super::{Camera, Error, Transport};
+use std::time::Duration;
+
+// how much of the object is fetched first, enough for the EXIF segment of most JPEGs
+const EXIF_WINDOW: u32 = 64 * 1024;
+
+// where the TIFF block lies in the start of an object: JPEGs carry it in an APP1 segment,
+// and TIFF-based raw formats (CR2, NEF, ARW, DNG...) are one
+enum TiffBlock {
+    Range(usize, usize),
+    WholeFile,
+}
+
+fn locate_tiff(data: &[u8]) -> Result<TiffBlock, Error> {
+    if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        return Ok(TiffBlock::WholeFile);
+    }
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err(Error::Malformed(
+            "Object is neither a JPEG nor a TIFF-based file".to_string(),
+        ));
+    }
+
+    // walk the segments up to the image data
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            break;
+        }
+        let marker = data[pos + 1];
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let body = pos + 4;
+        if marker == 0xE1 && data.get(body..body + 6) == Some(b"Exif\0\0") {
+            return Ok(TiffBlock::Range(body + 6, pos + 2 + len));
+        }
+        pos += 2 + len;
+    }
+    Err(Error::Malformed(
+        "No EXIF segment found in the start of the JPEG".to_string(),
+    ))
+}
+
+impl<T: Transport> Camera<T> {
+    /// Read the EXIF data of a JPEG or a TIFF-based raw file, returned as the raw TIFF
+    /// block, without downloading the whole object.
+    ///
+    /// Only the start of the object is fetched with GetPartialObject. For raw files that
+    /// is the first 64KB, which normally covers IFD0 and the EXIF IFD, but tags whose
+    /// values lie further into the file can't be resolved.
+    pub fn get_exif(&mut self, handle: u32, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
+        let mut data = self.get_partialobject(handle, 0, EXIF_WINDOW, timeout)?;
+        match locate_tiff(&data)? {
+            TiffBlock::WholeFile => Ok(data),
+            TiffBlock::Range(start, end) => {
+                if end > data.len() {
+                    let rest = self.get_partialobject(
+                        handle,
+                        data.len() as u32,
+                        (end - data.len()) as u32,
+                        timeout,
+                    )?;
+                    data.extend_from_slice(&rest);
+                }
+                data.get(start..end).map(<[u8]>::to_vec).ok_or_else(|| {
+                    Error::Malformed("EXIF segment extends past the end of the object".to_string())
+                })
+            }
+        }
+    }
+
+    /// `get_exif`, parsed.
+    #[cfg(feature = "kamadak-exif")]
+    pub fn get_exif_parsed(
+        &mut self,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<::exif::Exif, Error> {
+        let tiff = self.get_exif(handle, timeout)?;
+        ::exif::Reader::new()
+            .read_raw(tiff)
+            .map_err(|e| Error::Malformed(format!("Invalid EXIF data: {}", e)))
+    }
+}
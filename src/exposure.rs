@@ -0,0 +1,597 @@
+use super::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Marketed f-numbers in full, half and third stops, used to print decoded apertures.
+const NOMINAL_APERTURES: &[f64] = &[
+    1.0, 1.1, 1.2, 1.4, 1.6, 1.8, 2.0, 2.2, 2.5, 2.8, 3.2, 3.5, 4.0, 4.5, 5.0, 5.6, 6.3, 6.7, 7.1,
+    8.0, 9.0, 9.5, 10.0, 11.0, 13.0, 14.0, 16.0, 18.0, 19.0, 20.0, 22.0, 25.0, 27.0, 29.0, 32.0,
+    36.0, 38.0, 40.0, 45.0, 51.0, 54.0, 57.0, 64.0, 72.0, 76.0, 80.0, 91.0,
+];
+
+/// Marketed exposure times in seconds, used to print decoded shutter speeds.
+const NOMINAL_SHUTTER_SPEEDS: &[f64] = &[
+    1.0 / 8000.0,
+    1.0 / 6400.0,
+    1.0 / 5000.0,
+    1.0 / 4000.0,
+    1.0 / 3200.0,
+    1.0 / 2500.0,
+    1.0 / 2000.0,
+    1.0 / 1600.0,
+    1.0 / 1250.0,
+    1.0 / 1000.0,
+    1.0 / 800.0,
+    1.0 / 750.0,
+    1.0 / 640.0,
+    1.0 / 500.0,
+    1.0 / 400.0,
+    1.0 / 350.0,
+    1.0 / 320.0,
+    1.0 / 250.0,
+    1.0 / 200.0,
+    1.0 / 180.0,
+    1.0 / 160.0,
+    1.0 / 125.0,
+    1.0 / 100.0,
+    1.0 / 90.0,
+    1.0 / 80.0,
+    1.0 / 60.0,
+    1.0 / 50.0,
+    1.0 / 45.0,
+    1.0 / 40.0,
+    1.0 / 30.0,
+    1.0 / 25.0,
+    1.0 / 20.0,
+    1.0 / 15.0,
+    1.0 / 13.0,
+    1.0 / 10.0,
+    1.0 / 8.0,
+    1.0 / 6.0,
+    1.0 / 5.0,
+    1.0 / 4.0,
+    1.0 / 3.0,
+    0.4,
+    0.5,
+    0.6,
+    0.8,
+    1.0,
+    1.3,
+    1.5,
+    1.6,
+    2.0,
+    2.5,
+    3.0,
+    3.2,
+    4.0,
+    5.0,
+    6.0,
+    8.0,
+    10.0,
+    13.0,
+    15.0,
+    20.0,
+    25.0,
+    30.0,
+];
+
+// snap a value computed from a logarithmic encoding to the nearest marketed one
+fn snap(value: f64, nominal: &[f64]) -> f64 {
+    nominal
+        .iter()
+        .copied()
+        .min_by(|a, b| {
+            let da = (a.ln() - value.ln()).abs();
+            let db = (b.ln() - value.ln()).abs();
+            da.total_cmp(&db)
+        })
+        .filter(|n| (n.ln() - value.ln()).abs() < 0.1)
+        .unwrap_or(value)
+}
+
+/// Marketed ISO speeds in third stops, used to print decoded sensitivities.
+const NOMINAL_ISOS: &[f64] = &[
+    50.0, 64.0, 80.0, 100.0, 125.0, 160.0, 200.0, 250.0, 320.0, 400.0, 500.0, 640.0, 800.0, 1000.0,
+    1250.0, 1600.0, 2000.0, 2500.0, 3200.0, 4000.0, 5000.0, 6400.0, 8000.0, 10000.0, 12800.0,
+    16000.0, 20000.0, 25600.0, 32000.0, 40000.0, 51200.0, 64000.0, 80000.0, 102400.0, 204800.0,
+    409600.0, 819200.0,
+];
+
+// Canon counts stops in eighths, with thirds encoded as 3 and 5 and halves as 4
+fn canon_eighths_to_stops(v: i32) -> f64 {
+    let (stops, rest) = (v.div_euclid(8), v.rem_euclid(8));
+    let fraction = match rest {
+        3 => 1.0 / 3.0,
+        5 => 2.0 / 3.0,
+        r => r as f64 / 8.0,
+    };
+    stops as f64 + fraction
+}
+
+fn canon_stops_to_eighths(stops: f64) -> i32 {
+    let whole = stops.floor();
+    let rest = stops - whole;
+    let eighths = [(0.0, 0), (1.0 / 3.0, 3), (0.5, 4), (2.0 / 3.0, 5), (1.0, 8)]
+        .iter()
+        .min_by(|a, b| (a.0 - rest).abs().total_cmp(&(b.0 - rest).abs()))
+        .map_or(0, |e| e.1);
+    whole as i32 * 8 + eighths
+}
+
+fn parse_number(s: &str, what: &str) -> Result<f64, Error> {
+    s.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| Error::Malformed(format!("Invalid {} {:?}", what, s)))
+}
+
+/// Lens aperture as an f-number.
+///
+/// Nikon and Sony use the standard FNumber encoding, f-number * 100. Canon EOS encodes
+/// the APEX value in eighths of a stop, offset by one stop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aperture(pub f64);
+
+impl Aperture {
+    pub fn from_ptp(v: u16) -> Aperture {
+        Aperture(v as f64 / 100.0)
+    }
+
+    pub fn to_ptp(self) -> u16 {
+        (self.0 * 100.0).round() as u16
+    }
+
+    /// APEX aperture value, Av = 2 * log2(N).
+    pub fn from_apex(av: f64) -> Aperture {
+        Aperture(snap(2f64.powf(av / 2.0), NOMINAL_APERTURES))
+    }
+
+    pub fn to_apex(self) -> f64 {
+        2.0 * self.0.log2()
+    }
+
+    pub fn from_canon_eos(v: u32) -> Aperture {
+        Aperture::from_apex(canon_eighths_to_stops(v as i32 - 8))
+    }
+
+    pub fn to_canon_eos(self) -> u32 {
+        (canon_stops_to_eighths(self.to_apex()) + 8).max(0) as u32
+    }
+}
+
+/// Parses "f/2.8", "F2.8" or "2.8".
+impl FromStr for Aperture {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Aperture, Error> {
+        let v = s.trim();
+        let v = v
+            .strip_prefix("f/")
+            .or_else(|| v.strip_prefix("F/"))
+            .or_else(|| v.strip_prefix(['f', 'F']))
+            .unwrap_or(v);
+        match parse_number(v, "aperture")? {
+            n if n > 0.0 => Ok(Aperture(n)),
+            _ => Err(Error::Malformed(format!("Invalid aperture {:?}", s))),
+        }
+    }
+}
+
+impl fmt::Display for Aperture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 >= 10.0 {
+            write!(f, "f/{}", self.0.round())
+        } else {
+            write!(f, "f/{:.1}", self.0)
+        }
+    }
+}
+
+/// Exposure time.
+///
+/// Nikon uses the standard ExposureTime encoding, in units of 0.1ms. Canon EOS encodes
+/// the APEX value in eighths of a stop, 0x38 being 1". Sony packs the exposure time as
+/// a fraction, numerator in the upper 16 bits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShutterSpeed {
+    Bulb,
+    Seconds(f64),
+}
+
+const PTP_SHUTTER_BULB: u32 = 0xFFFF_FFFF;
+const CANON_EOS_SHUTTER_BULB: u32 = 0x0C;
+const SONY_SHUTTER_BULB: u32 = 0;
+
+impl ShutterSpeed {
+    pub fn from_ptp(v: u32) -> ShutterSpeed {
+        match v {
+            PTP_SHUTTER_BULB => ShutterSpeed::Bulb,
+            v => ShutterSpeed::Seconds(v as f64 / 10000.0),
+        }
+    }
+
+    pub fn to_ptp(self) -> u32 {
+        match self {
+            ShutterSpeed::Bulb => PTP_SHUTTER_BULB,
+            ShutterSpeed::Seconds(s) => (s * 10000.0).round() as u32,
+        }
+    }
+
+    /// APEX time value, Tv = -log2(t).
+    pub fn from_apex(tv: f64) -> ShutterSpeed {
+        ShutterSpeed::Seconds(snap(2f64.powf(-tv), NOMINAL_SHUTTER_SPEEDS))
+    }
+
+    /// APEX time value, `None` for bulb.
+    pub fn to_apex(self) -> Option<f64> {
+        match self {
+            ShutterSpeed::Bulb => None,
+            ShutterSpeed::Seconds(s) => Some(-s.log2()),
+        }
+    }
+
+    pub fn from_canon_eos(v: u32) -> ShutterSpeed {
+        match v {
+            CANON_EOS_SHUTTER_BULB => ShutterSpeed::Bulb,
+            v => ShutterSpeed::from_apex(canon_eighths_to_stops(v as i32 - 0x38)),
+        }
+    }
+
+    pub fn to_canon_eos(self) -> u32 {
+        match self.to_apex() {
+            None => CANON_EOS_SHUTTER_BULB,
+            Some(tv) => (canon_stops_to_eighths(tv) + 0x38).max(0x10) as u32,
+        }
+    }
+
+    pub fn from_sony(v: u32) -> ShutterSpeed {
+        let (num, den) = (v >> 16, v & 0xFFFF);
+        if v == SONY_SHUTTER_BULB || den == 0 {
+            ShutterSpeed::Bulb
+        } else {
+            ShutterSpeed::Seconds(num as f64 / den as f64)
+        }
+    }
+
+    /// Fractions of a second are encoded as 1/n, longer times in tenths of a second.
+    pub fn to_sony(self) -> u32 {
+        match self {
+            ShutterSpeed::Bulb => SONY_SHUTTER_BULB,
+            ShutterSpeed::Seconds(s) if s < 0.4 => (1 << 16) | (1.0 / s).round() as u32,
+            ShutterSpeed::Seconds(s) => (((s * 10.0).round() as u32) << 16) | 10,
+        }
+    }
+}
+
+/// Parses "bulb", "1/250", "2\"", "2s" or "0.5".
+impl FromStr for ShutterSpeed {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ShutterSpeed, Error> {
+        let v = s.trim();
+        if v.eq_ignore_ascii_case("bulb") {
+            return Ok(ShutterSpeed::Bulb);
+        }
+        let seconds = match v.split_once('/') {
+            Some((num, den)) => {
+                parse_number(num, "shutter speed")? / parse_number(den, "shutter speed")?
+            }
+            None => parse_number(v.trim_end_matches(['"', 's']), "shutter speed")?,
+        };
+        if seconds.is_finite() && seconds > 0.0 {
+            Ok(ShutterSpeed::Seconds(seconds))
+        } else {
+            Err(Error::Malformed(format!("Invalid shutter speed {:?}", s)))
+        }
+    }
+}
+
+impl fmt::Display for ShutterSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ShutterSpeed::Bulb => write!(f, "bulb"),
+            ShutterSpeed::Seconds(s) if s < 0.4 => write!(f, "1/{}", (1.0 / s).round()),
+            ShutterSpeed::Seconds(s) if s.fract() == 0.0 => write!(f, "{}\"", s),
+            ShutterSpeed::Seconds(s) => write!(f, "{:.1}\"", s),
+        }
+    }
+}
+
+/// Sensitivity.
+///
+/// Nikon uses the standard ExposureIndex encoding, the ISO number with 0xFFFF for auto.
+/// Canon EOS encodes the APEX speed value in eighths of a stop, 0x48 being ISO 100 and
+/// 0 auto. Sony uses the ISO number with 0xFFFFFF for auto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Iso {
+    Auto,
+    Value(u32),
+}
+
+const PTP_ISO_AUTO: u16 = 0xFFFF;
+const CANON_EOS_ISO_AUTO: u32 = 0;
+const SONY_ISO_AUTO: u32 = 0x00FF_FFFF;
+
+impl Iso {
+    pub fn from_ptp(v: u16) -> Iso {
+        match v {
+            PTP_ISO_AUTO => Iso::Auto,
+            v => Iso::Value(v as u32),
+        }
+    }
+
+    pub fn to_ptp(self) -> u16 {
+        match self {
+            Iso::Auto => PTP_ISO_AUTO,
+            Iso::Value(v) => v.min(PTP_ISO_AUTO as u32 - 1) as u16,
+        }
+    }
+
+    pub fn from_canon_eos(v: u32) -> Iso {
+        match v {
+            CANON_EOS_ISO_AUTO => Iso::Auto,
+            v => {
+                let iso = 100.0 * 2f64.powf(canon_eighths_to_stops(v as i32 - 0x48));
+                Iso::Value(snap(iso, NOMINAL_ISOS).round() as u32)
+            }
+        }
+    }
+
+    pub fn to_canon_eos(self) -> u32 {
+        match self {
+            Iso::Auto => CANON_EOS_ISO_AUTO,
+            Iso::Value(v) => {
+                (canon_stops_to_eighths((v.max(1) as f64 / 100.0).log2()) + 0x48).max(1) as u32
+            }
+        }
+    }
+
+    pub fn from_sony(v: u32) -> Iso {
+        match v & 0x00FF_FFFF {
+            SONY_ISO_AUTO => Iso::Auto,
+            v => Iso::Value(v),
+        }
+    }
+
+    pub fn to_sony(self) -> u32 {
+        match self {
+            Iso::Auto => SONY_ISO_AUTO,
+            Iso::Value(v) => v.min(SONY_ISO_AUTO - 1),
+        }
+    }
+}
+
+/// Parses "auto", "ISO 400" or "400".
+impl FromStr for Iso {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Iso, Error> {
+        let v = s.trim();
+        let v = v
+            .strip_prefix("ISO")
+            .or_else(|| v.strip_prefix("iso"))
+            .unwrap_or(v)
+            .trim();
+        if v.eq_ignore_ascii_case("auto") {
+            return Ok(Iso::Auto);
+        }
+        v.parse::<u32>()
+            .ok()
+            .filter(|v| *v > 0)
+            .map(Iso::Value)
+            .ok_or_else(|| Error::Malformed(format!("Invalid ISO {:?}", s)))
+    }
+}
+
+impl fmt::Display for Iso {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Iso::Auto => write!(f, "ISO auto"),
+            Iso::Value(v) => write!(f, "ISO {}", v),
+        }
+    }
+}
+
+/// Exposure compensation in EV.
+///
+/// Nikon and Sony use the standard ExposureBiasCompensation encoding, an i16 in
+/// thousandths of a stop. Canon EOS uses an i8 in eighths of a stop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExposureCompensation(pub f64);
+
+impl ExposureCompensation {
+    pub fn from_ptp(v: i16) -> ExposureCompensation {
+        ExposureCompensation(v as f64 / 1000.0)
+    }
+
+    pub fn to_ptp(self) -> i16 {
+        (self.0 * 1000.0).round() as i16
+    }
+
+    pub fn from_canon_eos(v: u32) -> ExposureCompensation {
+        let eighths = v as u8 as i8 as i32;
+        ExposureCompensation(canon_eighths_to_stops(eighths.abs()).copysign(eighths as f64))
+    }
+
+    pub fn to_canon_eos(self) -> u32 {
+        let eighths = canon_stops_to_eighths(self.0.abs()) * self.0.signum() as i32;
+        eighths as i8 as u8 as u32
+    }
+}
+
+/// Parses "+0.7 EV", "-1/3", "-1" or "0".
+impl FromStr for ExposureCompensation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ExposureCompensation, Error> {
+        let v = s.trim();
+        let v = v
+            .strip_suffix("EV")
+            .or_else(|| v.strip_suffix("ev"))
+            .unwrap_or(v)
+            .trim();
+        let v = v.strip_prefix('+').unwrap_or(v);
+        let ev = match v.split_once('/') {
+            Some((num, den)) => {
+                parse_number(num, "exposure compensation")?
+                    / parse_number(den, "exposure compensation")?
+            }
+            None => parse_number(v, "exposure compensation")?,
+        };
+        if ev.is_finite() {
+            Ok(ExposureCompensation(ev))
+        } else {
+            Err(Error::Malformed(format!(
+                "Invalid exposure compensation {:?}",
+                s
+            )))
+        }
+    }
+}
+
+impl fmt::Display for ExposureCompensation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:+.1} EV", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canon_eos() {
+        assert_eq!(Aperture::from_canon_eos(0x20), Aperture(2.8));
+        assert_eq!(Aperture::from_canon_eos(0x23), Aperture(3.2));
+        assert_eq!(Aperture::from_canon_eos(0x25), Aperture(3.5));
+        assert_eq!(Aperture(2.8).to_canon_eos(), 0x20);
+        assert_eq!(Aperture(5.6).to_canon_eos(), 0x30);
+
+        assert_eq!(
+            ShutterSpeed::from_canon_eos(0x38),
+            ShutterSpeed::Seconds(1.0)
+        );
+        assert_eq!(
+            ShutterSpeed::from_canon_eos(0x78),
+            ShutterSpeed::Seconds(1.0 / 250.0)
+        );
+        assert_eq!(ShutterSpeed::from_canon_eos(0x0C), ShutterSpeed::Bulb);
+        assert_eq!(ShutterSpeed::Seconds(1.0).to_canon_eos(), 0x38);
+        assert_eq!(ShutterSpeed::Bulb.to_canon_eos(), 0x0C);
+
+        assert_eq!(Iso::from_canon_eos(0x48), Iso::Value(100));
+        assert_eq!(Iso::from_canon_eos(0x4B), Iso::Value(125));
+        assert_eq!(Iso::from_canon_eos(0x68), Iso::Value(1600));
+        assert_eq!(Iso::from_canon_eos(0), Iso::Auto);
+        assert_eq!(Iso::Value(100).to_canon_eos(), 0x48);
+
+        assert_eq!(
+            ExposureCompensation::from_canon_eos(0xFD),
+            ExposureCompensation(-1.0 / 3.0)
+        );
+        assert_eq!(ExposureCompensation(-1.0 / 3.0).to_canon_eos(), 0xFD);
+        assert_eq!(ExposureCompensation(1.5).to_canon_eos(), 0x0C);
+    }
+
+    #[test]
+    fn sony() {
+        assert_eq!(
+            ShutterSpeed::from_sony(0x0001_00FA),
+            ShutterSpeed::Seconds(1.0 / 250.0)
+        );
+        assert_eq!(ShutterSpeed::Seconds(1.0 / 250.0).to_sony(), 0x0001_00FA);
+        assert_eq!(
+            ShutterSpeed::from_sony(0x0014_000A),
+            ShutterSpeed::Seconds(2.0)
+        );
+        assert_eq!(ShutterSpeed::Seconds(2.0).to_sony(), 0x0014_000A);
+        assert_eq!(ShutterSpeed::from_sony(0), ShutterSpeed::Bulb);
+
+        assert_eq!(Iso::from_sony(0x0100_0190), Iso::Value(400));
+        assert_eq!(Iso::from_sony(0x00FF_FFFF), Iso::Auto);
+    }
+
+    #[test]
+    fn ptp() {
+        assert_eq!(Aperture::from_ptp(280), Aperture(2.8));
+        assert_eq!(Aperture(2.8).to_ptp(), 280);
+        assert_eq!(ShutterSpeed::from_ptp(40), ShutterSpeed::Seconds(0.004));
+        assert_eq!(ShutterSpeed::from_ptp(0xFFFF_FFFF), ShutterSpeed::Bulb);
+        assert_eq!(Iso::from_ptp(0xFFFF), Iso::Auto);
+        assert_eq!(ExposureCompensation::from_ptp(-700).to_ptp(), -700);
+    }
+
+    #[test]
+    fn apex_snapping() {
+        assert_eq!(Aperture::from_apex(5.0), Aperture(5.6));
+        assert_eq!(Aperture::from_apex(3.0 + 1.0 / 3.0), Aperture(3.2));
+        assert_eq!(
+            ShutterSpeed::from_apex(6.0),
+            ShutterSpeed::Seconds(1.0 / 60.0)
+        );
+        // values far from any marketed one are kept
+        assert_eq!(Aperture::from_apex(20.0), Aperture(1024.0));
+        assert_eq!(ShutterSpeed::Seconds(0.5).to_apex(), Some(1.0));
+        assert_eq!(ShutterSpeed::Bulb.to_apex(), None);
+    }
+
+    #[test]
+    fn parse() {
+        for s in &["f/2.8", "F2.8", " 2.8 "] {
+            assert_eq!(s.parse::<Aperture>().unwrap(), Aperture(2.8));
+        }
+        assert_eq!(
+            "1/250".parse::<ShutterSpeed>().unwrap(),
+            ShutterSpeed::Seconds(0.004)
+        );
+        assert_eq!(
+            "2\"".parse::<ShutterSpeed>().unwrap(),
+            ShutterSpeed::Seconds(2.0)
+        );
+        assert_eq!(
+            "2s".parse::<ShutterSpeed>().unwrap(),
+            ShutterSpeed::Seconds(2.0)
+        );
+        assert_eq!("Bulb".parse::<ShutterSpeed>().unwrap(), ShutterSpeed::Bulb);
+        assert_eq!("ISO 400".parse::<Iso>().unwrap(), Iso::Value(400));
+        assert_eq!("auto".parse::<Iso>().unwrap(), Iso::Auto);
+        assert_eq!(
+            "+0.7 EV".parse::<ExposureCompensation>().unwrap(),
+            ExposureCompensation(0.7)
+        );
+        assert_eq!(
+            "-1/3".parse::<ExposureCompensation>().unwrap(),
+            ExposureCompensation(-1.0 / 3.0)
+        );
+
+        assert!("f/0".parse::<Aperture>().is_err());
+        assert!("1/0".parse::<ShutterSpeed>().is_err());
+        assert!("fast".parse::<ShutterSpeed>().is_err());
+        assert!("ISO 0".parse::<Iso>().is_err());
+        assert!("1/0".parse::<ExposureCompensation>().is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Aperture(2.8).to_string(), "f/2.8");
+        assert_eq!(Aperture(11.0).to_string(), "f/11");
+        assert_eq!(ShutterSpeed::Seconds(1.0 / 250.0).to_string(), "1/250");
+        assert_eq!(ShutterSpeed::Seconds(2.0).to_string(), "2\"");
+        assert_eq!(ShutterSpeed::Seconds(0.5).to_string(), "0.5\"");
+        assert_eq!(ShutterSpeed::Bulb.to_string(), "bulb");
+        assert_eq!(Iso::Value(100).to_string(), "ISO 100");
+        assert_eq!(Iso::Auto.to_string(), "ISO auto");
+        assert_eq!(ExposureCompensation(-1.0 / 3.0).to_string(), "-0.3 EV");
+
+        for s in &["f/5.6", "1/8000", "30\"", "ISO 3200", "+1.0 EV"] {
+            let round_trip = match s.chars().next() {
+                Some('f') => s.parse::<Aperture>().unwrap().to_string(),
+                Some('I') => s.parse::<Iso>().unwrap().to_string(),
+                Some('+') => s.parse::<ExposureCompensation>().unwrap().to_string(),
+                _ => s.parse::<ShutterSpeed>().unwrap().to_string(),
+            };
+            assert_eq!(&round_trip, s);
+        }
+    }
+}
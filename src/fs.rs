@@ -0,0 +1,246 @@
+use super::{Camera, Error, ObjectInfo, StandardObjectFormatCode, Transport};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
+use std::time::Duration;
+
+// parent handle of the objects at the root of a store
+const ROOT: u32 = 0xFFFF_FFFF;
+
+/// An object found by `CameraFs`.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub handle: u32,
+    pub info: ObjectInfo,
+}
+
+impl Entry {
+    pub fn name(&self) -> &str {
+        &self.info.Filename
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.info.is_folder()
+    }
+}
+
+/// Path-based access to one store of a camera, e.g. `fs.read("/DCIM/100CANON/IMG_0001.JPG")`.
+///
+/// Paths are `/`-separated object names from the root of the store, matched exactly.
+/// Folder listings are cached as they are read, and kept up to date with the changes
+/// made through this `CameraFs`; call `invalidate` after the contents of the store
+/// changed otherwise, e.g. after a capture.
+pub struct CameraFs<'a, T: Transport> {
+    camera: &'a mut Camera<T>,
+    storage_id: u32,
+    timeout: Option<Duration>,
+    // children of each folder listed so far, by parent handle
+    dirs: HashMap<u32, Vec<Entry>>,
+}
+
+impl<'a, T: Transport> CameraFs<'a, T> {
+    pub fn new(
+        camera: &'a mut Camera<T>,
+        storage_id: u32,
+        timeout: Option<Duration>,
+    ) -> CameraFs<'a, T> {
+        CameraFs {
+            camera,
+            storage_id,
+            timeout,
+            dirs: HashMap::new(),
+        }
+    }
+
+    pub fn storage_id(&self) -> u32 {
+        self.storage_id
+    }
+
+    /// Forget all cached folder listings.
+    pub fn invalidate(&mut self) {
+        self.dirs.clear();
+    }
+
+    /// The objects in the folder at `path`, `/` for the root of the store.
+    pub fn list_dir(&mut self, path: &str) -> Result<Vec<Entry>, Error> {
+        let parent = self.lookup_dir(path)?;
+        Ok(self.children(parent)?.to_vec())
+    }
+
+    pub fn metadata(&mut self, path: &str) -> Result<Entry, Error> {
+        let (parent, name) = split(path).ok_or_else(|| not_found(path))?;
+        let parent = self.lookup_dir(parent)?;
+        self.child(parent, name)?.ok_or_else(|| not_found(path))
+    }
+
+    pub fn exists(&mut self, path: &str) -> Result<bool, Error> {
+        match self.metadata(path) {
+            Ok(_) => Ok(true),
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Download the file at `path`.
+    pub fn read(&mut self, path: &str) -> Result<Vec<u8>, Error> {
+        let entry = self.metadata(path)?;
+        if entry.is_dir() {
+            return Err(is_a_dir(path));
+        }
+        self.camera.get_object(entry.handle, self.timeout)
+    }
+
+    /// Upload `data` as the file at `path`, replacing an existing file of that name.
+    /// The parent folder has to exist.
+    ///
+    /// A file being replaced is deleted only once the new one has been uploaded, so a
+    /// failed upload leaves it in place. Both copies exist in between, which cameras
+    /// that refuse duplicate names answer with an error.
+    ///
+    /// The object is sent with the Undefined format; cameras generally only index the
+    /// files they recognize by their extension anyway.
+    pub fn write(&mut self, path: &str, data: &[u8]) -> Result<u32, Error> {
+        let (parent_path, name) = split(path).ok_or_else(|| is_a_dir(path))?;
+        let size = u32::try_from(data.len()).map_err(|_| {
+            Error::Io(io::Error::other(format!(
+                "{}: files of 4GB or more are not supported",
+                path
+            )))
+        })?;
+        let parent = self.lookup_dir(parent_path)?;
+        let existing = self.child(parent, name)?;
+        if existing.as_ref().is_some_and(Entry::is_dir) {
+            return Err(is_a_dir(path));
+        }
+
+        let info = ObjectInfo {
+            StorageID: self.storage_id,
+            ObjectFormat: StandardObjectFormatCode::Undefined,
+            ObjectCompressedSize: size,
+            // the dataset refers to the root as 0 rather than 0xFFFFFFFF
+            ParentObject: if parent == ROOT { 0 } else { parent },
+            Filename: name.to_owned(),
+            ..Default::default()
+        };
+        let handle = self.camera.send_object_info(parent, &info, self.timeout)?;
+        self.camera.send_object(data, self.timeout)?;
+        self.added(parent, handle, info);
+        if let Some(existing) = existing {
+            self.remove_entry(parent, existing.handle)?;
+        }
+        Ok(handle)
+    }
+
+    /// Create the folder at `path`, its parent has to exist.
+    pub fn mkdir(&mut self, path: &str) -> Result<u32, Error> {
+        let (parent_path, name) = split(path).ok_or_else(|| already_exists(path))?;
+        let parent = self.lookup_dir(parent_path)?;
+        if self.child(parent, name)?.is_some() {
+            return Err(already_exists(path));
+        }
+
+        let handle = self
+            .camera
+            .create_folder(self.storage_id, parent, name, self.timeout)?;
+        // re-read it for the fields the device filled in
+        let info = self.camera.get_objectinfo(handle, self.timeout)?;
+        self.added(parent, handle, info);
+        Ok(handle)
+    }
+
+    /// Delete the object at `path`. Deleting a folder deletes its contents as well, on
+    /// the devices that allow it at all.
+    pub fn remove(&mut self, path: &str) -> Result<(), Error> {
+        let (parent_path, name) = split(path).ok_or_else(|| not_found(path))?;
+        let parent = self.lookup_dir(parent_path)?;
+        let entry = self.child(parent, name)?.ok_or_else(|| not_found(path))?;
+        self.remove_entry(parent, entry.handle)
+    }
+
+    fn remove_entry(&mut self, parent: u32, handle: u32) -> Result<(), Error> {
+        self.camera.delete_object(handle, self.timeout)?;
+        if let Some(children) = self.dirs.get_mut(&parent) {
+            children.retain(|e| e.handle != handle);
+        }
+        self.dirs.remove(&handle);
+        Ok(())
+    }
+
+    fn added(&mut self, parent: u32, handle: u32, info: ObjectInfo) {
+        if let Some(children) = self.dirs.get_mut(&parent) {
+            children.push(Entry { handle, info });
+        }
+    }
+
+    // handle of the folder at `path`, ROOT for the root of the store
+    fn lookup_dir(&mut self, path: &str) -> Result<u32, Error> {
+        let mut handle = ROOT;
+        for name in components(path) {
+            let entry = self.child(handle, name)?.ok_or_else(|| not_found(path))?;
+            if !entry.is_dir() {
+                return Err(Error::Io(io::Error::other(format!(
+                    "{}: not a directory",
+                    path
+                ))));
+            }
+            handle = entry.handle;
+        }
+        Ok(handle)
+    }
+
+    fn child(&mut self, parent: u32, name: &str) -> Result<Option<Entry>, Error> {
+        Ok(self
+            .children(parent)?
+            .iter()
+            .find(|e| e.name() == name)
+            .cloned())
+    }
+
+    fn children(&mut self, parent: u32) -> Result<&[Entry], Error> {
+        if !self.dirs.contains_key(&parent) {
+            let handles =
+                self.camera
+                    .get_objecthandles(self.storage_id, parent, None, self.timeout)?;
+            let mut children = Vec::with_capacity(handles.len());
+            for handle in handles {
+                let info = self.camera.get_objectinfo(handle, self.timeout)?;
+                children.push(Entry { handle, info });
+            }
+            self.dirs.insert(parent, children);
+        }
+        Ok(&self.dirs[&parent])
+    }
+}
+
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|c| !c.is_empty())
+}
+
+// split a path into its parent folder and last component, `None` for the root
+fn split(path: &str) -> Option<(&str, &str)> {
+    let path = path.trim_end_matches('/');
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if name.is_empty() {
+        None
+    } else {
+        Some((parent, name))
+    }
+}
+
+fn not_found(path: &str) -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{}: no such file or directory", path),
+    ))
+}
+
+fn is_a_dir(path: &str) -> Error {
+    Error::Io(io::Error::other(format!("{}: is a directory", path)))
+}
+
+fn already_exists(path: &str) -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{}: already exists", path),
+    ))
+}
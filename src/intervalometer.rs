@@ -0,0 +1,206 @@
+use super::{Camera, CancelToken, Error, StandardEventCode, StandardResponseCode, Transport};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Outcome of one frame of an `Intervalometer` run.
+#[derive(Debug)]
+pub struct FrameReport {
+    /// Index of the frame, starting at 0.
+    pub index: u32,
+    /// Objects the capture produced, e.g. a JPEG and a raw file.
+    pub handles: Vec<u32>,
+    /// Where the objects were saved, when downloading.
+    pub files: Vec<PathBuf>,
+    /// Number of times the capture was retried because the camera was busy.
+    pub busy_retries: u32,
+    /// The frame wasn't captured because the previous one overran its slot.
+    pub skipped: bool,
+    /// Why the frame failed, if it did.
+    pub error: Option<Error>,
+}
+
+/// Captures frames at a fixed interval, e.g. for a timelapse.
+///
+/// Frames are scheduled from the start of the run, so a slow frame doesn't shift the
+/// ones after it; frames whose slot has passed entirely are skipped, and reported as
+/// such. A capture that
+/// fails with DeviceBusy is retried with a growing backoff.
+#[derive(Debug, Clone)]
+pub struct Intervalometer {
+    interval: Duration,
+    frames: Option<u32>,
+    download_dir: Option<PathBuf>,
+    capture_timeout: Duration,
+    max_busy_wait: Duration,
+    cancel: Option<CancelToken>,
+}
+
+impl Intervalometer {
+    pub fn new(interval: Duration) -> Intervalometer {
+        Intervalometer {
+            interval,
+            frames: None,
+            download_dir: None,
+            capture_timeout: Duration::from_secs(30),
+            max_busy_wait: Duration::from_secs(10),
+            cancel: None,
+        }
+    }
+
+    /// Stop after `frames` frames, the run is endless by default.
+    pub fn with_frames(mut self, frames: u32) -> Intervalometer {
+        self.frames = Some(frames);
+        self
+    }
+
+    /// Download each frame into `dir` and delete it from the camera, so a long run
+    /// doesn't fill the card.
+    pub fn with_download_dir<P: AsRef<Path>>(mut self, dir: P) -> Intervalometer {
+        self.download_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// How long to wait for CaptureComplete after each capture, 30s by default.
+    pub fn with_capture_timeout(mut self, timeout: Duration) -> Intervalometer {
+        self.capture_timeout = timeout;
+        self
+    }
+
+    /// How long to keep retrying a capture the camera reports busy for, 10s by default.
+    pub fn with_max_busy_wait(mut self, wait: Duration) -> Intervalometer {
+        self.max_busy_wait = wait;
+        self
+    }
+
+    /// Stop the run from another thread.
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Intervalometer {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Run the timelapse, calling `on_frame` after each frame.
+    ///
+    /// A frame failing with an error response is reported and the run goes on. Any other
+    /// error, e.g. the camera going away, is reported and ends the run.
+    pub fn run<T: Transport, F: FnMut(&FrameReport)>(
+        &self,
+        camera: &mut Camera<T>,
+        mut on_frame: F,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+        let mut index = 0;
+
+        while self.frames.map_or(true, |frames| index < frames) {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                return Err(Error::Cancelled);
+            }
+
+            let mut report = FrameReport {
+                index,
+                handles: vec![],
+                files: vec![],
+                busy_retries: 0,
+                skipped: false,
+                error: None,
+            };
+
+            let slot = start + self.interval * index;
+            let now = Instant::now();
+            if slot > now {
+                thread::sleep(slot - now);
+            } else if now - slot >= self.interval && !self.interval.is_zero() {
+                warn!("intervalometer: frame {} overran its slot, skipping", index);
+                report.skipped = true;
+                on_frame(&report);
+                index += 1;
+                continue;
+            }
+
+            report.error = self.frame(camera, &mut report).err();
+            on_frame(&report);
+            match report.error {
+                None | Some(Error::Response(_)) => {}
+                Some(e) => return Err(e),
+            }
+
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn frame<T: Transport>(
+        &self,
+        camera: &mut Camera<T>,
+        report: &mut FrameReport,
+    ) -> Result<(), Error> {
+        let tid = self.capture(camera, report)?;
+        report.handles = self.wait_capture_complete(camera, tid)?;
+
+        if let Some(dir) = &self.download_dir {
+            for &handle in &report.handles {
+                let info = camera.get_objectinfo(handle, None)?;
+                // the name comes from the device, only keep its last component
+                let path = match Path::new(&info.Filename).file_name() {
+                    Some(name) => dir.join(name),
+                    None => dir.join(format!("{:08x}", handle)),
+                };
+                camera.get_object_to_file(handle, &path, |_, _| {}, None)?;
+                camera.delete_object(handle, None)?;
+                report.files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    // InitiateCapture, backing off while the camera is busy
+    fn capture<T: Transport>(
+        &self,
+        camera: &mut Camera<T>,
+        report: &mut FrameReport,
+    ) -> Result<u32, Error> {
+        let deadline = Instant::now() + self.max_busy_wait;
+        let mut backoff = Duration::from_millis(100);
+        loop {
+            match camera.initiate_capture(0, 0, None) {
+                Err(Error::Response(StandardResponseCode::DeviceBusy))
+                    if Instant::now() + backoff < deadline =>
+                {
+                    report.busy_retries += 1;
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(Duration::from_secs(2));
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // collect the ObjectAdded events of a capture until its CaptureComplete
+    fn wait_capture_complete<T: Transport>(
+        &self,
+        camera: &mut Camera<T>,
+        tid: u32,
+    ) -> Result<Vec<u32>, Error> {
+        let deadline = Instant::now() + self.capture_timeout;
+        let mut handles = vec![];
+        while Instant::now() < deadline {
+            let mut events = camera.drain_pending_events();
+            events.extend(camera.poll_event(Some(Duration::from_millis(200)))?);
+            for event in events {
+                match event.code {
+                    StandardEventCode::ObjectAdded => handles.extend(event.object_handle()),
+                    StandardEventCode::CaptureComplete if event.tid == tid => return Ok(handles),
+                    _ => debug!("intervalometer: ignoring {:?}", event),
+                }
+            }
+        }
+        // some cameras never send CaptureComplete
+        if handles.is_empty() {
+            warn!(
+                "intervalometer: no object captured by transaction {} within {:?}",
+                tid, self.capture_timeout
+            );
+        }
+        Ok(handles)
+    }
+}
@@ -2,18 +2,96 @@
 #[macro_use]
 extern crate log;
 
-use byteorder::LittleEndian;
-use std::io::Cursor;
+use self::container::{ContainerInfo, ContainerType, CONTAINER_INFO_SIZE};
+use std::{cmp::min, io::Cursor};
 
+mod async_camera;
+mod battery;
+mod borrowed;
 mod camera;
+mod cancel;
+mod container;
 mod data_type;
+#[cfg(feature = "chrono")]
+mod datetime;
+mod directory;
+mod download;
+mod edit;
 mod error;
+mod exif;
+mod exposure;
+mod fs;
+mod intervalometer;
+mod liveview;
+mod location;
+mod object_cache;
+mod objects;
+mod ptpip;
+mod query;
+mod quirks;
 mod read;
+mod read_only;
+mod remote;
+mod responder;
+mod sync;
+#[cfg(feature = "image")]
+mod thumbnail;
+mod transport;
+mod vendor;
+mod watch;
+mod write;
+mod zoom;
 
-pub use self::camera::Camera;
+pub use self::async_camera::AsyncCamera;
+pub use self::battery::BatteryStatus;
+pub use self::borrowed::{DeviceInfoRef, ObjectInfoRef, PtpStr};
+pub use self::camera::{
+    Camera, CameraBuilder, CameraConfig, DeleteOutcome, PhaseTimeouts, PostCommandHook,
+    PreCommandHook, MAX_DATA_LEN, MIN_CHUNK_SIZE,
+};
+pub use self::cancel::CancelToken;
 pub use self::data_type::{DataType, FormData};
+#[cfg(feature = "chrono")]
+pub use self::datetime::{format_ptp_datetime, parse_ptp_datetime};
+pub use self::directory::{DirectoryDevice, DIRECTORY_STORAGE_ID};
+pub use self::download::{ResumableDownload, StreamDigest, Verified, VerifiedDownload};
+pub use self::edit::EditSession;
 pub use self::error::Error;
-pub use self::read::Read;
+pub use self::exposure::{Aperture, ExposureCompensation, Iso, ShutterSpeed};
+pub use self::fs::{CameraFs, Entry};
+pub use self::intervalometer::{FrameReport, Intervalometer};
+pub use self::liveview::{FocusArea, Frames, LiveView, LiveViewFrame};
+pub use self::location::GpsLocation;
+pub use self::object_cache::ObjectCache;
+pub use self::objects::Objects;
+pub use self::ptpip::{serve_directory, PtpIpLink, PTPIP_PORT};
+pub use self::query::{capture_date_in_range, ObjectQuery};
+pub use self::quirks::{lookup_quirks, register_quirks, DeviceQuirks, Quirks};
+pub use self::read::{DatasetReader, Read, MAX_ARRAY_LEN};
+pub use self::read_only::{is_destructive, is_read_operation, ReadOnlyCamera};
+pub use self::remote::{AfStatus, FocusDirection, FocusStep, RemoteControl};
+pub use self::responder::{
+    has_data_in, has_data_out, ContainerLink, Handler, Reply, Request, Responder, ResponderLink,
+};
+pub use self::sync::{StoreSync, SyncSummary};
+pub use self::transport::{
+    read_recording, AsyncTransport, DeviceStatus, MockCamera, MockDevice, MockObject,
+    MockTransport, RecordingTransport, ReplayTransport, StreamTransport, Transfer,
+    TransferDirection, Transport, UsbTransport,
+};
+pub use self::vendor::{
+    encode_eos_prop_value, parse_eos_events, parse_eos_viewfinder_data,
+    parse_nikon_live_view_image, parse_sony_live_view_object, CanonCommandCode, CanonEventCode,
+    CanonPropCode, EosDeviceInfo, EosEvent, EosLiveView, EosRemoteControl, FujiCommandCode,
+    FujiPropCode, LeicaCommandCode, NikonCommandCode, NikonEventCode, NikonLiveView, NikonPropCode,
+    NikonRemoteControl, OlympusCommandCode, OlympusPropCode, PanasonicCommandCode,
+    PanasonicPropCode, PanasonicProperty, RicohPropCode, SigmaCommandCode, SonyCommandCode,
+    SonyEventCode, SonyLiveView, SonyPropCode, SonyRemoteControl, FUJI_PRIORITY_MODE_PC,
+    SONY_LIVE_VIEW_HANDLE,
+};
+pub use self::watch::{CollisionPolicy, WatchOptions};
+pub use self::write::PtpWrite;
+pub use self::zoom::ZoomControl;
 
 pub type ResponseCode = u16;
 
@@ -95,6 +173,39 @@ pub mod StandardResponseCode {
     }
 }
 
+/// Response codes added by MTP.
+#[allow(non_upper_case_globals)]
+pub mod MtpResponseCode {
+    use super::ResponseCode;
+
+    pub const Undefined: ResponseCode = 0xA800;
+    pub const InvalidObjectPropCode: ResponseCode = 0xA801;
+    pub const InvalidObjectPropFormat: ResponseCode = 0xA802;
+    pub const InvalidObjectPropValue: ResponseCode = 0xA803;
+    pub const InvalidObjectReference: ResponseCode = 0xA804;
+    pub const InvalidDataset: ResponseCode = 0xA805;
+    pub const SpecificationByGroupUnsupported: ResponseCode = 0xA806;
+    pub const SpecificationByDepthUnsupported: ResponseCode = 0xA807;
+    pub const ObjectTooLarge: ResponseCode = 0xA808;
+    pub const ObjectPropNotSupported: ResponseCode = 0xA809;
+
+    pub fn name(v: ResponseCode) -> Option<&'static str> {
+        match v {
+            Undefined => Some("Undefined"),
+            InvalidObjectPropCode => Some("InvalidObjectPropCode"),
+            InvalidObjectPropFormat => Some("InvalidObjectPropFormat"),
+            InvalidObjectPropValue => Some("InvalidObjectPropValue"),
+            InvalidObjectReference => Some("InvalidObjectReference"),
+            InvalidDataset => Some("InvalidDataset"),
+            SpecificationByGroupUnsupported => Some("SpecificationByGroupUnsupported"),
+            SpecificationByDepthUnsupported => Some("SpecificationByDepthUnsupported"),
+            ObjectTooLarge => Some("ObjectTooLarge"),
+            ObjectPropNotSupported => Some("ObjectPropNotSupported"),
+            _ => None,
+        }
+    }
+}
+
 pub type CommandCode = u16;
 
 #[allow(non_upper_case_globals)]
@@ -167,8 +278,442 @@ pub mod StandardCommandCode {
     }
 }
 
+/// Operations from the MTP specification and its Android extensions.
+#[allow(non_upper_case_globals)]
+pub mod MtpCommandCode {
+    use super::CommandCode;
+
+    pub const GetObjectPropsSupported: CommandCode = 0x9801;
+    pub const GetObjectPropDesc: CommandCode = 0x9802;
+    pub const GetObjectPropValue: CommandCode = 0x9803;
+    pub const SetObjectPropValue: CommandCode = 0x9804;
+    pub const GetObjectPropList: CommandCode = 0x9805;
+    pub const SetObjectPropList: CommandCode = 0x9806;
+    pub const GetInterdependentPropDesc: CommandCode = 0x9807;
+    pub const SendObjectPropList: CommandCode = 0x9808;
+    pub const GetObjectReferences: CommandCode = 0x9810;
+    pub const SetObjectReferences: CommandCode = 0x9811;
+    pub const UpdateDeviceFirmware: CommandCode = 0x9812;
+    pub const Skip: CommandCode = 0x9820;
+    pub const GetPartialObject64: CommandCode = 0x95C1;
+    pub const SendPartialObject: CommandCode = 0x95C2;
+    pub const TruncateObject: CommandCode = 0x95C3;
+    pub const BeginEditObject: CommandCode = 0x95C4;
+    pub const EndEditObject: CommandCode = 0x95C5;
+
+    pub fn name(v: CommandCode) -> Option<&'static str> {
+        match v {
+            GetObjectPropsSupported => Some("GetObjectPropsSupported"),
+            GetObjectPropDesc => Some("GetObjectPropDesc"),
+            GetObjectPropValue => Some("GetObjectPropValue"),
+            SetObjectPropValue => Some("SetObjectPropValue"),
+            GetObjectPropList => Some("GetObjectPropList"),
+            SetObjectPropList => Some("SetObjectPropList"),
+            GetInterdependentPropDesc => Some("GetInterdependentPropDesc"),
+            SendObjectPropList => Some("SendObjectPropList"),
+            GetObjectReferences => Some("GetObjectReferences"),
+            SetObjectReferences => Some("SetObjectReferences"),
+            UpdateDeviceFirmware => Some("UpdateDeviceFirmware"),
+            Skip => Some("Skip"),
+            GetPartialObject64 => Some("GetPartialObject64"),
+            SendPartialObject => Some("SendPartialObject"),
+            TruncateObject => Some("TruncateObject"),
+            BeginEditObject => Some("BeginEditObject"),
+            EndEditObject => Some("EndEditObject"),
+            _ => None,
+        }
+    }
+}
+
+pub type EventCode = u16;
+
+#[allow(non_upper_case_globals)]
+pub mod StandardEventCode {
+    use super::EventCode;
+
+    pub const Undefined: EventCode = 0x4000;
+    pub const CancelTransaction: EventCode = 0x4001;
+    pub const ObjectAdded: EventCode = 0x4002;
+    pub const ObjectRemoved: EventCode = 0x4003;
+    pub const StoreAdded: EventCode = 0x4004;
+    pub const StoreRemoved: EventCode = 0x4005;
+    pub const DevicePropChanged: EventCode = 0x4006;
+    pub const ObjectInfoChanged: EventCode = 0x4007;
+    pub const DeviceInfoChanged: EventCode = 0x4008;
+    pub const RequestObjectTransfer: EventCode = 0x4009;
+    pub const StoreFull: EventCode = 0x400A;
+    pub const DeviceReset: EventCode = 0x400B;
+    pub const StorageInfoChanged: EventCode = 0x400C;
+    pub const CaptureComplete: EventCode = 0x400D;
+    pub const UnreportedStatus: EventCode = 0x400E;
+
+    pub fn name(v: EventCode) -> Option<&'static str> {
+        match v {
+            Undefined => Some("Undefined"),
+            CancelTransaction => Some("CancelTransaction"),
+            ObjectAdded => Some("ObjectAdded"),
+            ObjectRemoved => Some("ObjectRemoved"),
+            StoreAdded => Some("StoreAdded"),
+            StoreRemoved => Some("StoreRemoved"),
+            DevicePropChanged => Some("DevicePropChanged"),
+            ObjectInfoChanged => Some("ObjectInfoChanged"),
+            DeviceInfoChanged => Some("DeviceInfoChanged"),
+            RequestObjectTransfer => Some("RequestObjectTransfer"),
+            StoreFull => Some("StoreFull"),
+            DeviceReset => Some("DeviceReset"),
+            StorageInfoChanged => Some("StorageInfoChanged"),
+            CaptureComplete => Some("CaptureComplete"),
+            UnreportedStatus => Some("UnreportedStatus"),
+            _ => None,
+        }
+    }
+}
+
+/// Event codes added by MTP.
+#[allow(non_upper_case_globals)]
+pub mod MtpEventCode {
+    use super::EventCode;
+
+    pub const Undefined: EventCode = 0xC800;
+    pub const ObjectPropChanged: EventCode = 0xC801;
+    pub const ObjectPropDescChanged: EventCode = 0xC802;
+    pub const ObjectReferencesChanged: EventCode = 0xC803;
+
+    pub fn name(v: EventCode) -> Option<&'static str> {
+        match v {
+            Undefined => Some("Undefined"),
+            ObjectPropChanged => Some("ObjectPropChanged"),
+            ObjectPropDescChanged => Some("ObjectPropDescChanged"),
+            ObjectReferencesChanged => Some("ObjectReferencesChanged"),
+            _ => None,
+        }
+    }
+}
+
+/// A device property code, standard or vendor-defined.
+pub type PropertyCode = u16;
+
+/// Device property codes of PTP.
+#[allow(non_upper_case_globals)]
+pub mod StandardPropertyCode {
+    use super::PropertyCode;
+
+    pub const Undefined: PropertyCode = 0x5000;
+    pub const BatteryLevel: PropertyCode = 0x5001;
+    pub const FunctionalMode: PropertyCode = 0x5002;
+    pub const ImageSize: PropertyCode = 0x5003;
+    pub const CompressionSetting: PropertyCode = 0x5004;
+    pub const WhiteBalance: PropertyCode = 0x5005;
+    pub const RGBGain: PropertyCode = 0x5006;
+    pub const FNumber: PropertyCode = 0x5007;
+    pub const FocalLength: PropertyCode = 0x5008;
+    pub const FocusDistance: PropertyCode = 0x5009;
+    pub const FocusMode: PropertyCode = 0x500A;
+    pub const ExposureMeteringMode: PropertyCode = 0x500B;
+    pub const FlashMode: PropertyCode = 0x500C;
+    pub const ExposureTime: PropertyCode = 0x500D;
+    pub const ExposureProgramMode: PropertyCode = 0x500E;
+    pub const ExposureIndex: PropertyCode = 0x500F;
+    pub const ExposureBiasCompensation: PropertyCode = 0x5010;
+    pub const DateTime: PropertyCode = 0x5011;
+    pub const CaptureDelay: PropertyCode = 0x5012;
+    pub const StillCaptureMode: PropertyCode = 0x5013;
+    pub const Contrast: PropertyCode = 0x5014;
+    pub const Sharpness: PropertyCode = 0x5015;
+    pub const DigitalZoom: PropertyCode = 0x5016;
+    pub const EffectMode: PropertyCode = 0x5017;
+    pub const BurstNumber: PropertyCode = 0x5018;
+    pub const BurstInterval: PropertyCode = 0x5019;
+    pub const TimelapseNumber: PropertyCode = 0x501A;
+    pub const TimelapseInterval: PropertyCode = 0x501B;
+    pub const FocusMeteringMode: PropertyCode = 0x501C;
+    pub const UploadURL: PropertyCode = 0x501D;
+    pub const Artist: PropertyCode = 0x501E;
+    pub const CopyrightInfo: PropertyCode = 0x501F;
+
+    pub fn name(v: PropertyCode) -> Option<&'static str> {
+        match v {
+            Undefined => Some("Undefined"),
+            BatteryLevel => Some("BatteryLevel"),
+            FunctionalMode => Some("FunctionalMode"),
+            ImageSize => Some("ImageSize"),
+            CompressionSetting => Some("CompressionSetting"),
+            WhiteBalance => Some("WhiteBalance"),
+            RGBGain => Some("RGBGain"),
+            FNumber => Some("FNumber"),
+            FocalLength => Some("FocalLength"),
+            FocusDistance => Some("FocusDistance"),
+            FocusMode => Some("FocusMode"),
+            ExposureMeteringMode => Some("ExposureMeteringMode"),
+            FlashMode => Some("FlashMode"),
+            ExposureTime => Some("ExposureTime"),
+            ExposureProgramMode => Some("ExposureProgramMode"),
+            ExposureIndex => Some("ExposureIndex"),
+            ExposureBiasCompensation => Some("ExposureBiasCompensation"),
+            DateTime => Some("DateTime"),
+            CaptureDelay => Some("CaptureDelay"),
+            StillCaptureMode => Some("StillCaptureMode"),
+            Contrast => Some("Contrast"),
+            Sharpness => Some("Sharpness"),
+            DigitalZoom => Some("DigitalZoom"),
+            EffectMode => Some("EffectMode"),
+            BurstNumber => Some("BurstNumber"),
+            BurstInterval => Some("BurstInterval"),
+            TimelapseNumber => Some("TimelapseNumber"),
+            TimelapseInterval => Some("TimelapseInterval"),
+            FocusMeteringMode => Some("FocusMeteringMode"),
+            UploadURL => Some("UploadURL"),
+            Artist => Some("Artist"),
+            CopyrightInfo => Some("CopyrightInfo"),
+            _ => None,
+        }
+    }
+}
+
+/// Object property codes of MTP, as used by GetObjectPropList.
+#[allow(non_upper_case_globals)]
+pub mod MtpObjectPropCode {
+    use super::PropertyCode;
+
+    pub const StorageID: PropertyCode = 0xDC01;
+    pub const ObjectFormat: PropertyCode = 0xDC02;
+    pub const ProtectionStatus: PropertyCode = 0xDC03;
+    pub const ObjectSize: PropertyCode = 0xDC04;
+    pub const AssociationType: PropertyCode = 0xDC05;
+    pub const AssociationDesc: PropertyCode = 0xDC06;
+    pub const ObjectFileName: PropertyCode = 0xDC07;
+    pub const DateCreated: PropertyCode = 0xDC08;
+    pub const DateModified: PropertyCode = 0xDC09;
+    pub const Keywords: PropertyCode = 0xDC0A;
+    pub const ParentObject: PropertyCode = 0xDC0B;
+    pub const PersistentUniqueObjectIdentifier: PropertyCode = 0xDC41;
+    pub const Name: PropertyCode = 0xDC44;
+
+    pub fn name(v: PropertyCode) -> Option<&'static str> {
+        match v {
+            StorageID => Some("StorageID"),
+            ObjectFormat => Some("ObjectFormat"),
+            ProtectionStatus => Some("ProtectionStatus"),
+            ObjectSize => Some("ObjectSize"),
+            AssociationType => Some("AssociationType"),
+            AssociationDesc => Some("AssociationDesc"),
+            ObjectFileName => Some("ObjectFileName"),
+            DateCreated => Some("DateCreated"),
+            DateModified => Some("DateModified"),
+            Keywords => Some("Keywords"),
+            ParentObject => Some("ParentObject"),
+            PersistentUniqueObjectIdentifier => Some("PersistentUniqueObjectIdentifier"),
+            Name => Some("Name"),
+            _ => None,
+        }
+    }
+}
+
+pub type ObjectFormatCode = u16;
+
+/// Object format codes of PTP, followed by those added by MTP.
+#[allow(non_upper_case_globals)]
+pub mod StandardObjectFormatCode {
+    use super::ObjectFormatCode;
+
+    pub const Undefined: ObjectFormatCode = 0x3000;
+    pub const Association: ObjectFormatCode = 0x3001;
+    pub const Script: ObjectFormatCode = 0x3002;
+    pub const Executable: ObjectFormatCode = 0x3003;
+    pub const Text: ObjectFormatCode = 0x3004;
+    pub const Html: ObjectFormatCode = 0x3005;
+    pub const Dpof: ObjectFormatCode = 0x3006;
+    pub const Aiff: ObjectFormatCode = 0x3007;
+    pub const Wav: ObjectFormatCode = 0x3008;
+    pub const Mp3: ObjectFormatCode = 0x3009;
+    pub const Avi: ObjectFormatCode = 0x300A;
+    pub const Mpeg: ObjectFormatCode = 0x300B;
+    pub const Asf: ObjectFormatCode = 0x300C;
+    pub const QuickTime: ObjectFormatCode = 0x300D;
+    pub const UndefinedImage: ObjectFormatCode = 0x3800;
+    pub const ExifJpeg: ObjectFormatCode = 0x3801;
+    pub const TiffEp: ObjectFormatCode = 0x3802;
+    pub const FlashPix: ObjectFormatCode = 0x3803;
+    pub const Bmp: ObjectFormatCode = 0x3804;
+    pub const Ciff: ObjectFormatCode = 0x3805;
+    pub const Gif: ObjectFormatCode = 0x3807;
+    pub const Jfif: ObjectFormatCode = 0x3808;
+    pub const Pcd: ObjectFormatCode = 0x3809;
+    pub const Pict: ObjectFormatCode = 0x380A;
+    pub const Png: ObjectFormatCode = 0x380B;
+    pub const Tiff: ObjectFormatCode = 0x380D;
+    pub const TiffIt: ObjectFormatCode = 0x380E;
+    pub const Jp2: ObjectFormatCode = 0x380F;
+    pub const Jpx: ObjectFormatCode = 0x3810;
+    pub const Dng: ObjectFormatCode = 0x3811;
+    /// MP4 movies as reported by most cameras, MTP devices use `Mp4Container`.
+    pub const Mp4: ObjectFormatCode = 0xB301;
+    pub const UndefinedFirmware: ObjectFormatCode = 0xB802;
+    pub const WindowsImageFormat: ObjectFormatCode = 0xB881;
+    pub const UndefinedAudio: ObjectFormatCode = 0xB900;
+    pub const Wma: ObjectFormatCode = 0xB901;
+    pub const Ogg: ObjectFormatCode = 0xB902;
+    pub const Aac: ObjectFormatCode = 0xB903;
+    pub const Audible: ObjectFormatCode = 0xB904;
+    pub const Flac: ObjectFormatCode = 0xB906;
+    pub const UndefinedVideo: ObjectFormatCode = 0xB980;
+    pub const Wmv: ObjectFormatCode = 0xB981;
+    pub const Mp4Container: ObjectFormatCode = 0xB982;
+    pub const Mp2: ObjectFormatCode = 0xB983;
+    pub const ThreeGpContainer: ObjectFormatCode = 0xB984;
+
+    pub fn name(v: ObjectFormatCode) -> Option<&'static str> {
+        match v {
+            Undefined => Some("Undefined"),
+            Association => Some("Association"),
+            Script => Some("Script"),
+            Executable => Some("Executable"),
+            Text => Some("Text"),
+            Html => Some("Html"),
+            Dpof => Some("Dpof"),
+            Aiff => Some("Aiff"),
+            Wav => Some("Wav"),
+            Mp3 => Some("Mp3"),
+            Avi => Some("Avi"),
+            Mpeg => Some("Mpeg"),
+            Asf => Some("Asf"),
+            QuickTime => Some("QuickTime"),
+            UndefinedImage => Some("UndefinedImage"),
+            ExifJpeg => Some("ExifJpeg"),
+            TiffEp => Some("TiffEp"),
+            FlashPix => Some("FlashPix"),
+            Bmp => Some("Bmp"),
+            Ciff => Some("Ciff"),
+            Gif => Some("Gif"),
+            Jfif => Some("Jfif"),
+            Pcd => Some("Pcd"),
+            Pict => Some("Pict"),
+            Png => Some("Png"),
+            Tiff => Some("Tiff"),
+            TiffIt => Some("TiffIt"),
+            Jp2 => Some("Jp2"),
+            Jpx => Some("Jpx"),
+            Dng => Some("Dng"),
+            Mp4 => Some("Mp4"),
+            UndefinedFirmware => Some("UndefinedFirmware"),
+            WindowsImageFormat => Some("WindowsImageFormat"),
+            UndefinedAudio => Some("UndefinedAudio"),
+            Wma => Some("Wma"),
+            Ogg => Some("Ogg"),
+            Aac => Some("Aac"),
+            Audible => Some("Audible"),
+            Flac => Some("Flac"),
+            UndefinedVideo => Some("UndefinedVideo"),
+            Wmv => Some("Wmv"),
+            Mp4Container => Some("Mp4Container"),
+            Mp2 => Some("Mp2"),
+            ThreeGpContainer => Some("ThreeGpContainer"),
+            _ => None,
+        }
+    }
+
+    /// Still image formats, including undefined and vendor-neutral raw images.
+    pub fn is_image(v: ObjectFormatCode) -> bool {
+        (0x3800..=0x38FF).contains(&v) || v == WindowsImageFormat
+    }
+
+    pub fn is_video(v: ObjectFormatCode) -> bool {
+        matches!(v, Avi | Mpeg | Asf | QuickTime | Mp4) || (0xB980..=0xB9FF).contains(&v)
+    }
+
+    pub fn is_audio(v: ObjectFormatCode) -> bool {
+        matches!(v, Aiff | Wav | Mp3) || (0xB900..=0xB97F).contains(&v)
+    }
+
+    /// Associations, e.g. folders.
+    pub fn is_association(v: ObjectFormatCode) -> bool {
+        v == Association
+    }
+}
+
+/// FunctionalMode of a device, see `DeviceInfo::functional_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionalMode {
+    StandardMode,
+    /// The device is asleep, most operations fail until it is woken up.
+    SleepState,
+    /// A vendor-defined mode, 0x8000 and above.
+    Vendor(u16),
+    /// A code reserved by the spec.
+    Reserved(u16),
+}
+
+impl FunctionalMode {
+    pub fn from_u16(v: u16) -> FunctionalMode {
+        match v {
+            0x0000 => FunctionalMode::StandardMode,
+            0x0001 => FunctionalMode::SleepState,
+            0x8000..=0xFFFF => FunctionalMode::Vendor(v),
+            _ => FunctionalMode::Reserved(v),
+        }
+    }
+
+    pub fn to_u16(self) -> u16 {
+        match self {
+            FunctionalMode::StandardMode => 0x0000,
+            FunctionalMode::SleepState => 0x0001,
+            FunctionalMode::Vendor(v) | FunctionalMode::Reserved(v) => v,
+        }
+    }
+}
+
+/// Vendor extension announced by a device in `DeviceInfo.VendorExID`, see
+/// `DeviceInfo::vendor_extension`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VendorExtension {
+    EastmanKodak,
+    SeikoEpson,
+    Agilent,
+    Polaroid,
+    AgfaGevaert,
+    MicrosoftMtp,
+    Equinox,
+    ViewQuest,
+    StMicroelectronics,
+    Nikon,
+    Canon,
+    FotoNation,
+    Pentax,
+    Fuji,
+    Sony,
+    Panasonic,
+    /// No extension, or one not known to this crate.
+    Unknown(u32),
+}
+
+impl VendorExtension {
+    pub fn from_u32(v: u32) -> VendorExtension {
+        use self::VendorExtension::*;
+        match v {
+            0x00000001 => EastmanKodak,
+            0x00000002 => SeikoEpson,
+            0x00000003 => Agilent,
+            0x00000004 => Polaroid,
+            0x00000005 => AgfaGevaert,
+            0x00000006 => MicrosoftMtp,
+            0x00000007 => Equinox,
+            0x00000008 => ViewQuest,
+            0x00000009 => StMicroelectronics,
+            0x0000000a => Nikon,
+            0x0000000b => Canon,
+            0x0000000c => FotoNation,
+            0x0000000d => Pentax,
+            0x0000000e => Fuji,
+            0x00000011 => Sony,
+            0x0000001c => Panasonic,
+            _ => Unknown(v),
+        }
+    }
+}
+
 #[allow(non_snake_case)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceInfo {
     pub Version: u16,
     pub VendorExID: u32,
@@ -187,30 +732,150 @@ pub struct DeviceInfo {
 }
 
 impl DeviceInfo {
+    pub fn functional_mode(&self) -> FunctionalMode {
+        FunctionalMode::from_u16(self.FunctionalMode)
+    }
+
+    pub fn is_sleeping(&self) -> bool {
+        self.functional_mode() == FunctionalMode::SleepState
+    }
+
+    /// The vendor extension of the device. Devices that leave VendorExID unset but
+    /// describe the MTP extension in VendorExtensionDesc are reported as MicrosoftMtp.
+    pub fn vendor_extension(&self) -> VendorExtension {
+        match VendorExtension::from_u32(self.VendorExID) {
+            VendorExtension::Unknown(_) if self.VendorExtensionDesc.contains("microsoft.com") => {
+                VendorExtension::MicrosoftMtp
+            }
+            extension => extension,
+        }
+    }
+
     pub fn decode(buf: &[u8]) -> Result<DeviceInfo, Error> {
-        let mut cur = Cursor::new(buf);
+        DeviceInfo::decode_from(&mut Cursor::new(buf))
+    }
 
+    /// Like `decode`, reading from any `Read`, e.g. a `DatasetReader`.
+    pub fn decode_from<T: Read>(cur: &mut T) -> Result<DeviceInfo, Error> {
         Ok(DeviceInfo {
-            Version: cur.read_ptp_u16()?,
-            VendorExID: cur.read_ptp_u32()?,
-            VendorExVersion: cur.read_ptp_u16()?,
-            VendorExtensionDesc: cur.read_ptp_str()?,
-            FunctionalMode: cur.read_ptp_u16()?,
-            OperationsSupported: cur.read_ptp_u16_vec()?,
-            EventsSupported: cur.read_ptp_u16_vec()?,
-            DevicePropertiesSupported: cur.read_ptp_u16_vec()?,
-            CaptureFormats: cur.read_ptp_u16_vec()?,
-            ImageFormats: cur.read_ptp_u16_vec()?,
-            Manufacturer: cur.read_ptp_str()?,
-            Model: cur.read_ptp_str()?,
-            DeviceVersion: cur.read_ptp_str()?,
-            SerialNumber: cur.read_ptp_str()?,
+            Version: cur.read_field("DeviceInfo", "Version", T::read_ptp_u16)?,
+            VendorExID: cur.read_field("DeviceInfo", "VendorExID", T::read_ptp_u32)?,
+            VendorExVersion: cur.read_field("DeviceInfo", "VendorExVersion", T::read_ptp_u16)?,
+            VendorExtensionDesc: cur.read_field(
+                "DeviceInfo",
+                "VendorExtensionDesc",
+                T::read_ptp_str,
+            )?,
+            FunctionalMode: cur.read_field("DeviceInfo", "FunctionalMode", T::read_ptp_u16)?,
+            OperationsSupported: cur.read_field(
+                "DeviceInfo",
+                "OperationsSupported",
+                T::read_ptp_u16_vec,
+            )?,
+            EventsSupported: cur.read_field(
+                "DeviceInfo",
+                "EventsSupported",
+                T::read_ptp_u16_vec,
+            )?,
+            DevicePropertiesSupported: cur.read_field(
+                "DeviceInfo",
+                "DevicePropertiesSupported",
+                T::read_ptp_u16_vec,
+            )?,
+            CaptureFormats: cur.read_field("DeviceInfo", "CaptureFormats", T::read_ptp_u16_vec)?,
+            ImageFormats: cur.read_field("DeviceInfo", "ImageFormats", T::read_ptp_u16_vec)?,
+            Manufacturer: cur.read_field("DeviceInfo", "Manufacturer", T::read_ptp_str)?,
+            Model: cur.read_field("DeviceInfo", "Model", T::read_ptp_str)?,
+            DeviceVersion: cur.read_field("DeviceInfo", "DeviceVersion", T::read_ptp_str)?,
+            SerialNumber: cur.read_field("DeviceInfo", "SerialNumber", T::read_ptp_str)?,
         })
     }
+
+    /// Serialize the DeviceInfo dataset, e.g. for a responder answering GetDeviceInfo.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        out.write_ptp_u16(self.Version)?;
+        out.write_ptp_u32(self.VendorExID)?;
+        out.write_ptp_u16(self.VendorExVersion)?;
+        out.write_ptp_str(&self.VendorExtensionDesc)?;
+        out.write_ptp_u16(self.FunctionalMode)?;
+        out.write_ptp_u16_vec(&self.OperationsSupported)?;
+        out.write_ptp_u16_vec(&self.EventsSupported)?;
+        out.write_ptp_u16_vec(&self.DevicePropertiesSupported)?;
+        out.write_ptp_u16_vec(&self.CaptureFormats)?;
+        out.write_ptp_u16_vec(&self.ImageFormats)?;
+        out.write_ptp_str(&self.Manufacturer)?;
+        out.write_ptp_str(&self.Model)?;
+        out.write_ptp_str(&self.DeviceVersion)?;
+        out.write_ptp_str(&self.SerialNumber)?;
+        Ok(out)
+    }
+}
+
+/// Explicit acknowledgement required by operations that irreversibly destroy data,
+/// such as `Camera::format_store`.
+#[derive(Debug)]
+pub struct Danger(());
+
+impl Danger {
+    /// Confirm that the caller really intends to destroy data.
+    pub fn confirmed() -> Danger {
+        Danger(())
+    }
+}
+
+/// ProtectionStatus of an object, as set with SetObjectProtection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum ProtectionStatus {
+    None = 0x0000,
+    ReadOnly = 0x0001,
+}
+
+impl ProtectionStatus {
+    pub fn from_u16(v: u16) -> Option<ProtectionStatus> {
+        match v {
+            0x0000 => Some(ProtectionStatus::None),
+            0x0001 => Some(ProtectionStatus::ReadOnly),
+            _ => None,
+        }
+    }
+}
+
+/// AssociationType of an association object, describing how its children relate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum AssociationType {
+    Undefined = 0x0000,
+    GenericFolder = 0x0001,
+    Album = 0x0002,
+    TimeSequence = 0x0003,
+    HorizontalPanoramic = 0x0004,
+    VerticalPanoramic = 0x0005,
+    TwoDimensionalPanoramic = 0x0006,
+    AncillaryData = 0x0007,
+}
+
+impl AssociationType {
+    pub fn from_u16(v: u16) -> Option<AssociationType> {
+        use self::AssociationType::*;
+        match v {
+            0x0000 => Some(Undefined),
+            0x0001 => Some(GenericFolder),
+            0x0002 => Some(Album),
+            0x0003 => Some(TimeSequence),
+            0x0004 => Some(HorizontalPanoramic),
+            0x0005 => Some(VerticalPanoramic),
+            0x0006 => Some(TwoDimensionalPanoramic),
+            0x0007 => Some(AncillaryData),
+            _ => None,
+        }
+    }
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjectInfo {
     pub StorageID: u32,
     pub ObjectFormat: u16,
@@ -234,35 +899,194 @@ pub struct ObjectInfo {
 }
 
 impl ObjectInfo {
+    /// The AssociationType of an association, `None` for other objects or vendor types.
+    pub fn association_type(&self) -> Option<AssociationType> {
+        if !StandardObjectFormatCode::is_association(self.ObjectFormat) {
+            return None;
+        }
+        AssociationType::from_u16(self.AssociationType)
+    }
+
+    /// Whether the object is a folder, i.e. a GenericFolder association. Associations
+    /// leaving the type Undefined are treated as folders too, as many devices do so.
+    pub fn is_folder(&self) -> bool {
+        matches!(
+            self.association_type(),
+            Some(AssociationType::GenericFolder) | Some(AssociationType::Undefined)
+        )
+    }
+
+    /// CaptureDate parsed with `parse_ptp_datetime`, `None` if the device left it empty.
+    #[cfg(feature = "chrono")]
+    pub fn capture_datetime(&self) -> Result<Option<chrono::DateTime<chrono::FixedOffset>>, Error> {
+        parse_ptp_datetime(&self.CaptureDate)
+    }
+
+    /// ModificationDate parsed with `parse_ptp_datetime`, `None` if the device left it empty.
+    #[cfg(feature = "chrono")]
+    pub fn modification_datetime(
+        &self,
+    ) -> Result<Option<chrono::DateTime<chrono::FixedOffset>>, Error> {
+        parse_ptp_datetime(&self.ModificationDate)
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn set_capture_datetime(&mut self, datetime: &chrono::DateTime<chrono::FixedOffset>) {
+        self.CaptureDate = format_ptp_datetime(datetime);
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn set_modification_datetime(&mut self, datetime: &chrono::DateTime<chrono::FixedOffset>) {
+        self.ModificationDate = format_ptp_datetime(datetime);
+    }
+
     pub fn decode(buf: &[u8]) -> Result<ObjectInfo, Error> {
-        let mut cur = Cursor::new(buf);
+        ObjectInfo::decode_from(&mut Cursor::new(buf))
+    }
 
+    /// Like `decode`, reading from any `Read`, e.g. a `DatasetReader`.
+    pub fn decode_from<T: Read>(cur: &mut T) -> Result<ObjectInfo, Error> {
         Ok(ObjectInfo {
-            StorageID: cur.read_ptp_u32()?,
-            ObjectFormat: cur.read_ptp_u16()?,
-            ProtectionStatus: cur.read_ptp_u16()?,
-            ObjectCompressedSize: cur.read_ptp_u32()?,
-            ThumbFormat: cur.read_ptp_u16()?,
-            ThumbCompressedSize: cur.read_ptp_u32()?,
-            ThumbPixWidth: cur.read_ptp_u32()?,
-            ThumbPixHeight: cur.read_ptp_u32()?,
-            ImagePixWidth: cur.read_ptp_u32()?,
-            ImagePixHeight: cur.read_ptp_u32()?,
-            ImageBitDepth: cur.read_ptp_u32()?,
-            ParentObject: cur.read_ptp_u32()?,
-            AssociationType: cur.read_ptp_u16()?,
-            AssociationDesc: cur.read_ptp_u32()?,
-            SequenceNumber: cur.read_ptp_u32()?,
-            Filename: cur.read_ptp_str()?,
-            CaptureDate: cur.read_ptp_str()?,
-            ModificationDate: cur.read_ptp_str()?,
-            Keywords: cur.read_ptp_str()?,
+            StorageID: cur.read_field("ObjectInfo", "StorageID", T::read_ptp_u32)?,
+            ObjectFormat: cur.read_field("ObjectInfo", "ObjectFormat", T::read_ptp_u16)?,
+            ProtectionStatus: cur.read_field("ObjectInfo", "ProtectionStatus", T::read_ptp_u16)?,
+            ObjectCompressedSize: cur.read_field(
+                "ObjectInfo",
+                "ObjectCompressedSize",
+                T::read_ptp_u32,
+            )?,
+            ThumbFormat: cur.read_field("ObjectInfo", "ThumbFormat", T::read_ptp_u16)?,
+            ThumbCompressedSize: cur.read_field(
+                "ObjectInfo",
+                "ThumbCompressedSize",
+                T::read_ptp_u32,
+            )?,
+            ThumbPixWidth: cur.read_field("ObjectInfo", "ThumbPixWidth", T::read_ptp_u32)?,
+            ThumbPixHeight: cur.read_field("ObjectInfo", "ThumbPixHeight", T::read_ptp_u32)?,
+            ImagePixWidth: cur.read_field("ObjectInfo", "ImagePixWidth", T::read_ptp_u32)?,
+            ImagePixHeight: cur.read_field("ObjectInfo", "ImagePixHeight", T::read_ptp_u32)?,
+            ImageBitDepth: cur.read_field("ObjectInfo", "ImageBitDepth", T::read_ptp_u32)?,
+            ParentObject: cur.read_field("ObjectInfo", "ParentObject", T::read_ptp_u32)?,
+            AssociationType: cur.read_field("ObjectInfo", "AssociationType", T::read_ptp_u16)?,
+            AssociationDesc: cur.read_field("ObjectInfo", "AssociationDesc", T::read_ptp_u32)?,
+            SequenceNumber: cur.read_field("ObjectInfo", "SequenceNumber", T::read_ptp_u32)?,
+            Filename: cur.read_field("ObjectInfo", "Filename", T::read_ptp_str)?,
+            CaptureDate: cur.read_field("ObjectInfo", "CaptureDate", T::read_ptp_str)?,
+            ModificationDate: cur.read_field("ObjectInfo", "ModificationDate", T::read_ptp_str)?,
+            Keywords: cur.read_field("ObjectInfo", "Keywords", T::read_ptp_str)?,
         })
     }
+
+    /// Serialize the ObjectInfo dataset, e.g. as the data phase of SendObjectInfo.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        out.write_ptp_u32(self.StorageID)?;
+        out.write_ptp_u16(self.ObjectFormat)?;
+        out.write_ptp_u16(self.ProtectionStatus)?;
+        out.write_ptp_u32(self.ObjectCompressedSize)?;
+        out.write_ptp_u16(self.ThumbFormat)?;
+        out.write_ptp_u32(self.ThumbCompressedSize)?;
+        out.write_ptp_u32(self.ThumbPixWidth)?;
+        out.write_ptp_u32(self.ThumbPixHeight)?;
+        out.write_ptp_u32(self.ImagePixWidth)?;
+        out.write_ptp_u32(self.ImagePixHeight)?;
+        out.write_ptp_u32(self.ImageBitDepth)?;
+        out.write_ptp_u32(self.ParentObject)?;
+        out.write_ptp_u16(self.AssociationType)?;
+        out.write_ptp_u32(self.AssociationDesc)?;
+        out.write_ptp_u32(self.SequenceNumber)?;
+        out.write_ptp_str(&self.Filename)?;
+        out.write_ptp_str(&self.CaptureDate)?;
+        out.write_ptp_str(&self.ModificationDate)?;
+        out.write_ptp_str(&self.Keywords)?;
+        Ok(out)
+    }
+}
+
+/// StorageType of a store, see `StorageInfo::storage_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum StorageType {
+    Undefined = 0x0000,
+    FixedRom = 0x0001,
+    RemovableRom = 0x0002,
+    FixedRam = 0x0003,
+    RemovableRam = 0x0004,
+}
+
+impl StorageType {
+    pub fn from_u16(v: u16) -> Option<StorageType> {
+        use self::StorageType::*;
+        match v {
+            0x0000 => Some(Undefined),
+            0x0001 => Some(FixedRom),
+            0x0002 => Some(RemovableRom),
+            0x0003 => Some(FixedRam),
+            0x0004 => Some(RemovableRam),
+            _ => None,
+        }
+    }
+
+    pub fn is_removable(self) -> bool {
+        self == StorageType::RemovableRom || self == StorageType::RemovableRam
+    }
+}
+
+/// FilesystemType of a store, see `StorageInfo::filesystem_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum FilesystemType {
+    Undefined = 0x0000,
+    GenericFlat = 0x0001,
+    GenericHierarchical = 0x0002,
+    Dcf = 0x0003,
+}
+
+impl FilesystemType {
+    pub fn from_u16(v: u16) -> Option<FilesystemType> {
+        use self::FilesystemType::*;
+        match v {
+            0x0000 => Some(Undefined),
+            0x0001 => Some(GenericFlat),
+            0x0002 => Some(GenericHierarchical),
+            0x0003 => Some(Dcf),
+            _ => None,
+        }
+    }
+}
+
+/// AccessCapability of a store, see `StorageInfo::access_capability`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum AccessCapability {
+    ReadWrite = 0x0000,
+    ReadOnlyWithoutDeletion = 0x0001,
+    ReadOnlyWithDeletion = 0x0002,
+}
+
+impl AccessCapability {
+    pub fn from_u16(v: u16) -> Option<AccessCapability> {
+        use self::AccessCapability::*;
+        match v {
+            0x0000 => Some(ReadWrite),
+            0x0001 => Some(ReadOnlyWithoutDeletion),
+            0x0002 => Some(ReadOnlyWithDeletion),
+            _ => None,
+        }
+    }
+
+    pub fn can_write(self) -> bool {
+        self == AccessCapability::ReadWrite
+    }
+
+    pub fn can_delete(self) -> bool {
+        self != AccessCapability::ReadOnlyWithoutDeletion
+    }
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StorageInfo {
     pub StorageType: u16,
     pub FilesystemType: u16,
@@ -275,21 +1099,142 @@ pub struct StorageInfo {
 }
 
 impl StorageInfo {
+    /// The StorageType, `None` for vendor-defined types.
+    pub fn storage_type(&self) -> Option<StorageType> {
+        StorageType::from_u16(self.StorageType)
+    }
+
+    /// The FilesystemType, `None` for vendor-defined types.
+    pub fn filesystem_type(&self) -> Option<FilesystemType> {
+        FilesystemType::from_u16(self.FilesystemType)
+    }
+
+    pub fn access_capability(&self) -> Option<AccessCapability> {
+        AccessCapability::from_u16(self.AccessCapability)
+    }
+
     pub fn decode<T: Read>(cur: &mut T) -> Result<StorageInfo, Error> {
         Ok(StorageInfo {
-            StorageType: cur.read_ptp_u16()?,
-            FilesystemType: cur.read_ptp_u16()?,
-            AccessCapability: cur.read_ptp_u16()?,
-            MaxCapacity: cur.read_ptp_u64()?,
-            FreeSpaceInBytes: cur.read_ptp_u64()?,
-            FreeSpaceInImages: cur.read_ptp_u32()?,
-            StorageDescription: cur.read_ptp_str()?,
-            VolumeLabel: cur.read_ptp_str()?,
+            StorageType: cur.read_field("StorageInfo", "StorageType", T::read_ptp_u16)?,
+            FilesystemType: cur.read_field("StorageInfo", "FilesystemType", T::read_ptp_u16)?,
+            AccessCapability: cur.read_field("StorageInfo", "AccessCapability", T::read_ptp_u16)?,
+            MaxCapacity: cur.read_field("StorageInfo", "MaxCapacity", T::read_ptp_u64)?,
+            FreeSpaceInBytes: cur.read_field("StorageInfo", "FreeSpaceInBytes", T::read_ptp_u64)?,
+            FreeSpaceInImages: cur.read_field(
+                "StorageInfo",
+                "FreeSpaceInImages",
+                T::read_ptp_u32,
+            )?,
+            StorageDescription: cur.read_field(
+                "StorageInfo",
+                "StorageDescription",
+                T::read_ptp_str,
+            )?,
+            VolumeLabel: cur.read_field("StorageInfo", "VolumeLabel", T::read_ptp_str)?,
         })
     }
+
+    /// Serialize the StorageInfo dataset, e.g. for a responder answering GetStorageInfo.
+    ///
+    /// Re-encoding a decoded dataset yields the original bytes, except for empty strings
+    /// sent as a lone terminator, which are always written as a zero count.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        out.write_ptp_u16(self.StorageType)?;
+        out.write_ptp_u16(self.FilesystemType)?;
+        out.write_ptp_u16(self.AccessCapability)?;
+        out.write_ptp_u64(self.MaxCapacity)?;
+        out.write_ptp_u64(self.FreeSpaceInBytes)?;
+        out.write_ptp_u32(self.FreeSpaceInImages)?;
+        out.write_ptp_str(&self.StorageDescription)?;
+        out.write_ptp_str(&self.VolumeLabel)?;
+        Ok(out)
+    }
 }
 
-#[derive(Debug)]
+/// An event reported by the responder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PtpEvent {
+    /// The event code, either a constant in StandardEventCode or a vendor-defined code.
+    pub code: EventCode,
+    /// Up to three event parameters, their meaning depends on `code`.
+    pub params: Vec<u32>,
+    /// Transaction ID of the operation the event relates to, if any.
+    pub tid: u32,
+}
+
+impl PtpEvent {
+    pub fn decode(buf: &[u8]) -> Result<PtpEvent, Error> {
+        let cinfo = ContainerInfo::parse(buf)?;
+        if cinfo.kind != ContainerType::Event {
+            return Err(Error::Malformed(format!(
+                "Expected an event container, got {:?}",
+                cinfo.kind
+            )));
+        }
+
+        let end = min(cinfo.payload_len, buf.len() - CONTAINER_INFO_SIZE);
+        PtpEvent::from_container(&cinfo, &buf[CONTAINER_INFO_SIZE..CONTAINER_INFO_SIZE + end])
+    }
+
+    // build the event out of an already parsed container header and its payload
+    pub(crate) fn from_container(cinfo: &ContainerInfo, payload: &[u8]) -> Result<PtpEvent, Error> {
+        let mut cur = Cursor::new(payload);
+        let mut params = Vec::with_capacity(3);
+        while params.len() < 3 && cur.position() as usize + 4 <= payload.len() {
+            params.push(cur.read_ptp_u32()?);
+        }
+
+        Ok(PtpEvent {
+            code: cinfo.code,
+            params,
+            tid: cinfo.tid,
+        })
+    }
+
+    /// The object handle carried by object related events such as ObjectAdded.
+    pub fn object_handle(&self) -> Option<u32> {
+        match self.code {
+            StandardEventCode::ObjectAdded
+            | StandardEventCode::ObjectRemoved
+            | StandardEventCode::ObjectInfoChanged
+            | StandardEventCode::RequestObjectTransfer => self.params.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// The storage id carried by storage related events such as StoreAdded.
+    pub fn storage_id(&self) -> Option<u32> {
+        match self.code {
+            StandardEventCode::StoreAdded
+            | StandardEventCode::StoreRemoved
+            | StandardEventCode::StoreFull
+            | StandardEventCode::StorageInfoChanged => self.params.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// The device property code carried by DevicePropChanged.
+    pub fn property_code(&self) -> Option<u16> {
+        match self.code {
+            StandardEventCode::DevicePropChanged => self.params.first().map(|&p| p as u16),
+            _ => None,
+        }
+    }
+
+    /// The transaction id carried by CaptureComplete and CancelTransaction.
+    pub fn transaction_id(&self) -> Option<u32> {
+        match self.code {
+            StandardEventCode::CaptureComplete | StandardEventCode::CancelTransaction => {
+                Some(self.tid)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PropInfo {
     /// A specific property_code.
     pub property_code: u16,
@@ -304,37 +1249,59 @@ pub struct PropInfo {
 
 impl PropInfo {
     pub fn decode<T: Read>(cur: &mut T) -> Result<PropInfo, Error> {
-        let property_code = cur.read_ptp_u16()?;
-        let data_type = cur.read_ptp_u16()?;
+        let property_code = cur.read_field("PropInfo", "property_code", T::read_ptp_u16)?;
+        let data_type = cur.read_field("PropInfo", "data_type", T::read_ptp_u16)?;
         Ok(PropInfo {
             property_code,
             data_type,
-            get_set: cur.read_u8()?,
-            factory_default: DataType::read_type(data_type, cur)?,
-            current: DataType::read_type(data_type, cur)?,
-            form: {
-                match cur.read_u8()? {
-                    // 0x00 => FormData::None,
-                    0x01 => FormData::Range {
-                        min_value: DataType::read_type(data_type, cur)?,
-                        max_value: DataType::read_type(data_type, cur)?,
-                        step: DataType::read_type(data_type, cur)?,
-                    },
-                    0x02 => FormData::Enumeration {
-                        array: {
-                            let len = cur.read_u16::<LittleEndian>()? as usize;
-                            let mut arr = Vec::with_capacity(len);
-                            for _ in 0..len {
-                                arr.push(DataType::read_type(data_type, cur)?);
-                            }
-                            arr
-                        },
-                    },
-                    _ => FormData::None,
-                }
-            },
+            get_set: cur.read_field("PropInfo", "get_set", T::read_ptp_u8)?,
+            factory_default: cur.read_field("PropInfo", "factory_default", |cur| {
+                DataType::read_type(data_type, cur)
+            })?,
+            current: cur.read_field("PropInfo", "current", |cur| {
+                DataType::read_type(data_type, cur)
+            })?,
+            form: cur.read_field("PropInfo", "form", |cur| read_form_data(data_type, cur))?,
         })
     }
+
+    /// Serialize the DevicePropDesc dataset, e.g. for a responder answering
+    /// GetDevicePropDesc. The values are expected to be of type `data_type`.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        out.write_ptp_u16(self.property_code)?;
+        out.write_ptp_u16(self.data_type)?;
+        out.write_ptp_u8(self.get_set)?;
+        out.extend(self.factory_default.encode()?);
+        out.extend(self.current.encode()?);
+        match &self.form {
+            FormData::None => out.write_ptp_u8(0x00)?,
+            FormData::Range {
+                min_value,
+                max_value,
+                step,
+            } => {
+                out.write_ptp_u8(0x01)?;
+                out.extend(min_value.encode()?);
+                out.extend(max_value.encode()?);
+                out.extend(step.encode()?);
+            }
+            FormData::Enumeration { array } => {
+                if array.len() > u16::MAX as usize {
+                    return Err(Error::Malformed(format!(
+                        "Enumeration of {} values doesn't fit a DevicePropDesc",
+                        array.len()
+                    )));
+                }
+                out.write_ptp_u8(0x02)?;
+                out.write_ptp_u16(array.len() as u16)?;
+                for value in array {
+                    out.extend(value.encode()?);
+                }
+            }
+        }
+        Ok(out)
+    }
 }
 
 #[derive(Debug)]
@@ -354,41 +1321,49 @@ pub struct PropInfoSony {
 
 impl PropInfoSony {
     pub fn decode<T: Read>(cur: &mut T) -> Result<PropInfoSony, Error> {
-        let property_code = cur.read_ptp_u16()?;
-        let data_type = cur.read_ptp_u16()?;
+        let property_code = cur.read_field("PropInfoSony", "property_code", T::read_ptp_u16)?;
+        let data_type = cur.read_field("PropInfoSony", "data_type", T::read_ptp_u16)?;
         Ok(PropInfoSony {
             property_code,
             data_type,
-            get_set: cur.read_u8()?,
-            is_enable: cur.read_u8()?,
-            factory_default: DataType::read_type(data_type, cur)?,
-            current: DataType::read_type(data_type, cur)?,
-            form: {
-                match cur.read_u8()? {
-                    // 0x00 => FormData::None,
-                    0x01 => FormData::Range {
-                        min_value: DataType::read_type(data_type, cur)?,
-                        max_value: DataType::read_type(data_type, cur)?,
-                        step: DataType::read_type(data_type, cur)?,
-                    },
-                    0x02 => FormData::Enumeration {
-                        array: {
-                            let len = cur.read_u16::<LittleEndian>()? as usize;
-                            let mut arr = Vec::with_capacity(len);
-                            for _ in 0..len {
-                                arr.push(DataType::read_type(data_type, cur)?);
-                            }
-                            arr
-                        },
-                    },
-                    _ => FormData::None,
-                }
-            },
+            get_set: cur.read_field("PropInfoSony", "get_set", T::read_ptp_u8)?,
+            is_enable: cur.read_field("PropInfoSony", "is_enable", T::read_ptp_u8)?,
+            factory_default: cur.read_field("PropInfoSony", "factory_default", |cur| {
+                DataType::read_type(data_type, cur)
+            })?,
+            current: cur.read_field("PropInfoSony", "current", |cur| {
+                DataType::read_type(data_type, cur)
+            })?,
+            form: cur.read_field("PropInfoSony", "form", |cur| read_form_data(data_type, cur))?,
         })
     }
 }
 
+// the form of a DevicePropDesc, its values being of type `data_type`
+fn read_form_data<T: Read>(data_type: u16, cur: &mut T) -> Result<FormData, Error> {
+    Ok(match cur.read_ptp_u8()? {
+        // 0x00 => FormData::None,
+        0x01 => FormData::Range {
+            min_value: DataType::read_type(data_type, cur)?,
+            max_value: DataType::read_type(data_type, cur)?,
+            step: DataType::read_type(data_type, cur)?,
+        },
+        0x02 => {
+            let len = cur.read_ptp_u16()? as usize;
+            // every value takes at least a byte
+            cur.check_array_len(len, 1)?;
+            let mut array = Vec::with_capacity(len);
+            for _ in 0..len {
+                array.push(DataType::read_type(data_type, cur)?);
+            }
+            FormData::Enumeration { array }
+        }
+        _ => FormData::None,
+    })
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjectTree {
     pub handle: u32,
     pub info: ObjectInfo,
@@ -417,3 +1392,165 @@ impl ObjectTree {
         output
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a PTP string: its length including the terminator, then UCS-2 code units
+    fn ptp_str(s: &str) -> Vec<u8> {
+        if s.is_empty() {
+            return vec![0];
+        }
+        let units: Vec<u16> = s.encode_utf16().chain(Some(0)).collect();
+        let mut out = vec![units.len() as u8];
+        for unit in units {
+            out.extend_from_slice(&unit.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn storage_info_round_trip() {
+        let mut data = vec![];
+        data.extend_from_slice(&0x0004u16.to_le_bytes()); // RemovableRam
+        data.extend_from_slice(&0x0002u16.to_le_bytes()); // GenericHierarchical
+        data.extend_from_slice(&0x0000u16.to_le_bytes()); // ReadWrite
+        data.extend_from_slice(&64_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&12_345_678_901u64.to_le_bytes());
+        data.extend_from_slice(&1234u32.to_le_bytes());
+        data.extend(ptp_str("SD"));
+        data.extend(ptp_str(""));
+
+        let info = StorageInfo::decode(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(info.MaxCapacity, 64_000_000_000);
+        assert_eq!(info.StorageDescription, "SD");
+        assert_eq!(info.VolumeLabel, "");
+        assert_eq!(info.encode().unwrap(), data);
+        assert_eq!(
+            StorageInfo::decode(&mut Cursor::new(info.encode().unwrap())).unwrap(),
+            info
+        );
+    }
+
+    #[test]
+    fn object_info_round_trip() {
+        let mut data = vec![];
+        data.extend_from_slice(&0x0001_0001u32.to_le_bytes());
+        data.extend_from_slice(&StandardObjectFormatCode::ExifJpeg.to_le_bytes());
+        data.extend_from_slice(&0x0000u16.to_le_bytes());
+        data.extend_from_slice(&5_242_880u32.to_le_bytes());
+        data.extend_from_slice(&StandardObjectFormatCode::Jfif.to_le_bytes());
+        for v in &[8192u32, 160, 120, 6000, 4000, 24, 0x0000_0010] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(&0x0000u16.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend(ptp_str("IMG_0001.JPG"));
+        data.extend(ptp_str("20240102T030405"));
+        data.extend(ptp_str("20240102T030405.5+0100"));
+        data.extend(ptp_str(""));
+
+        let info = ObjectInfo::decode(&data).unwrap();
+        assert_eq!(info.ImagePixWidth, 6000);
+        assert_eq!(info.ParentObject, 0x10);
+        assert_eq!(info.Filename, "IMG_0001.JPG");
+        assert_eq!(info.ModificationDate, "20240102T030405.5+0100");
+        assert_eq!(info.encode().unwrap(), data);
+        assert_eq!(ObjectInfo::decode(&info.encode().unwrap()).unwrap(), info);
+    }
+
+    #[test]
+    fn device_info_round_trip() {
+        // a u16 array: its length, then the elements
+        fn codes(codes: &[u16]) -> Vec<u8> {
+            let mut out = (codes.len() as u32).to_le_bytes().to_vec();
+            for code in codes {
+                out.extend_from_slice(&code.to_le_bytes());
+            }
+            out
+        }
+
+        let mut data = vec![];
+        data.extend_from_slice(&100u16.to_le_bytes());
+        data.extend_from_slice(&0x0000_0006u32.to_le_bytes());
+        data.extend_from_slice(&100u16.to_le_bytes());
+        data.extend(ptp_str("microsoft.com: 1.0"));
+        data.extend_from_slice(&0x0000u16.to_le_bytes());
+        data.extend(codes(&[0x1001, 0x1002, 0x1003, 0x1009, 0x101B, 0x9805]));
+        data.extend(codes(&[0x4002, 0x4003]));
+        data.extend(codes(&[0x5001, 0xD402]));
+        data.extend(codes(&[]));
+        data.extend(codes(&[0x3801, 0x3000]));
+        data.extend(ptp_str("libptp"));
+        data.extend(ptp_str("Test Camera"));
+        data.extend(ptp_str("1.0"));
+        data.extend(ptp_str(""));
+
+        let info = DeviceInfo::decode(&data).unwrap();
+        assert_eq!(info.vendor_extension(), VendorExtension::MicrosoftMtp);
+        assert_eq!(info.OperationsSupported.len(), 6);
+        assert!(info.CaptureFormats.is_empty());
+        assert_eq!(info.Model, "Test Camera");
+        assert_eq!(info.encode().unwrap(), data);
+        assert_eq!(DeviceInfo::decode(&info.encode().unwrap()).unwrap(), info);
+    }
+
+    #[test]
+    fn prop_info_round_trip() {
+        // ExposureIndex, a u16 with a Range form
+        let mut range = vec![];
+        range.extend_from_slice(&0x500Fu16.to_le_bytes());
+        range.extend_from_slice(&0x0004u16.to_le_bytes());
+        range.push(0x01);
+        range.extend_from_slice(&100u16.to_le_bytes());
+        range.extend_from_slice(&400u16.to_le_bytes());
+        range.push(0x01);
+        for v in &[100u16, 12800, 100] {
+            range.extend_from_slice(&v.to_le_bytes());
+        }
+        let prop = PropInfo::decode(&mut Cursor::new(&range)).unwrap();
+        assert_eq!(
+            prop.form,
+            FormData::Range {
+                min_value: DataType::UINT16(100),
+                max_value: DataType::UINT16(12800),
+                step: DataType::UINT16(100),
+            }
+        );
+        assert_eq!(prop.encode().unwrap(), range);
+        assert_eq!(
+            PropInfo::decode(&mut Cursor::new(prop.encode().unwrap())).unwrap(),
+            prop
+        );
+
+        // a string with an Enumeration form
+        let mut enumeration = vec![];
+        enumeration.extend_from_slice(&0xD402u16.to_le_bytes());
+        enumeration.extend_from_slice(&0xFFFFu16.to_le_bytes());
+        enumeration.push(0x01);
+        enumeration.extend(ptp_str(""));
+        enumeration.extend(ptp_str("Camera"));
+        enumeration.push(0x02);
+        enumeration.extend_from_slice(&2u16.to_le_bytes());
+        enumeration.extend(ptp_str("Camera"));
+        enumeration.extend(ptp_str("Studio"));
+        let prop = PropInfo::decode(&mut Cursor::new(&enumeration)).unwrap();
+        assert_eq!(prop.current, DataType::STR("Camera".to_owned()));
+        assert_eq!(prop.encode().unwrap(), enumeration);
+        assert_eq!(
+            PropInfo::decode(&mut Cursor::new(prop.encode().unwrap())).unwrap(),
+            prop
+        );
+
+        // no form
+        let mut none = vec![];
+        none.extend_from_slice(&0x5001u16.to_le_bytes());
+        none.extend_from_slice(&0x0002u16.to_le_bytes());
+        none.extend_from_slice(&[0x00, 100, 87, 0x00]);
+        let prop = PropInfo::decode(&mut Cursor::new(&none)).unwrap();
+        assert_eq!(prop.form, FormData::None);
+        assert_eq!(prop.encode().unwrap(), none);
+    }
+}
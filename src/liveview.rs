@@ -0,0 +1,65 @@
+use super::Error;
+use std::time::Duration;
+
+/// A rectangle in the coordinates of the live view frame, e.g. an AF area or the
+/// zoomed-in region.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FocusArea {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Whether the camera reports focus as achieved in this area, if it says.
+    pub in_focus: Option<bool>,
+}
+
+/// One frame of an electronic viewfinder feed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LiveViewFrame {
+    /// The frame itself, a complete JPEG image.
+    pub jpeg: Vec<u8>,
+    /// AF and zoom areas the camera attached to the frame, empty if it reports none.
+    pub focus_info: Vec<FocusArea>,
+    /// Luminance histogram, for the cameras that send one along.
+    pub histogram: Option<Vec<u32>>,
+    /// Clockwise rotation of the camera body in degrees, if reported.
+    pub rotation: Option<u16>,
+}
+
+/// A vendor-specific way of streaming the viewfinder over PTP.
+///
+/// Backends borrow the `Camera` for the duration of the feed, which needs an open session.
+/// `stop` is not called on drop, so a backend left running keeps the camera in live view.
+pub trait LiveView {
+    fn start(&mut self, timeout: Option<Duration>) -> Result<(), Error>;
+
+    fn stop(&mut self, timeout: Option<Duration>) -> Result<(), Error>;
+
+    /// Fetch the next frame, waiting for the camera to produce one.
+    fn next_frame(&mut self, timeout: Option<Duration>) -> Result<LiveViewFrame, Error>;
+
+    /// Endless iterator over the frames of a started feed.
+    fn frames(&mut self, timeout: Option<Duration>) -> Frames<'_, Self>
+    where
+        Self: Sized,
+    {
+        Frames {
+            live_view: self,
+            timeout,
+        }
+    }
+}
+
+/// Iterator returned by `LiveView::frames`.
+pub struct Frames<'a, L: LiveView> {
+    live_view: &'a mut L,
+    timeout: Option<Duration>,
+}
+
+impl<L: LiveView> Iterator for Frames<'_, L> {
+    type Item = Result<LiveViewFrame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.live_view.next_frame(self.timeout))
+    }
+}
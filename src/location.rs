@@ -0,0 +1,72 @@
+use super::{Camera, DataType, Error, PropertyCode, Transport};
+use std::time::Duration;
+
+/// A position to geotag captures with, in WGS 84.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpsLocation {
+    /// Degrees north, negative for south.
+    pub latitude: f64,
+    /// Degrees east, negative for west.
+    pub longitude: f64,
+    /// Meters above sea level.
+    pub altitude: Option<f64>,
+    /// UTC time of the fix as a PTP DateTime string, see `format_ptp_datetime`.
+    pub time: Option<String>,
+}
+
+impl GpsLocation {
+    pub fn new(latitude: f64, longitude: f64) -> GpsLocation {
+        GpsLocation {
+            latitude,
+            longitude,
+            altitude: None,
+            time: None,
+        }
+    }
+
+    /// The position as an ISO 6709 string, e.g. "+48.858370+002.294481+35.0CRSWGS_84/".
+    pub fn to_iso6709(&self) -> String {
+        let mut s = format!("{:+010.6}{:+011.6}", self.latitude, self.longitude);
+        if let Some(altitude) = self.altitude {
+            s += &format!("{:+.1}", altitude);
+        }
+        s + "CRSWGS_84/"
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(Error::Malformed(format!(
+                "Invalid location {}, {}",
+                self.latitude, self.longitude
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Transport> Camera<T> {
+    /// Hand the host's position to the camera for geotagging, writing it as an ISO 6709
+    /// string to the string property `property_code` and, if set, the time of the fix to
+    /// `time_property_code`.
+    ///
+    /// There is no standard GPS property; the codes to use depend on the vendor and the
+    /// model, and are listed in the camera's DeviceInfo.
+    pub fn set_location(
+        &mut self,
+        property_code: PropertyCode,
+        time_property_code: Option<PropertyCode>,
+        location: &GpsLocation,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        location.check()?;
+        self.set_device_prop_value(
+            property_code,
+            &DataType::STR(location.to_iso6709()),
+            timeout,
+        )?;
+        if let (Some(code), Some(time)) = (time_property_code, &location.time) {
+            self.set_device_prop_value(code, &DataType::STR(time.clone()), timeout)?;
+        }
+        Ok(())
+    }
+}
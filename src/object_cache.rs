@@ -0,0 +1,91 @@
+use super::{Camera, Error, ObjectInfo, PtpEvent, StandardEventCode, Transport};
+use std::collections::hash_map::{Entry, HashMap};
+use std::time::Duration;
+
+/// Memoizes ObjectInfos and handle listings, so refreshing a view of the camera only
+/// fetches what changed.
+///
+/// The cache doesn't read events itself: pass it each event received from the camera with
+/// `handle_event`, and it drops the entries the event makes stale. Listings are dropped
+/// on ObjectAdded and ObjectInfoChanged as the parent of the new or possibly moved object
+/// isn't known without asking the device.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectCache {
+    infos: HashMap<u32, ObjectInfo>,
+    // GetObjectHandles results by storage id and parent handle
+    listings: HashMap<(u32, u32), Vec<u32>>,
+}
+
+impl ObjectCache {
+    pub fn new() -> ObjectCache {
+        ObjectCache::default()
+    }
+
+    /// The ObjectInfo of `handle`, fetched from the device if not cached.
+    pub fn object_info<T: Transport>(
+        &mut self,
+        camera: &mut Camera<T>,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<&ObjectInfo, Error> {
+        match self.infos.entry(handle) {
+            Entry::Occupied(e) => Ok(e.into_mut()),
+            Entry::Vacant(e) => Ok(e.insert(camera.get_objectinfo(handle, timeout)?)),
+        }
+    }
+
+    /// The handles under `parent` on `storage_id`, as with `Camera::get_objecthandles`,
+    /// fetched from the device if not cached.
+    pub fn object_handles<T: Transport>(
+        &mut self,
+        camera: &mut Camera<T>,
+        storage_id: u32,
+        parent: u32,
+        timeout: Option<Duration>,
+    ) -> Result<&[u32], Error> {
+        match self.listings.entry((storage_id, parent)) {
+            Entry::Occupied(e) => Ok(e.into_mut()),
+            Entry::Vacant(e) => {
+                Ok(e.insert(camera.get_objecthandles(storage_id, parent, None, timeout)?))
+            }
+        }
+    }
+
+    /// Drop the entries made stale by `event`, other events are ignored.
+    pub fn handle_event(&mut self, event: &PtpEvent) {
+        let param = match event.params.first() {
+            Some(&param) => param,
+            None => return,
+        };
+        match event.code {
+            StandardEventCode::ObjectAdded => self.listings.clear(),
+            StandardEventCode::ObjectRemoved => {
+                self.infos.remove(&param);
+                self.listings.retain(|&(_, parent), _| parent != param);
+                for handles in self.listings.values_mut() {
+                    handles.retain(|&h| h != param);
+                }
+            }
+            StandardEventCode::ObjectInfoChanged => {
+                self.infos.remove(&param);
+                self.listings.clear();
+            }
+            StandardEventCode::StoreRemoved => {
+                self.infos.retain(|_, info| info.StorageID != param);
+                self.listings
+                    .retain(|&(storage_id, _), _| storage_id != param);
+            }
+            _ => {}
+        }
+    }
+
+    /// Forget the cached entry of one object, e.g. after changing it.
+    pub fn invalidate(&mut self, handle: u32) {
+        self.infos.remove(&handle);
+    }
+
+    pub fn clear(&mut self) {
+        self.infos.clear();
+        self.listings.clear();
+    }
+}
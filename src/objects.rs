@@ -0,0 +1,70 @@
+use super::{Camera, Error, ObjectInfo, ObjectQuery, Transport};
+use std::time::Duration;
+use std::vec;
+
+/// Iterator over the objects of a store, see `Camera::objects` and `Camera::query_objects`.
+///
+/// The handles are fetched with a single GetObjectHandles on the first call to `next`,
+/// at 4 bytes per object; the ObjectInfo of each object is only read when it is
+/// yielded, so stopping early saves the round trips for the rest. Objects not matching
+/// the query are skipped.
+pub struct Objects<'a, T: Transport> {
+    camera: &'a mut Camera<T>,
+    storage_id: u32,
+    query: ObjectQuery,
+    timeout: Option<Duration>,
+    handles: Option<vec::IntoIter<u32>>,
+}
+
+impl<'a, T: Transport> Objects<'a, T> {
+    pub(crate) fn new(
+        camera: &'a mut Camera<T>,
+        storage_id: u32,
+        query: ObjectQuery,
+        timeout: Option<Duration>,
+    ) -> Objects<'a, T> {
+        Objects {
+            camera,
+            storage_id,
+            query,
+            timeout,
+            handles: None,
+        }
+    }
+
+    /// Number of objects not yielded yet, once the handles were fetched.
+    pub fn remaining(&self) -> Option<usize> {
+        self.handles.as_ref().map(|h| h.len())
+    }
+}
+
+impl<T: Transport> Iterator for Objects<'_, T> {
+    type Item = Result<(u32, ObjectInfo), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.handles.is_none() {
+            let handles = self.camera.get_objecthandles(
+                self.storage_id,
+                self.query.parent().unwrap_or(0),
+                self.query.format().map(u32::from),
+                self.timeout,
+            );
+            match handles {
+                Ok(handles) => self.handles = Some(handles.into_iter()),
+                Err(e) => {
+                    // end the iteration after reporting the error
+                    self.handles = Some(vec![].into_iter());
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        loop {
+            let handle = self.handles.as_mut()?.next()?;
+            match self.camera.get_objectinfo(handle, self.timeout) {
+                Ok(info) if !self.query.matches(&info) => continue,
+                result => return Some(result.map(|info| (handle, info))),
+            }
+        }
+    }
+}
@@ -0,0 +1,527 @@
+use super::{
+    DirectoryDevice, Error, PtpEvent, Request, Responder, ResponderLink, ResponseCode, MAX_DATA_LEN,
+};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default TCP port of PTP/IP.
+pub const PTPIP_PORT: u16 = 15740;
+
+// PTP/IP packet types
+const INIT_COMMAND_REQUEST: u32 = 1;
+const INIT_COMMAND_ACK: u32 = 2;
+const INIT_EVENT_REQUEST: u32 = 3;
+const INIT_EVENT_ACK: u32 = 4;
+const INIT_FAIL: u32 = 5;
+const OPERATION_REQUEST: u32 = 6;
+const OPERATION_RESPONSE: u32 = 7;
+const EVENT: u32 = 8;
+const START_DATA: u32 = 9;
+const DATA: u32 = 10;
+const CANCEL: u32 = 11;
+const END_DATA: u32 = 12;
+const PROBE_REQUEST: u32 = 13;
+const PROBE_RESPONSE: u32 = 14;
+
+const PROTOCOL_VERSION: u32 = 0x0001_0000;
+// DataPhaseInfo of an OperationRequest followed by data from the initiator
+const DATA_OUT: u32 = 2;
+// InitFail reason for connections out of sequence
+const FAIL_REJECTED_INITIATOR: u32 = 1;
+// largest packet accepted outside of a data phase, whose packets are bounded by the
+// total length announced in its StartData instead
+const MAX_PACKET_LEN: usize = 1024 * 1024;
+// StartData total length of a data phase whose length is not known up front
+const UNKNOWN_DATA_LEN: u64 = u64::MAX;
+// this responder serves a single initiator, its connection number is always 1
+const CONNECTION_NUMBER: u32 = 1;
+// how long an initiator may take to send each packet of the handshake, and to open its
+// event connection
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// the next packet on `stream`, refusing payloads longer than `max_len`
+fn read_packet(stream: &mut TcpStream, max_len: usize) -> Result<Option<(u32, Vec<u8>)>, Error> {
+    let mut header = [0; 8];
+    match stream.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = (&header[..4]).read_u32::<LittleEndian>()? as usize;
+    let kind = (&header[4..]).read_u32::<LittleEndian>()?;
+    if len < header.len() {
+        return Err(Error::Malformed(format!(
+            "Invalid PTP/IP packet length {}",
+            len
+        )));
+    }
+    let payload_len = len - header.len();
+    if payload_len > max_len {
+        return Err(Error::Malformed(format!(
+            "PTP/IP packet type {} of {} bytes is too long",
+            kind, len
+        )));
+    }
+    // read as it arrives rather than allocated up front
+    let mut payload = Vec::with_capacity(payload_len.min(MAX_PACKET_LEN));
+    stream.take(payload_len as u64).read_to_end(&mut payload)?;
+    if payload.len() < payload_len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(Some((kind, payload)))
+}
+
+fn write_packet(stream: &mut TcpStream, kind: u32, payload: &[u8]) -> Result<(), Error> {
+    let mut buf = Vec::with_capacity(8 + payload.len());
+    buf.write_u32::<LittleEndian>(8 + payload.len() as u32)?;
+    buf.write_u32::<LittleEndian>(kind)?;
+    buf.extend_from_slice(payload);
+    stream.write_all(&buf)?;
+    Ok(())
+}
+
+fn write_init_fail(stream: &mut TcpStream) -> Result<(), Error> {
+    let mut reason = vec![];
+    reason.write_u32::<LittleEndian>(FAIL_REJECTED_INITIATOR)?;
+    write_packet(stream, INIT_FAIL, &reason)
+}
+
+// the event connection of the initiator given connection number CONNECTION_NUMBER,
+// rejecting other connections meanwhile
+fn accept_events(listener: &TcpListener) -> Result<TcpStream, Error> {
+    listener.set_nonblocking(true)?;
+    let result = wait_for_events(listener, Instant::now() + HANDSHAKE_TIMEOUT);
+    listener.set_nonblocking(false)?;
+    result
+}
+
+fn wait_for_events(listener: &TcpListener, deadline: Instant) -> Result<TcpStream, Error> {
+    loop {
+        let (mut stream, peer) = match listener.accept() {
+            Ok(connection) => connection,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock && Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "PTP/IP initiator didn't open its event connection",
+                )
+                .into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // accepted sockets inherit the non-blocking mode on some platforms
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        match read_packet(&mut stream, MAX_PACKET_LEN) {
+            Ok(Some((INIT_EVENT_REQUEST, payload)))
+                if payload.len() >= 4 && LittleEndian::read_u32(&payload) == CONNECTION_NUMBER =>
+            {
+                write_packet(&mut stream, INIT_EVENT_ACK, &[])?;
+                stream.set_read_timeout(None)?;
+                return Ok(stream);
+            }
+            Ok(Some((INIT_EVENT_REQUEST, _))) => {
+                warn!(
+                    "Rejecting PTP/IP event connection of another initiator from {}",
+                    peer
+                );
+                write_init_fail(&mut stream).ok();
+            }
+            Ok(Some((kind, _))) => {
+                warn!("Rejecting PTP/IP packet type {} from {}", kind, peer);
+                write_init_fail(&mut stream).ok();
+            }
+            Ok(None) => {}
+            Err(e) => warn!("PTP/IP handshake with {} failed: {}", peer, e),
+        }
+    }
+}
+
+fn write_name(buf: &mut Vec<u8>, name: &str) {
+    for unit in name.encode_utf16().chain(Some(0)) {
+        buf.extend_from_slice(&unit.to_le_bytes());
+    }
+}
+
+fn read_name(buf: &[u8]) -> String {
+    let units: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// The responder side of a PTP/IP connection: the command and the event TCP connections
+/// of one initiator.
+pub struct PtpIpLink {
+    command: TcpStream,
+    events: TcpStream,
+    initiator_name: String,
+}
+
+impl PtpIpLink {
+    /// Accept the next initiator on `listener`, going through the PTP/IP handshake that
+    /// introduces the responder as `name` with `guid`.
+    ///
+    /// Connections that don't start the handshake within a few seconds are dropped, and
+    /// the initiator has as long to open its event connection once its command
+    /// connection was acknowledged.
+    pub fn accept(listener: &TcpListener, guid: [u8; 16], name: &str) -> Result<PtpIpLink, Error> {
+        let (mut command, initiator_name) = loop {
+            let (mut stream, peer) = listener.accept()?;
+            stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+            match read_packet(&mut stream, MAX_PACKET_LEN) {
+                Ok(Some((INIT_COMMAND_REQUEST, payload))) if payload.len() >= 16 => {
+                    break (stream, read_name(&payload[16..]));
+                }
+                Ok(Some((kind, _))) => {
+                    warn!("Rejecting PTP/IP packet type {} from {}", kind, peer);
+                    write_init_fail(&mut stream).ok();
+                }
+                Ok(None) => {}
+                Err(e) => warn!("PTP/IP handshake with {} failed: {}", peer, e),
+            }
+        };
+
+        let mut ack = vec![];
+        ack.write_u32::<LittleEndian>(CONNECTION_NUMBER)?;
+        ack.extend_from_slice(&guid);
+        write_name(&mut ack, name);
+        ack.write_u32::<LittleEndian>(PROTOCOL_VERSION)?;
+        write_packet(&mut command, INIT_COMMAND_ACK, &ack)?;
+
+        let events = accept_events(listener)?;
+        command.set_read_timeout(None)?;
+        debug!("PTP/IP initiator {:?} connected", initiator_name);
+
+        Ok(PtpIpLink {
+            command,
+            events,
+            initiator_name,
+        })
+    }
+
+    /// The friendly name the initiator introduced itself with.
+    pub fn initiator_name(&self) -> &str {
+        &self.initiator_name
+    }
+
+    // the next packet on the command connection, answering probes on the way
+    fn read_command_packet(&mut self, max_len: usize) -> Result<Option<(u32, Vec<u8>)>, Error> {
+        loop {
+            match read_packet(&mut self.command, max_len)? {
+                Some((PROBE_REQUEST, _)) => write_packet(&mut self.command, PROBE_RESPONSE, &[])?,
+                Some((CANCEL, _)) => debug!("Ignoring PTP/IP Cancel"),
+                packet => return Ok(packet),
+            }
+        }
+    }
+}
+
+impl ResponderLink for PtpIpLink {
+    fn read_request(&mut self) -> Result<Option<Request>, Error> {
+        let payload = match self.read_command_packet(MAX_PACKET_LEN)? {
+            Some((OPERATION_REQUEST, payload)) => payload,
+            Some((kind, _)) => {
+                return Err(Error::Malformed(format!(
+                    "Expected a PTP/IP OperationRequest, got packet type {}",
+                    kind
+                )))
+            }
+            None => return Ok(None),
+        };
+        let mut cur = &payload[..];
+        let data_phase = cur.read_u32::<LittleEndian>()?;
+        let code = cur.read_u16::<LittleEndian>()?;
+        let tid = cur.read_u32::<LittleEndian>()?;
+        let params = cur
+            .chunks_exact(4)
+            .map(|p| u32::from_le_bytes([p[0], p[1], p[2], p[3]]))
+            .collect();
+        Ok(Some(Request {
+            code,
+            tid,
+            params,
+            has_data: Some(data_phase == DATA_OUT),
+        }))
+    }
+
+    fn read_data(&mut self, request: &Request) -> Result<Vec<u8>, Error> {
+        let mut data = vec![];
+        // bytes the data phase may still carry, known once StartData has been read
+        let mut total = None;
+        loop {
+            let left = total.map_or(0, |total: usize| total - data.len());
+            let (kind, payload) = self
+                .read_command_packet(MAX_PACKET_LEN.max(4 + left))?
+                .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            if payload.len() < 4 {
+                return Err(Error::Malformed(format!(
+                    "PTP/IP packet type {} is too short",
+                    kind
+                )));
+            }
+            let tid = (&payload[..4]).read_u32::<LittleEndian>()?;
+            if tid != request.tid {
+                return Err(Error::Malformed(format!(
+                    "Expected the data of transaction {}, got packet type {} of transaction {}",
+                    request.tid, kind, tid
+                )));
+            }
+            match (kind, total) {
+                (START_DATA, None) => {
+                    let len = (&payload[4..]).read_u64::<LittleEndian>()?;
+                    if len != UNKNOWN_DATA_LEN && len > MAX_DATA_LEN as u64 {
+                        return Err(Error::Malformed(format!(
+                            "PTP/IP data phase of {} bytes exceeds the limit of {} bytes",
+                            len, MAX_DATA_LEN
+                        )));
+                    }
+                    total = Some((len as usize).min(MAX_DATA_LEN));
+                }
+                (DATA, Some(_)) | (END_DATA, Some(_)) => {
+                    if payload.len() - 4 > left {
+                        return Err(Error::Malformed(format!(
+                            "PTP/IP data phase longer than the {} bytes announced",
+                            data.len() + left
+                        )));
+                    }
+                    data.extend_from_slice(&payload[4..]);
+                    if kind == END_DATA {
+                        return Ok(data);
+                    }
+                }
+                (kind, _) => {
+                    return Err(Error::Malformed(format!(
+                        "Unexpected PTP/IP packet type {} in a data phase",
+                        kind
+                    )))
+                }
+            }
+        }
+    }
+
+    fn write_data(&mut self, request: &Request, data: &[u8]) -> Result<(), Error> {
+        let mut start = vec![];
+        start.write_u32::<LittleEndian>(request.tid)?;
+        start.write_u64::<LittleEndian>(data.len() as u64)?;
+        write_packet(&mut self.command, START_DATA, &start)?;
+
+        let mut end = Vec::with_capacity(4 + data.len());
+        end.write_u32::<LittleEndian>(request.tid)?;
+        end.extend_from_slice(data);
+        write_packet(&mut self.command, END_DATA, &end)
+    }
+
+    fn write_response(
+        &mut self,
+        request: &Request,
+        code: ResponseCode,
+        params: &[u32],
+    ) -> Result<(), Error> {
+        let mut payload = vec![];
+        payload.write_u16::<LittleEndian>(code)?;
+        payload.write_u32::<LittleEndian>(request.tid)?;
+        for &p in params {
+            payload.write_u32::<LittleEndian>(p)?;
+        }
+        write_packet(&mut self.command, OPERATION_RESPONSE, &payload)
+    }
+
+    fn write_event(&mut self, event: &PtpEvent) -> Result<(), Error> {
+        let mut payload = vec![];
+        payload.write_u16::<LittleEndian>(event.code)?;
+        payload.write_u32::<LittleEndian>(event.tid)?;
+        for &p in &event.params {
+            payload.write_u32::<LittleEndian>(p)?;
+        }
+        write_packet(&mut self.events, EVENT, &payload)
+    }
+}
+
+/// Serve the directory `root` as a read-only PTP/IP camera on `addr`, e.g.
+/// `("0.0.0.0", PTPIP_PORT)`, to one initiator after the other. Failed connections are
+/// logged and dropped, so this only returns if the address can't be bound or the directory
+/// can't be read.
+///
+/// The directory is scanned again for each initiator, see `DirectoryDevice`.
+pub fn serve_directory<A: ToSocketAddrs, P: AsRef<Path>>(addr: A, root: P) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr)?;
+    let name = format!("libptp {}", root.as_ref().display());
+    // a stable GUID per served directory, so initiators recognize the camera
+    let mut guid = [0u8; 16];
+    for (i, b) in name.bytes().enumerate() {
+        guid[i % 16] = guid[i % 16].wrapping_mul(31).wrapping_add(b);
+    }
+
+    loop {
+        let link = match PtpIpLink::accept(&listener, guid, &name) {
+            Ok(link) => link,
+            Err(e) => {
+                warn!("PTP/IP handshake failed: {}", e);
+                continue;
+            }
+        };
+        info!(
+            "Serving {} to {}",
+            root.as_ref().display(),
+            link.initiator_name()
+        );
+        let device = DirectoryDevice::new(root.as_ref())?;
+        if let Err(e) = Responder::new(link, device).serve() {
+            warn!("PTP/IP connection failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // both ends of a TCP connection on the loopback interface
+    fn connection() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (client, server)
+    }
+
+    #[test]
+    fn handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let responder = thread::spawn(move || PtpIpLink::accept(&listener, [7; 16], "Test"));
+
+        let mut command = TcpStream::connect(addr).unwrap();
+        let mut request = vec![0; 16];
+        write_name(&mut request, "Initiator");
+        request.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+        write_packet(&mut command, INIT_COMMAND_REQUEST, &request).unwrap();
+        let (kind, ack) = read_packet(&mut command, MAX_PACKET_LEN).unwrap().unwrap();
+        assert_eq!(kind, INIT_COMMAND_ACK);
+        assert_eq!(LittleEndian::read_u32(&ack), CONNECTION_NUMBER);
+
+        // the event connection of another initiator
+        let mut other = TcpStream::connect(addr).unwrap();
+        write_packet(&mut other, INIT_EVENT_REQUEST, &7u32.to_le_bytes()).unwrap();
+        assert_eq!(
+            read_packet(&mut other, MAX_PACKET_LEN).unwrap().unwrap().0,
+            INIT_FAIL
+        );
+
+        let mut events = TcpStream::connect(addr).unwrap();
+        write_packet(
+            &mut events,
+            INIT_EVENT_REQUEST,
+            &CONNECTION_NUMBER.to_le_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            read_packet(&mut events, MAX_PACKET_LEN).unwrap().unwrap().0,
+            INIT_EVENT_ACK
+        );
+
+        let link = responder.join().unwrap().unwrap();
+        assert_eq!(link.initiator_name(), "Initiator");
+    }
+
+    #[test]
+    fn oversized_packet() {
+        let (mut client, mut server) = connection();
+        client
+            .write_all(&[0xFF, 0xFF, 0xFF, 0xFF, 6, 0, 0, 0])
+            .unwrap();
+        assert!(matches!(
+            read_packet(&mut server, MAX_PACKET_LEN),
+            Err(Error::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_data_packet() {
+        let (mut client, mut server) = connection();
+        client
+            .write_all(&[0xFF, 0xFF, 0xFF, 0xFF, 10, 0, 0, 0, 1, 2, 3])
+            .unwrap();
+        drop(client);
+        // read as it arrives rather than allocated up front
+        assert!(read_packet(&mut server, u32::MAX as usize).is_err());
+    }
+
+    // a link whose command connection is fed from the returned stream
+    fn link() -> (PtpIpLink, TcpStream) {
+        let (client, server) = connection();
+        let (_, events) = connection();
+        let link = PtpIpLink {
+            command: server,
+            events,
+            initiator_name: String::new(),
+        };
+        (link, client)
+    }
+
+    fn request() -> Request {
+        Request {
+            code: 0x100D,
+            tid: 5,
+            params: vec![],
+            has_data: Some(true),
+        }
+    }
+
+    fn start_data(tid: u32, len: u64) -> Vec<u8> {
+        let mut payload = tid.to_le_bytes().to_vec();
+        payload.extend_from_slice(&len.to_le_bytes());
+        payload
+    }
+
+    #[test]
+    fn data_phase() {
+        let (mut link, mut client) = link();
+        write_packet(&mut client, START_DATA, &start_data(5, 6)).unwrap();
+        write_packet(&mut client, DATA, &[5, 0, 0, 0, 1, 2, 3, 4]).unwrap();
+        write_packet(&mut client, END_DATA, &[5, 0, 0, 0, 5, 6]).unwrap();
+        assert_eq!(link.read_data(&request()).unwrap(), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn data_beyond_start_data_length() {
+        let (mut link, mut client) = link();
+        write_packet(&mut client, START_DATA, &start_data(5, 4)).unwrap();
+        write_packet(&mut client, DATA, &[5, 0, 0, 0, 1, 2, 3]).unwrap();
+        write_packet(&mut client, END_DATA, &[5, 0, 0, 0, 4, 5]).unwrap();
+        assert!(matches!(
+            link.read_data(&request()),
+            Err(Error::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_data_phase() {
+        let (mut link, mut client) = link();
+        let len = MAX_DATA_LEN as u64 + 1;
+        write_packet(&mut client, START_DATA, &start_data(5, len)).unwrap();
+        assert!(matches!(
+            link.read_data(&request()),
+            Err(Error::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn data_without_start_data() {
+        let (mut link, mut client) = link();
+        write_packet(&mut client, END_DATA, &[5, 0, 0, 0, 1, 2]).unwrap();
+        assert!(matches!(
+            link.read_data(&request()),
+            Err(Error::Malformed(_))
+        ));
+    }
+}
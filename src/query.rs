@@ -0,0 +1,165 @@
+use super::{ObjectInfo, ObjectTree, ProtectionStatus};
+
+/// Criteria selecting objects, for `Camera::query_objects` and `ObjectQuery::filter_tree`.
+///
+/// The format and the parent are passed to GetObjectHandles, the other criteria are
+/// checked against the ObjectInfo of each object.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectQuery {
+    format: Option<u16>,
+    parent: Option<u32>,
+    name: Option<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    protection: Option<ProtectionStatus>,
+    captured_after: Option<String>,
+    captured_before: Option<String>,
+}
+
+impl ObjectQuery {
+    pub fn new() -> ObjectQuery {
+        ObjectQuery::default()
+    }
+
+    pub fn with_format(mut self, format: u16) -> ObjectQuery {
+        self.format = Some(format);
+        self
+    }
+
+    /// Only the direct children of `parent`, 0xFFFFFFFF for the root of the store.
+    pub fn with_parent(mut self, parent: u32) -> ObjectQuery {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Only the objects whose name matches `pattern`, where `*` matches any run of
+    /// characters and `?` any single one. Case is ignored, as on the FAT cards cameras use.
+    pub fn with_name(mut self, pattern: &str) -> ObjectQuery {
+        self.name = Some(pattern.to_owned());
+        self
+    }
+
+    pub fn with_min_size(mut self, size: u64) -> ObjectQuery {
+        self.min_size = Some(size);
+        self
+    }
+
+    pub fn with_max_size(mut self, size: u64) -> ObjectQuery {
+        self.max_size = Some(size);
+        self
+    }
+
+    pub fn with_protection(mut self, protection: ProtectionStatus) -> ObjectQuery {
+        self.protection = Some(protection);
+        self
+    }
+
+    /// Only the objects captured at or after `date`, a PTP DateTime string such as
+    /// "20240131T180000". See `capture_date_in_range` for how dates are compared.
+    pub fn with_captured_after(mut self, date: &str) -> ObjectQuery {
+        self.captured_after = Some(date.to_owned());
+        self
+    }
+
+    /// Only the objects captured before `date`, a PTP DateTime string.
+    pub fn with_captured_before(mut self, date: &str) -> ObjectQuery {
+        self.captured_before = Some(date.to_owned());
+        self
+    }
+
+    pub fn format(&self) -> Option<u16> {
+        self.format
+    }
+
+    pub fn parent(&self) -> Option<u32> {
+        self.parent
+    }
+
+    /// Whether `info` meets all the criteria, including those sent to the device.
+    pub fn matches(&self, info: &ObjectInfo) -> bool {
+        let size = info.ObjectCompressedSize as u64;
+        self.format.map_or(true, |f| f == info.ObjectFormat)
+            && self.parent.map_or(true, |p| {
+                p == info.ParentObject || (p == 0xFFFF_FFFF && info.ParentObject == 0)
+            })
+            && self
+                .name
+                .as_deref()
+                .map_or(true, |pattern| glob_match(pattern, &info.Filename))
+            && self.min_size.map_or(true, |min| size >= min)
+            && self.max_size.map_or(true, |max| size <= max)
+            && self
+                .protection
+                .map_or(true, |p| p as u16 == info.ProtectionStatus)
+            && (self.captured_after.is_none() && self.captured_before.is_none()
+                || capture_date_in_range(
+                    &info.CaptureDate,
+                    self.captured_after.as_deref(),
+                    self.captured_before.as_deref(),
+                ))
+    }
+
+    /// Drop the objects of a tree from `Camera::object_tree` that don't match. Folders are
+    /// kept regardless of the criteria so the matches below them stay reachable, use
+    /// `ObjectTree::walk` to flatten the result.
+    pub fn filter_tree(&self, nodes: &mut Vec<ObjectTree>) {
+        nodes.retain_mut(|node| match node.children {
+            Some(ref mut children) => {
+                self.filter_tree(children);
+                true
+            }
+            None => node.info.is_folder() || self.matches(&node.info),
+        });
+    }
+}
+
+/// Whether the PTP DateTime `date` is at or after `after` and before `before`.
+///
+/// Dates are compared on their "YYYYMMDDThhmmss" part, i.e. as the wall clock time of the
+/// device: most cameras don't record a UTC offset, and those that do use their own. An
+/// empty or malformed `date` is never in range. The bounds may also be just a date,
+/// "YYYYMMDD", standing for its midnight.
+pub fn capture_date_in_range<'a>(
+    date: &str,
+    after: Option<&'a str>,
+    before: Option<&'a str>,
+) -> bool {
+    let date = match date.get(..15) {
+        Some(date) if date.as_bytes()[8] == b'T' => date,
+        _ => return false,
+    };
+    let bound = |b: &'a str| b.get(..15).unwrap_or(b);
+    after.map_or(true, |after| date >= bound(after))
+        && before.map_or(true, |before| date < bound(before))
+}
+
+// `*` and `?` wildcards, ignoring ASCII case
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let name: Vec<char> = name.chars().map(|c| c.to_ascii_lowercase()).collect();
+
+    // greedy matching, backtracking to the last `*` on a mismatch
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,104 @@
+use std::sync::Mutex;
+
+/// Deviations from the PTP specification a device needs worked around, see `Camera::set_quirks`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// Outgoing containers whose length is a multiple of 512 bytes have to be ended with
+    /// a zero-length packet.
+    pub needs_zlp: bool,
+    /// The device has no usable event channel, `poll_event` only waits out its timeout.
+    /// Events arriving on the bulk pipe are still collected, see `drain_pending_events`.
+    pub no_interrupt_endpoint: bool,
+    /// Session id the device insists on, used instead of 1 by `open_session`.
+    pub session_id: Option<u32>,
+    /// GetPartialObject returns wrong data, so it fails with OperationNotSupported without
+    /// reaching the device and callers fall back to GetObject.
+    pub broken_partial_object: bool,
+    /// The device may send the response of an operation ahead of its data phase, so an
+    /// Ok response arriving first for an operation returning data (`has_data_in`) is held
+    /// until the data has been read.
+    pub response_before_data: bool,
+}
+
+/// Quirks of the devices matching a vendor id and, if given, a product id and model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceQuirks {
+    pub vendor_id: u16,
+    pub product_id: Option<u16>,
+    /// Matched against the USB product string, e.g. for vendors reusing a product id
+    /// across models.
+    pub model: Option<String>,
+    pub quirks: Quirks,
+}
+
+impl DeviceQuirks {
+    fn matches(&self, vendor_id: u16, product_id: u16, model: Option<&str>) -> bool {
+        self.vendor_id == vendor_id
+            && self.product_id.map_or(true, |p| p == product_id)
+            && self.model.as_deref().map_or(true, |m| Some(m) == model)
+    }
+
+    // entries naming the product and model win over those for the whole vendor
+    fn specificity(&self) -> u8 {
+        self.product_id.is_some() as u8 + self.model.is_some() as u8
+    }
+}
+
+const CANON: u16 = 0x04a9;
+
+// EOS bodies report their events through the Canon GetEvent operation, nothing useful
+// arrives on the interrupt endpoint
+const CANON_EOS: Quirks = Quirks {
+    needs_zlp: false,
+    no_interrupt_endpoint: true,
+    session_id: None,
+    broken_partial_object: false,
+    response_before_data: false,
+};
+
+// Devices known to need quirks. Entries are only added once confirmed on the hardware.
+const BUILTIN: &[(u16, Option<u16>, Option<&str>, Quirks)] = &[
+    // Canon EOS 5D Mark II
+    (CANON, Some(0x3199), None, CANON_EOS),
+    // Canon EOS 550D
+    (CANON, Some(0x31ea), None, CANON_EOS),
+    // Canon EOS 5D Mark III
+    (CANON, Some(0x323a), None, CANON_EOS),
+    // Canon EOS 6D
+    (CANON, Some(0x3250), None, CANON_EOS),
+];
+
+static REGISTERED: Mutex<Vec<DeviceQuirks>> = Mutex::new(Vec::new());
+
+/// Add an entry to the quirks database, for devices that aren't known to it yet.
+/// Registered entries take precedence over the built-in ones, the latest registered first.
+pub fn register_quirks(entry: DeviceQuirks) {
+    REGISTERED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(entry);
+}
+
+/// The quirks of a device, applied by `Camera::new`. When several entries match, the most
+/// specific one is used; unknown devices get the default, no quirks.
+pub fn lookup_quirks(vendor_id: u16, product_id: u16, model: Option<&str>) -> Quirks {
+    let registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+    let builtin = BUILTIN
+        .iter()
+        .map(|&(vendor_id, product_id, model, quirks)| DeviceQuirks {
+            vendor_id,
+            product_id,
+            model: model.map(str::to_owned),
+            quirks,
+        })
+        .collect::<Vec<_>>();
+
+    // max_by_key returns the last of equal elements, so registered entries go last
+    builtin
+        .iter()
+        .chain(registered.iter())
+        .filter(|e| e.matches(vendor_id, product_id, model))
+        .max_by_key(|e| e.specificity())
+        .map(|e| e.quirks)
+        .unwrap_or_default()
+}
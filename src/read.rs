@@ -1,6 +1,9 @@
 use super::Error;
 use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::Cursor;
+use std::io::{self, Cursor};
+
+/// Default limit on the number of elements of an array in a dataset.
+pub const MAX_ARRAY_LEN: usize = 16 * 1024 * 1024;
 
 pub trait Read: ReadBytesExt {
     fn read_ptp_u8(&mut self) -> Result<u8, Error> {
@@ -43,12 +46,68 @@ pub trait Read: ReadBytesExt {
         Ok(self.read_i128::<LittleEndian>()?)
     }
 
+    /// Largest array length accepted before allocating for its elements.
+    fn max_array_len(&self) -> usize {
+        MAX_ARRAY_LEN
+    }
+
+    /// Position of the next byte to read, if known.
+    fn offset(&self) -> Option<u64> {
+        None
+    }
+
+    /// Read `field` of `dataset` with `read`, naming both along with the offset of the
+    /// field in a Malformed error.
+    fn read_field<T, F: FnOnce(&mut Self) -> Result<T, Error>>(
+        &mut self,
+        dataset: &str,
+        field: &str,
+        read: F,
+    ) -> Result<T, Error> {
+        let offset = self.offset();
+        read(self).map_err(|e| match e {
+            Error::Malformed(msg) => Error::Malformed(match offset {
+                Some(offset) => format!("{}.{} at offset {}: {}", dataset, field, offset, msg),
+                None => format!("{}.{}: {}", dataset, field, msg),
+            }),
+            e => e,
+        })
+    }
+
+    /// Number of bytes left to read, if known.
+    fn remaining_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// Check an array length read off the wire before allocating for it, `elem_size`
+    /// being the minimum encoded size of an element.
+    fn check_array_len(&self, len: usize, elem_size: usize) -> Result<(), Error> {
+        if len > self.max_array_len() {
+            return Err(Error::Malformed(format!(
+                "Array of {} elements exceeds the limit of {}",
+                len,
+                self.max_array_len()
+            )));
+        }
+        if let Some(remaining) = self.remaining_len() {
+            if len.saturating_mul(elem_size) > remaining {
+                return Err(Error::Malformed(format!(
+                    "Array of {} elements doesn't fit the remaining {} bytes",
+                    len, remaining
+                )));
+            }
+        }
+        Ok(())
+    }
+
     #[inline(always)]
     fn read_ptp_vec<T: Sized, U: Fn(&mut Self) -> Result<T, Error>>(
         &mut self,
         func: U,
     ) -> Result<Vec<T>, Error> {
         let len = self.read_u32::<LittleEndian>()? as usize;
+        // only used for integers, whose encoded size is their size in memory
+        self.check_array_len(len, std::mem::size_of::<T>())?;
         (0..len).map(|_| func(self)).collect()
     }
 
@@ -92,9 +151,30 @@ pub trait Read: ReadBytesExt {
         self.read_ptp_vec(|cur| cur.read_ptp_i128())
     }
 
+    /// Whether `read_ptp_str` tolerates malformed strings, see `DatasetReader`.
+    fn lenient_strings(&self) -> bool {
+        false
+    }
+
     fn read_ptp_str(&mut self) -> Result<String, Error> {
         let len = self.read_u8()?;
-        if len > 0 {
+        if len > 0 && self.lenient_strings() {
+            // read up to the announced length, terminator included, but settle for
+            // whatever is left if the data ends early
+            let mut data = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                match self.read_u16::<LittleEndian>() {
+                    Ok(unit) => data.push(unit),
+                    Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            // drop the terminator and any null padding
+            while data.last() == Some(&0) {
+                data.pop();
+            }
+            Ok(String::from_utf16_lossy(&data))
+        } else if len > 0 {
             // len includes the trailing null u16
             let data: Vec<u16> = (0..(len - 1))
                 .map(|_| self.read_u16::<LittleEndian>())
@@ -111,6 +191,15 @@ pub trait Read: ReadBytesExt {
 }
 
 impl<T: AsRef<[u8]>> Read for Cursor<T> {
+    fn offset(&self) -> Option<u64> {
+        Some(self.position())
+    }
+
+    fn remaining_len(&self) -> Option<usize> {
+        let len = self.get_ref().as_ref().len() as u64;
+        Some(len.saturating_sub(self.position()) as usize)
+    }
+
     fn expect_end(&mut self) -> Result<(), Error> {
         let len = self.get_ref().as_ref().len();
         if len as u64 != self.position() {
@@ -124,3 +213,70 @@ impl<T: AsRef<[u8]>> Read for Cursor<T> {
         }
     }
 }
+
+/// Reader applying decoding options to the datasets read through it.
+///
+/// With `lenient` strings, a string cut short by the end of the data, e.g. the last one
+/// of a dataset missing its trailing null, is accepted, null padding within the announced
+/// length is dropped, and invalid UTF-16 is replaced with U+FFFD instead of failing the
+/// dataset. Strings are still read by their announced length: one missing its null in the
+/// middle of a dataset shifts the fields after it, as the data doesn't tell where it ends.
+pub struct DatasetReader<R> {
+    inner: R,
+    lenient: bool,
+    max_array_len: usize,
+}
+
+impl<R> DatasetReader<R> {
+    pub fn new(inner: R) -> DatasetReader<R> {
+        DatasetReader {
+            inner,
+            lenient: false,
+            max_array_len: MAX_ARRAY_LEN,
+        }
+    }
+
+    /// Decode strings leniently, disabled by default.
+    pub fn lenient(mut self, lenient: bool) -> DatasetReader<R> {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Reject arrays announcing more than `max` elements, `MAX_ARRAY_LEN` by default.
+    pub fn max_array_len(mut self, max: usize) -> DatasetReader<R> {
+        self.max_array_len = max;
+        self
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: io::Read> io::Read for DatasetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<T: AsRef<[u8]>> Read for DatasetReader<Cursor<T>> {
+    fn lenient_strings(&self) -> bool {
+        self.lenient
+    }
+
+    fn max_array_len(&self) -> usize {
+        self.max_array_len
+    }
+
+    fn offset(&self) -> Option<u64> {
+        self.inner.offset()
+    }
+
+    fn remaining_len(&self) -> Option<usize> {
+        self.inner.remaining_len()
+    }
+
+    fn expect_end(&mut self) -> Result<(), Error> {
+        self.inner.expect_end()
+    }
+}
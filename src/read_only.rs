@@ -0,0 +1,288 @@
+use super::{
+    Camera, CommandCode, DataType, DeviceInfo, Error, MtpCommandCode, ObjectInfo, ObjectTree,
+    Objects, PropInfo, PtpEvent, StandardCommandCode, StorageInfo, Transport,
+};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Whether operation `code` changes the contents or the settings of the device, the
+/// operations refused by `CameraConfig::read_only` and `ReadOnlyCamera`.
+///
+/// Vendor operations aren't classified and are never considered destructive, so
+/// `ReadOnlyCamera::command` goes by `is_read_operation` instead.
+pub fn is_destructive(code: CommandCode) -> bool {
+    matches!(
+        code,
+        StandardCommandCode::DeleteObject
+            | StandardCommandCode::SendObjectInfo
+            | StandardCommandCode::SendObject
+            | StandardCommandCode::InitiateCapture
+            | StandardCommandCode::FormatStore
+            | StandardCommandCode::ResetDevice
+            | StandardCommandCode::SetObjectProtection
+            | StandardCommandCode::SetDevicePropValue
+            | StandardCommandCode::ResetDevicePropValue
+            | StandardCommandCode::MoveObject
+            | StandardCommandCode::CopyObject
+            | StandardCommandCode::InitiateOpenCapture
+            | MtpCommandCode::SetObjectPropValue
+            | MtpCommandCode::SetObjectPropList
+            | MtpCommandCode::SendObjectPropList
+            | MtpCommandCode::SetObjectReferences
+            | MtpCommandCode::UpdateDeviceFirmware
+            | MtpCommandCode::SendPartialObject
+            | MtpCommandCode::TruncateObject
+            | MtpCommandCode::BeginEditObject
+            | MtpCommandCode::EndEditObject
+    )
+}
+
+/// Whether operation `code` is a standard or MTP operation known to only read from the
+/// device, the operations `ReadOnlyCamera::command` lets through.
+pub fn is_read_operation(code: CommandCode) -> bool {
+    matches!(
+        code,
+        StandardCommandCode::GetDeviceInfo
+            | StandardCommandCode::OpenSession
+            | StandardCommandCode::CloseSession
+            | StandardCommandCode::GetStorageIDs
+            | StandardCommandCode::GetStorageInfo
+            | StandardCommandCode::GetNumObjects
+            | StandardCommandCode::GetObjectHandles
+            | StandardCommandCode::GetObjectInfo
+            | StandardCommandCode::GetObject
+            | StandardCommandCode::GetThumb
+            | StandardCommandCode::GetDevicePropDesc
+            | StandardCommandCode::GetDevicePropValue
+            | StandardCommandCode::GetPartialObject
+            | MtpCommandCode::GetObjectPropsSupported
+            | MtpCommandCode::GetObjectPropDesc
+            | MtpCommandCode::GetObjectPropValue
+            | MtpCommandCode::GetObjectPropList
+            | MtpCommandCode::GetInterdependentPropDesc
+            | MtpCommandCode::GetObjectReferences
+            | MtpCommandCode::GetPartialObject64
+    )
+}
+
+/// A `Camera` that can only be read from, e.g. for forensic acquisition or to hand to
+/// untrusted code.
+///
+/// Only the non-destructive operations are exposed, `command` only sends the operations
+/// of `is_read_operation`, and the wrapped camera has `CameraConfig::read_only` set.
+pub struct ReadOnlyCamera<T: Transport> {
+    camera: Camera<T>,
+}
+
+impl<T: Transport> ReadOnlyCamera<T> {
+    pub fn new(mut camera: Camera<T>) -> ReadOnlyCamera<T> {
+        let mut config = camera.config().clone();
+        config.read_only = true;
+        camera.set_config(config);
+        ReadOnlyCamera { camera }
+    }
+
+    /// The wrapped camera, for its accessors.
+    pub fn camera(&self) -> &Camera<T> {
+        &self.camera
+    }
+
+    /// Give the camera back, still configured as read-only.
+    pub fn into_inner(self) -> Camera<T> {
+        self.camera
+    }
+
+    /// Execute a PTP transaction, failing with `Error::ReadOnly` for all but the operations
+    /// of `is_read_operation`. Vendor operations are always refused, as nothing tells
+    /// whether they modify the device.
+    pub fn command(
+        &mut self,
+        code: CommandCode,
+        params: &[u32],
+        data: Option<&[u8]>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, Error> {
+        if !is_read_operation(code) {
+            return Err(Error::ReadOnly(code));
+        }
+        self.camera.command(code, params, data, timeout)
+    }
+
+    pub fn open_session(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        self.camera.open_session(timeout)
+    }
+
+    pub fn close_session(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        self.camera.close_session(timeout)
+    }
+
+    pub fn get_device_info(&mut self, timeout: Option<Duration>) -> Result<DeviceInfo, Error> {
+        self.camera.get_device_info(timeout)
+    }
+
+    pub fn get_storageids(&mut self, timeout: Option<Duration>) -> Result<Vec<u32>, Error> {
+        self.camera.get_storageids(timeout)
+    }
+
+    pub fn get_storage_info(
+        &mut self,
+        storage_id: u32,
+        timeout: Option<Duration>,
+    ) -> Result<StorageInfo, Error> {
+        self.camera.get_storage_info(storage_id, timeout)
+    }
+
+    pub fn get_objecthandles(
+        &mut self,
+        storage_id: u32,
+        handle_id: u32,
+        filter: Option<u32>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u32>, Error> {
+        self.camera
+            .get_objecthandles(storage_id, handle_id, filter, timeout)
+    }
+
+    pub fn get_numobjects(
+        &mut self,
+        storage_id: u32,
+        handle_id: u32,
+        filter: Option<u32>,
+        timeout: Option<Duration>,
+    ) -> Result<u32, Error> {
+        self.camera
+            .get_numobjects(storage_id, handle_id, filter, timeout)
+    }
+
+    /// See `Camera::object_tree`.
+    pub fn object_tree<F: FnMut(usize)>(
+        &mut self,
+        storage_id: u32,
+        root: Option<u32>,
+        depth: Option<usize>,
+        format: Option<u16>,
+        progress: F,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<ObjectTree>, Error> {
+        self.camera
+            .object_tree(storage_id, root, depth, format, progress, timeout)
+    }
+
+    /// See `Camera::objects`.
+    pub fn objects(
+        &mut self,
+        storage_id: u32,
+        format: Option<u16>,
+        timeout: Option<Duration>,
+    ) -> Objects<'_, T> {
+        self.camera.objects(storage_id, format, timeout)
+    }
+
+    pub fn get_objectinfo(
+        &mut self,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<ObjectInfo, Error> {
+        self.camera.get_objectinfo(handle, timeout)
+    }
+
+    pub fn get_object(&mut self, handle: u32, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
+        self.camera.get_object(handle, timeout)
+    }
+
+    pub fn get_object_to<W: io::Write + ?Sized>(
+        &mut self,
+        handle: u32,
+        sink: &mut W,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.camera.get_object_to(handle, sink, timeout)
+    }
+
+    pub fn get_object_to_file<P: AsRef<Path>, F: FnMut(u64, u64)>(
+        &mut self,
+        handle: u32,
+        path: P,
+        progress: F,
+        timeout: Option<Duration>,
+    ) -> Result<u64, Error> {
+        self.camera
+            .get_object_to_file(handle, path, progress, timeout)
+    }
+
+    pub fn get_partialobject(
+        &mut self,
+        handle: u32,
+        offset: u32,
+        max: u32,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, Error> {
+        self.camera.get_partialobject(handle, offset, max, timeout)
+    }
+
+    pub fn get_partialobject64(
+        &mut self,
+        handle: u32,
+        offset: u64,
+        max: u32,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, Error> {
+        self.camera
+            .get_partialobject64(handle, offset, max, timeout)
+    }
+
+    pub fn get_thumb(&mut self, handle: u32, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
+        self.camera.get_thumb(handle, timeout)
+    }
+
+    pub fn get_device_prop_desc(
+        &mut self,
+        property_code: u16,
+        timeout: Option<Duration>,
+    ) -> Result<PropInfo, Error> {
+        self.camera.get_device_prop_desc(property_code, timeout)
+    }
+
+    pub fn get_device_prop_value(
+        &mut self,
+        property_code: u16,
+        data_type: u16,
+        timeout: Option<Duration>,
+    ) -> Result<DataType, Error> {
+        self.camera
+            .get_device_prop_value(property_code, data_type, timeout)
+    }
+
+    pub fn poll_event(&mut self, timeout: Option<Duration>) -> Result<Option<PtpEvent>, Error> {
+        self.camera.poll_event(timeout)
+    }
+
+    pub fn drain_pending_events(&mut self) -> Vec<PtpEvent> {
+        self.camera.drain_pending_events()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CanonCommandCode, MockDevice, MockTransport};
+
+    #[test]
+    fn command_refuses_all_but_reads() {
+        let mut camera = ReadOnlyCamera::new(MockTransport::open(MockDevice::default()).unwrap());
+        assert!(camera
+            .command(StandardCommandCode::GetStorageIDs, &[], None, None)
+            .is_ok());
+        for &code in &[
+            StandardCommandCode::DeleteObject,
+            CanonCommandCode::EosDeleteObject,
+            CanonCommandCode::EosFormatStore,
+        ] {
+            assert!(matches!(
+                camera.command(code, &[0xFFFF_FFFF], None, None),
+                Err(Error::ReadOnly(c)) if c == code
+            ));
+        }
+    }
+}
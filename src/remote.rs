@@ -0,0 +1,68 @@
+use super::Error;
+use std::time::Duration;
+
+/// Direction of a manual focus move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusDirection {
+    Near,
+    Far,
+}
+
+/// Size of a manual focus move, mapped to each vendor's smallest, medium and largest step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusStep {
+    Fine,
+    Medium,
+    Coarse,
+}
+
+/// Outcome of the last autofocus run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AfStatus {
+    Focusing,
+    InFocus,
+    Failed,
+    /// The camera only reports focus through events, if at all.
+    Unknown,
+}
+
+/// Vendor-agnostic control of the exposure settings and the shutter, implemented by
+/// `EosRemoteControl`, `NikonRemoteControl` and `SonyRemoteControl`.
+///
+/// Values are in the camera's own encoding of each setting, i.e. one of those listed in
+/// the form of the matching property descriptor.
+pub trait RemoteControl {
+    fn set_aperture(&mut self, value: u32, timeout: Option<Duration>) -> Result<(), Error>;
+
+    fn set_shutter_speed(&mut self, value: u32, timeout: Option<Duration>) -> Result<(), Error>;
+
+    fn set_iso(&mut self, value: u32, timeout: Option<Duration>) -> Result<(), Error>;
+
+    fn set_white_balance(&mut self, value: u32, timeout: Option<Duration>) -> Result<(), Error>;
+
+    /// Take a picture. The new object is announced by an ObjectAdded event, or the
+    /// vendor's equivalent.
+    fn trigger_shutter(&mut self, timeout: Option<Duration>) -> Result<(), Error>;
+
+    /// Move the focus of the lens, which must be in manual focus or allow manual override.
+    fn drive_focus(
+        &mut self,
+        direction: FocusDirection,
+        step: FocusStep,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error>;
+
+    /// Start an autofocus run, see `af_status` for its outcome.
+    fn autofocus(&mut self, timeout: Option<Duration>) -> Result<(), Error>;
+
+    fn af_status(&mut self, timeout: Option<Duration>) -> Result<AfStatus, Error>;
+
+    /// Start recording a movie, the camera must be in movie mode.
+    fn start_recording(&mut self, timeout: Option<Duration>) -> Result<(), Error>;
+
+    fn stop_recording(&mut self, timeout: Option<Duration>) -> Result<(), Error>;
+
+    /// Whether a movie is being recorded. Cameras offer no reliable way to ask, so this
+    /// is the state last set through this controller.
+    fn is_recording(&self) -> bool;
+}
@@ -0,0 +1,405 @@
+use super::container::{ContainerInfo, ContainerType, CONTAINER_INFO_SIZE};
+use super::{
+    CommandCode, Error, MtpCommandCode, PtpEvent, ResponseCode, StandardCommandCode,
+    StandardResponseCode, Transport,
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io;
+use std::time::Duration;
+
+/// The outcome of an operation, returned by a `Handler`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub code: ResponseCode,
+    pub params: Vec<u32>,
+    /// Data sent to the initiator before the response.
+    pub data: Option<Vec<u8>>,
+}
+
+impl Reply {
+    pub fn ok() -> Reply {
+        Reply::error(StandardResponseCode::Ok)
+    }
+
+    /// A reply without data, usually an error such as InvalidObjectHandle.
+    pub fn error(code: ResponseCode) -> Reply {
+        Reply {
+            code,
+            params: vec![],
+            data: None,
+        }
+    }
+
+    /// An Ok reply sending `data` to the initiator.
+    pub fn data(data: Vec<u8>) -> Reply {
+        Reply {
+            data: Some(data),
+            ..Reply::ok()
+        }
+    }
+
+    pub fn with_params(mut self, params: &[u32]) -> Reply {
+        self.params = params.to_vec();
+        self
+    }
+}
+
+/// Whether the initiator sends data along with operation `code`, for the standard and MTP
+/// operations.
+pub fn has_data_out(code: CommandCode) -> bool {
+    matches!(
+        code,
+        StandardCommandCode::SendObjectInfo
+            | StandardCommandCode::SendObject
+            | StandardCommandCode::SetDevicePropValue
+            | MtpCommandCode::SetObjectPropValue
+            | MtpCommandCode::SetObjectPropList
+            | MtpCommandCode::SendObjectPropList
+            | MtpCommandCode::SetObjectReferences
+            | MtpCommandCode::UpdateDeviceFirmware
+            | MtpCommandCode::SendPartialObject
+    )
+}
+
+/// Whether the device sends data back for operation `code`, for the standard and MTP
+/// operations.
+pub fn has_data_in(code: CommandCode) -> bool {
+    matches!(
+        code,
+        StandardCommandCode::GetDeviceInfo
+            | StandardCommandCode::GetStorageIDs
+            | StandardCommandCode::GetStorageInfo
+            | StandardCommandCode::GetObjectHandles
+            | StandardCommandCode::GetObjectInfo
+            | StandardCommandCode::GetObject
+            | StandardCommandCode::GetThumb
+            | StandardCommandCode::GetDevicePropDesc
+            | StandardCommandCode::GetDevicePropValue
+            | StandardCommandCode::GetPartialObject
+            | MtpCommandCode::GetObjectPropsSupported
+            | MtpCommandCode::GetObjectPropDesc
+            | MtpCommandCode::GetObjectPropValue
+            | MtpCommandCode::GetObjectPropList
+            | MtpCommandCode::GetInterdependentPropDesc
+            | MtpCommandCode::GetObjectReferences
+            | MtpCommandCode::GetPartialObject64
+    )
+}
+
+/// The device side of PTP: answers the operations received by a `Responder`.
+pub trait Handler {
+    /// Run one operation. `data` is what the initiator sent along, if anything.
+    fn operation(&mut self, code: CommandCode, params: &[u32], data: Option<&[u8]>) -> Reply;
+
+    /// Whether operation `code` comes with data from the initiator, which the framing of
+    /// USB containers doesn't tell. Override it for vendor operations sending data.
+    fn expects_data(&self, code: CommandCode) -> bool {
+        has_data_out(code)
+    }
+
+    /// The next event to send, called after each transaction until it returns None.
+    fn poll_event(&mut self) -> Option<PtpEvent> {
+        None
+    }
+}
+
+/// An operation request received from the initiator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub code: CommandCode,
+    pub tid: u32,
+    pub params: Vec<u32>,
+    /// Whether a data phase follows, for links whose framing tells (PTP/IP). None leaves
+    /// it to `Handler::expects_data`.
+    pub has_data: Option<bool>,
+}
+
+/// The framing of a connection to an initiator, as seen from the device.
+pub trait ResponderLink {
+    /// Wait for the next operation request, None once the initiator has gone away.
+    fn read_request(&mut self) -> Result<Option<Request>, Error>;
+
+    /// Receive the data the initiator sends along with `request`.
+    fn read_data(&mut self, request: &Request) -> Result<Vec<u8>, Error>;
+
+    /// Send the data phase of `request` to the initiator.
+    fn write_data(&mut self, request: &Request, data: &[u8]) -> Result<(), Error>;
+
+    fn write_response(
+        &mut self,
+        request: &Request,
+        code: ResponseCode,
+        params: &[u32],
+    ) -> Result<(), Error>;
+
+    fn write_event(&mut self, event: &PtpEvent) -> Result<(), Error>;
+}
+
+/// USB style framing of containers over a `Transport`, e.g. `StreamTransport` over the
+/// endpoint files of a Linux functionfs gadget.
+///
+/// Events go to a separate transport for the interrupt endpoint if one is given, and are
+/// discarded otherwise. Zero-length packets are left to the transport.
+pub struct ContainerLink<T: Transport> {
+    transport: T,
+    events: Option<Box<dyn Transport>>,
+    timeout: Duration,
+    buf: Vec<u8>,
+}
+
+impl<T: Transport> ContainerLink<T> {
+    pub fn new(transport: T) -> ContainerLink<T> {
+        ContainerLink {
+            transport,
+            events: None,
+            timeout: Duration::from_secs(5),
+            buf: vec![0; 512 * 1024],
+        }
+    }
+
+    /// Send events through `events`, the interrupt endpoint.
+    pub fn with_events<E: Transport + 'static>(mut self, events: E) -> ContainerLink<T> {
+        self.events = Some(Box::new(events));
+        self
+    }
+
+    /// Timeout of the transfers within a transaction. Waiting for the next request isn't
+    /// limited.
+    pub fn with_timeout(mut self, timeout: Duration) -> ContainerLink<T> {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    // None when the link ended between containers
+    fn read_container(&mut self, idle: bool) -> Result<Option<(ContainerInfo, Vec<u8>)>, Error> {
+        let n = loop {
+            match self.transport.read(&mut self.buf, self.timeout) {
+                // stray zero-length packets between containers
+                Ok(0) => continue,
+                Ok(n) => break n,
+                Err(Error::Usb(rusb::Error::Timeout)) if idle => continue,
+                // streams report their end as a truncated container
+                Err(Error::Malformed(_)) | Err(Error::Usb(rusb::Error::NoDevice)) if idle => {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        if n < CONTAINER_INFO_SIZE {
+            return Err(Error::Malformed(format!(
+                "Container of {} bytes is shorter than its header",
+                n
+            )));
+        }
+        let info = ContainerInfo::parse(&self.buf[..n])?;
+        let mut payload = self.buf[CONTAINER_INFO_SIZE..n].to_vec();
+        while payload.len() < info.payload_len {
+            let n = self.transport.read(&mut self.buf, self.timeout)?;
+            if n == 0 {
+                return Err(Error::Malformed(format!(
+                    "{:?} container ended after {} of {} bytes",
+                    info.kind,
+                    payload.len(),
+                    info.payload_len
+                )));
+            }
+            payload.extend_from_slice(&self.buf[..n]);
+        }
+        if payload.len() > info.payload_len {
+            return Err(Error::Malformed(format!(
+                "{:?} container overran its length of {} bytes",
+                info.kind, info.payload_len
+            )));
+        }
+        Ok(Some((info, payload)))
+    }
+
+    fn write_container(
+        &mut self,
+        kind: ContainerType,
+        code: u16,
+        tid: u32,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        let mut buf = Vec::with_capacity(CONTAINER_INFO_SIZE + payload.len());
+        ContainerInfo {
+            payload_len: payload.len(),
+            kind,
+            code,
+            tid,
+        }
+        .write(&mut buf);
+        buf.extend_from_slice(payload);
+        self.transport.write(&buf, self.timeout)
+    }
+}
+
+fn encode_params(params: &[u32]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(params.len() * 4);
+    for &p in params {
+        payload.write_u32::<LittleEndian>(p).ok();
+    }
+    payload
+}
+
+impl<T: Transport> ResponderLink for ContainerLink<T> {
+    fn read_request(&mut self) -> Result<Option<Request>, Error> {
+        loop {
+            let (info, payload) = match self.read_container(true)? {
+                Some(container) => container,
+                None => return Ok(None),
+            };
+            match info.kind {
+                ContainerType::Command => {
+                    return Ok(Some(Request {
+                        code: info.code,
+                        tid: info.tid,
+                        params: payload
+                            .chunks_exact(4)
+                            .map(|p| u32::from_le_bytes([p[0], p[1], p[2], p[3]]))
+                            .collect(),
+                        has_data: None,
+                    }))
+                }
+                // data of a command the handler didn't expect data for
+                ContainerType::Data => {
+                    warn!("Dropping unexpected data of transaction {}", info.tid)
+                }
+                kind => {
+                    return Err(Error::Malformed(format!(
+                        "Unexpected {:?} container from the initiator",
+                        kind
+                    )))
+                }
+            }
+        }
+    }
+
+    fn read_data(&mut self, request: &Request) -> Result<Vec<u8>, Error> {
+        match self.read_container(false)? {
+            Some((info, payload))
+                if info.kind == ContainerType::Data && info.belongs_to(request.tid) =>
+            {
+                Ok(payload)
+            }
+            Some((info, _)) => Err(Error::Malformed(format!(
+                "Expected the data of transaction {}, got a {:?} container of transaction {}",
+                request.tid, info.kind, info.tid
+            ))),
+            None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
+
+    fn write_data(&mut self, request: &Request, data: &[u8]) -> Result<(), Error> {
+        self.write_container(ContainerType::Data, request.code, request.tid, data)
+    }
+
+    fn write_response(
+        &mut self,
+        request: &Request,
+        code: ResponseCode,
+        params: &[u32],
+    ) -> Result<(), Error> {
+        let payload = encode_params(params);
+        self.write_container(ContainerType::Response, code, request.tid, &payload)
+    }
+
+    fn write_event(&mut self, event: &PtpEvent) -> Result<(), Error> {
+        let events = match self.events {
+            Some(ref mut events) => events,
+            None => return Ok(()),
+        };
+        let mut buf = Vec::new();
+        ContainerInfo {
+            payload_len: event.params.len() * 4,
+            kind: ContainerType::Event,
+            code: event.code,
+            tid: event.tid,
+        }
+        .write(&mut buf);
+        buf.extend_from_slice(&encode_params(&event.params));
+        events.write(&buf, self.timeout)
+    }
+}
+
+/// Serves the operations of an initiator with a `Handler`, turning libptp into the
+/// device side of the protocol.
+///
+/// Each request is read from the link along with its data, if any, handed to the handler,
+/// and its reply sent back as a data phase and a response; the events the handler has
+/// queued are sent after each transaction.
+pub struct Responder<L: ResponderLink, H: Handler> {
+    link: L,
+    handler: H,
+}
+
+impl<L: ResponderLink, H: Handler> Responder<L, H> {
+    pub fn new(link: L, handler: H) -> Responder<L, H> {
+        Responder { link, handler }
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    pub fn link_mut(&mut self) -> &mut L {
+        &mut self.link
+    }
+
+    pub fn into_inner(self) -> (L, H) {
+        (self.link, self.handler)
+    }
+
+    /// Serve one transaction, returning false once the initiator has gone away.
+    pub fn serve_one(&mut self) -> Result<bool, Error> {
+        let request = match self.link.read_request()? {
+            Some(request) => request,
+            None => return Ok(false),
+        };
+        let has_data = request
+            .has_data
+            .unwrap_or_else(|| self.handler.expects_data(request.code));
+        let data = if has_data {
+            Some(self.link.read_data(&request)?)
+        } else {
+            None
+        };
+
+        let reply = self
+            .handler
+            .operation(request.code, &request.params, data.as_deref());
+        debug!(
+            "Operation 0x{:04x} (tid {}): response 0x{:04x}",
+            request.code, request.tid, reply.code
+        );
+        if let Some(ref data) = reply.data {
+            self.link.write_data(&request, data)?;
+        }
+        self.link
+            .write_response(&request, reply.code, &reply.params)?;
+        self.send_events()?;
+        Ok(true)
+    }
+
+    /// Serve transactions until the initiator goes away.
+    pub fn serve(&mut self) -> Result<(), Error> {
+        while self.serve_one()? {}
+        Ok(())
+    }
+
+    /// Send the events queued by the handler.
+    pub fn send_events(&mut self) -> Result<(), Error> {
+        while let Some(event) = self.handler.poll_event() {
+            self.link.write_event(&event)?;
+        }
+        Ok(())
+    }
+}
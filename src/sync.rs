@@ -0,0 +1,306 @@
+use super::{Camera, Error, ObjectInfo, Transport};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const STATE_HEADER: &str = "libptp-sync 1";
+
+/// What a `StoreSync` run did.
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    /// Files written by this run, new objects as well as changed ones.
+    pub downloaded: Vec<PathBuf>,
+    /// Objects already up to date locally.
+    pub unchanged: usize,
+    /// Objects that failed with an error response or have an unusable name, by their
+    /// path on the store.
+    pub failed: Vec<(String, Error)>,
+    /// Number of bytes downloaded.
+    pub bytes: u64,
+}
+
+// what was downloaded for an object, as persisted in the state file
+#[derive(Debug, Clone, PartialEq)]
+struct SyncedObject {
+    handle: u32,
+    size: u32,
+    capture_date: String,
+    modification_date: String,
+}
+
+impl SyncedObject {
+    fn new(handle: u32, info: &ObjectInfo) -> SyncedObject {
+        SyncedObject {
+            handle,
+            size: info.ObjectCompressedSize,
+            capture_date: state_field(&info.CaptureDate),
+            modification_date: state_field(&info.ModificationDate),
+        }
+    }
+
+    // handles aren't stable across sessions, so they aren't compared
+    fn same_object(&self, other: &SyncedObject) -> bool {
+        self.size == other.size
+            && self.capture_date == other.capture_date
+            && self.modification_date == other.modification_date
+    }
+}
+
+// the names come from the device, don't let them point outside `dest` or break the lines
+// of the state file
+fn is_safe_path(path: &str) -> bool {
+    path.split('/')
+        .all(|c| !c.is_empty() && c != "." && c != ".." && !c.contains(['\\', '\n', '\r', '\0']))
+}
+
+// a date from the device as stored in a tab separated line of the state file
+fn state_field(s: &str) -> String {
+    s.replace(|c: char| c.is_control(), " ")
+}
+
+/// Mirrors a store of the camera into a local directory, downloading only the objects
+/// that are new or changed since the last run.
+///
+/// The objects downloaded are recorded in a state file, `.libptp-sync` in the
+/// destination directory by default, by their path on the store along with their size
+/// and dates. An object is downloaded again if any of those changed or if its local
+/// file is gone. Objects deleted from the camera are left alone locally.
+#[derive(Debug, Clone)]
+pub struct StoreSync {
+    storage_id: u32,
+    dest: PathBuf,
+    state_file: PathBuf,
+    format: Option<u16>,
+    timeout: Option<Duration>,
+}
+
+impl StoreSync {
+    pub fn new<P: AsRef<Path>>(storage_id: u32, dest: P) -> StoreSync {
+        let dest = dest.as_ref().to_path_buf();
+        StoreSync {
+            storage_id,
+            state_file: dest.join(".libptp-sync"),
+            dest,
+            format: None,
+            timeout: None,
+        }
+    }
+
+    /// Keep the state somewhere else than in the destination directory.
+    pub fn with_state_file<P: AsRef<Path>>(mut self, path: P) -> StoreSync {
+        self.state_file = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Only mirror the objects of format `format`.
+    pub fn with_format(mut self, format: u16) -> StoreSync {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> StoreSync {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Run the sync, calling `on_file(path, done, total)` as each object downloads.
+    ///
+    /// An object failing with an error response is recorded in the summary and the run
+    /// goes on. Any other error ends the run; the state of the objects downloaded until
+    /// then is saved either way.
+    pub fn run<T: Transport, F: FnMut(&str, u64, u64)>(
+        &self,
+        camera: &mut Camera<T>,
+        mut on_file: F,
+    ) -> Result<SyncSummary, Error> {
+        let mut state = self.load_state()?;
+        let mut summary = SyncSummary::default();
+
+        let mut objects = vec![];
+        let tree = camera.object_tree(
+            self.storage_id,
+            None,
+            None,
+            self.format,
+            |_| {},
+            self.timeout,
+        )?;
+        for node in tree {
+            objects.extend(node.walk());
+        }
+
+        let mut result = Ok(());
+        for (path, node) in objects {
+            if node.info.is_folder() {
+                continue;
+            }
+            if !is_safe_path(&path) {
+                let e = Error::Malformed(format!("Unsafe object path {:?}", path));
+                summary.failed.push((path, e));
+                continue;
+            }
+            let synced = SyncedObject::new(node.handle, &node.info);
+            let local = self.dest.join(&path);
+            if local.exists() && state.get(&path).is_some_and(|s| s.same_object(&synced)) {
+                summary.unchanged += 1;
+                continue;
+            }
+
+            match self.download(camera, node.handle, &path, &local, &mut on_file) {
+                Ok(bytes) => {
+                    summary.bytes += bytes;
+                    summary.downloaded.push(local);
+                    state.insert(path, synced);
+                }
+                Err(e @ Error::Response(_)) => summary.failed.push((path, e)),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        self.save_state(&state)?;
+        result.map(|_| summary)
+    }
+
+    // download into a temporary file first, so an interrupted run leaves no truncated file
+    fn download<T: Transport, F: FnMut(&str, u64, u64)>(
+        &self,
+        camera: &mut Camera<T>,
+        handle: u32,
+        path: &str,
+        local: &Path,
+        on_file: &mut F,
+    ) -> Result<u64, Error> {
+        if let Some(parent) = local.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut partial = local.as_os_str().to_owned();
+        partial.push(".part");
+
+        let bytes = camera.get_object_to_file(
+            handle,
+            &partial,
+            |done, total| on_file(path, done, total),
+            self.timeout,
+        )?;
+        fs::rename(&partial, local)?;
+        Ok(bytes)
+    }
+
+    fn load_state(&self) -> Result<HashMap<String, SyncedObject>, Error> {
+        let file = match File::open(&self.state_file) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut lines = BufReader::new(file).lines();
+        match lines.next().transpose()? {
+            Some(ref header) if header == STATE_HEADER => {}
+            None => return Ok(HashMap::new()),
+            Some(header) => {
+                return Err(Error::Malformed(format!(
+                    "Unknown sync state file header {:?}",
+                    header
+                )))
+            }
+        }
+
+        let mut state = HashMap::new();
+        for line in lines {
+            let line = line?;
+            // handle, size, capture date, modification date and the path, which goes last
+            // as it is the only field that may contain tabs
+            let fields: Vec<&str> = line.splitn(5, '\t').collect();
+            match fields[..] {
+                [handle, size, capture_date, modification_date, path] => {
+                    let (handle, size) = match (handle.parse(), size.parse()) {
+                        (Ok(handle), Ok(size)) => (handle, size),
+                        _ => {
+                            warn!("sync: ignoring malformed state line {:?}", line);
+                            continue;
+                        }
+                    };
+                    state.insert(
+                        path.to_owned(),
+                        SyncedObject {
+                            handle,
+                            size,
+                            capture_date: capture_date.to_owned(),
+                            modification_date: modification_date.to_owned(),
+                        },
+                    );
+                }
+                _ => warn!("sync: ignoring malformed state line {:?}", line),
+            }
+        }
+        Ok(state)
+    }
+
+    fn save_state(&self, state: &HashMap<String, SyncedObject>) -> Result<(), Error> {
+        if let Some(parent) = self.state_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp = self.state_file.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut paths: Vec<&String> = state.keys().collect();
+        paths.sort();
+
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        writeln!(out, "{}", STATE_HEADER)?;
+        for path in paths {
+            let s = &state[path];
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}",
+                s.handle, s.size, s.capture_date, s.modification_date, path
+            )?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, &self.state_file)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeviceInfo, MockDevice, MockTransport, StandardObjectFormatCode, StorageInfo};
+
+    const STORAGE: u32 = 0x0001_0001;
+
+    #[test]
+    fn unsafe_names() {
+        let mut device = MockDevice::new(DeviceInfo::default());
+        device.add_storage(STORAGE, StorageInfo::default());
+        for name in &["..\\evil.jpg", "a\nb.jpg", "IMG_0001.JPG"] {
+            device.add_object(
+                ObjectInfo {
+                    StorageID: STORAGE,
+                    ObjectFormat: StandardObjectFormatCode::ExifJpeg,
+                    Filename: (*name).to_owned(),
+                    CaptureDate: "20240101T120000\n".to_owned(),
+                    ..Default::default()
+                },
+                vec![1, 2, 3],
+            );
+        }
+        let mut camera = MockTransport::open(device).unwrap();
+
+        let dest = std::env::temp_dir().join(format!("libptp-sync-{}", std::process::id()));
+        let sync = StoreSync::new(STORAGE, &dest);
+        let summary = sync.run(&mut camera, |_, _, _| {}).unwrap();
+        assert_eq!(summary.downloaded, vec![dest.join("IMG_0001.JPG")]);
+        assert_eq!(summary.failed.len(), 2);
+
+        // the date with a line break in it doesn't break the state file
+        let summary = sync.run(&mut camera, |_, _, _| {}).unwrap();
+        assert_eq!(summary.unchanged, 1);
+        fs::remove_dir_all(&dest).unwrap();
+    }
+}
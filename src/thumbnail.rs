@@ -0,0 +1,42 @@
+use super::{Camera, Error, StandardObjectFormatCode, Transport};
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use std::time::Duration;
+
+impl<T: Transport> Camera<T> {
+    /// Fetch and decode the thumbnail of an object.
+    ///
+    /// The decoded size has to match the ThumbPixWidth and ThumbPixHeight of the
+    /// ObjectInfo, when the device fills them in. Only JPEG thumbnails are decoded, which
+    /// is what cameras produce.
+    pub fn get_thumb_image(
+        &mut self,
+        handle: u32,
+        timeout: Option<Duration>,
+    ) -> Result<DynamicImage, Error> {
+        let info = self.get_objectinfo(handle, timeout)?;
+        let data = self.get_thumb(handle, timeout)?;
+
+        let image = match info.ThumbFormat {
+            StandardObjectFormatCode::ExifJpeg | StandardObjectFormatCode::Jfif => {
+                image::load_from_memory_with_format(&data, ImageFormat::Jpeg)
+            }
+            _ => image::load_from_memory(&data),
+        }
+        .map_err(|e| {
+            Error::Malformed(format!(
+                "Undecodable thumbnail of object 0x{:08x}: {}",
+                handle, e
+            ))
+        })?;
+
+        let (width, height) = image.dimensions();
+        let expected = (info.ThumbPixWidth, info.ThumbPixHeight);
+        if expected != (0, 0) && expected != (width, height) {
+            return Err(Error::Malformed(format!(
+                "Thumbnail of object 0x{:08x} is {}x{}, its ObjectInfo says {}x{}",
+                handle, width, height, expected.0, expected.1
+            )));
+        }
+        Ok(image)
+    }
+}
@@ -0,0 +1,697 @@
+use super::Transport;
+use crate::container::{ContainerInfo, ContainerType, CONTAINER_INFO_SIZE};
+use crate::{
+    Camera, CommandCode, DataType, DeviceInfo, Error, EventCode, Handler, MtpResponseCode,
+    ObjectInfo, PropInfo, PropertyCode, PtpEvent, Reply, ResponseCode, StandardCommandCode,
+    StandardEventCode, StandardObjectFormatCode, StandardResponseCode, StorageInfo,
+};
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use std::cmp::min;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Cursor;
+use std::time::Duration;
+
+/// A `Camera` talking to an in-memory `MockDevice`, for tests without hardware.
+pub type MockCamera = Camera<MockTransport>;
+
+/// An object held by a `MockDevice`.
+#[derive(Debug, Clone, Default)]
+pub struct MockObject {
+    pub info: ObjectInfo,
+    pub data: Vec<u8>,
+    pub thumb: Option<Vec<u8>>,
+}
+
+/// A synthesized camera: a DeviceInfo, stores, objects and device properties, answering
+/// the standard operations on them the way a device would.
+///
+/// Supported are the session, storage, object transfer (including SendObjectInfo,
+/// SendObject and GetPartialObject), DeleteObject and device property operations; the
+/// others fail with OperationNotSupported. Objects sent to the device are given the
+/// next free handle, and changes made through SetDevicePropValue queue a DevicePropChanged
+/// event. The ObjectInfos are served as given, so keep their StorageID and ParentObject
+/// consistent with the stores and folders.
+#[derive(Debug, Clone, Default)]
+pub struct MockDevice {
+    pub device_info: DeviceInfo,
+    pub storages: BTreeMap<u32, StorageInfo>,
+    pub objects: BTreeMap<u32, MockObject>,
+    pub properties: BTreeMap<PropertyCode, PropInfo>,
+    events: VecDeque<PtpEvent>,
+    session_open: bool,
+    // the object announced by SendObjectInfo, waiting for its SendObject
+    sent_info: Option<u32>,
+}
+
+impl MockDevice {
+    pub fn new(device_info: DeviceInfo) -> MockDevice {
+        MockDevice {
+            device_info,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_storage(&mut self, storage_id: u32, info: StorageInfo) {
+        self.storages.insert(storage_id, info);
+    }
+
+    /// Store an object under the next free handle, which is returned.
+    pub fn add_object(&mut self, info: ObjectInfo, data: Vec<u8>) -> u32 {
+        let handle = self.next_handle();
+        self.objects.insert(
+            handle,
+            MockObject {
+                info,
+                data,
+                thumb: None,
+            },
+        );
+        handle
+    }
+
+    pub fn add_property(&mut self, prop: PropInfo) {
+        self.properties.insert(prop.property_code, prop);
+    }
+
+    /// Queue an event for the event channel, e.g. ObjectAdded after adding an object.
+    pub fn queue_event(&mut self, code: EventCode, params: &[u32]) {
+        self.events.push_back(PtpEvent {
+            code,
+            params: params.to_vec(),
+            tid: 0,
+        });
+    }
+
+    pub fn is_session_open(&self) -> bool {
+        self.session_open
+    }
+
+    fn next_handle(&self) -> u32 {
+        self.objects.keys().next_back().map_or(1, |h| h + 1)
+    }
+
+    // objects matching the storage, format and parent parameters of GetObjectHandles
+    fn find_objects(&self, storage_id: u32, format: u32, parent: u32) -> Vec<u32> {
+        self.objects
+            .iter()
+            .filter(|(_, o)| storage_id == 0xFFFF_FFFF || o.info.StorageID == storage_id)
+            .filter(|(_, o)| format == 0 || o.info.ObjectFormat as u32 == format)
+            .filter(|(_, o)| match parent {
+                0 => true,
+                0xFFFF_FFFF => o.info.ParentObject == 0,
+                parent => o.info.ParentObject == parent,
+            })
+            .map(|(&h, _)| h)
+            .collect()
+    }
+
+    fn delete(&mut self, handle: u32) -> ResponseCode {
+        let object = match self.objects.get(&handle) {
+            Some(object) => object,
+            None => return StandardResponseCode::InvalidObjectHandle,
+        };
+        if object.info.ProtectionStatus != 0 {
+            return StandardResponseCode::ObjectWriteProtected;
+        }
+        let mut partial = false;
+        for child in self.find_objects(0xFFFF_FFFF, 0, handle) {
+            partial |= self.delete(child) != StandardResponseCode::Ok;
+        }
+        if partial {
+            return StandardResponseCode::PartialDeletion;
+        }
+        self.objects.remove(&handle);
+        if self.sent_info == Some(handle) {
+            self.sent_info = None;
+        }
+        StandardResponseCode::Ok
+    }
+}
+
+impl Handler for MockDevice {
+    fn operation(&mut self, code: CommandCode, params: &[u32], data: Option<&[u8]>) -> Reply {
+        let param = |i: usize| params.get(i).copied().unwrap_or(0);
+        let encoded = |result: Result<Vec<u8>, Error>| match result {
+            Ok(data) => Reply::data(data),
+            Err(_) => Reply::error(StandardResponseCode::GeneralError),
+        };
+
+        match code {
+            StandardCommandCode::GetDeviceInfo => return encoded(self.device_info.encode()),
+            StandardCommandCode::OpenSession if self.session_open => {
+                return Reply::error(StandardResponseCode::SessionAlreadyOpen)
+            }
+            StandardCommandCode::OpenSession if param(0) == 0 => {
+                return Reply::error(StandardResponseCode::InvalidParameter)
+            }
+            StandardCommandCode::OpenSession => {
+                self.session_open = true;
+                return Reply::ok();
+            }
+            _ if !self.session_open => return Reply::error(StandardResponseCode::SessionNotOpen),
+            _ => {}
+        }
+
+        match code {
+            StandardCommandCode::CloseSession => {
+                self.session_open = false;
+                Reply::ok()
+            }
+            StandardCommandCode::GetStorageIDs => {
+                let ids: Vec<u32> = self.storages.keys().copied().collect();
+                let mut out = vec![];
+                out.write_u32::<LittleEndian>(ids.len() as u32).ok();
+                for id in ids {
+                    out.write_u32::<LittleEndian>(id).ok();
+                }
+                Reply::data(out)
+            }
+            StandardCommandCode::GetStorageInfo => match self.storages.get(&param(0)) {
+                Some(info) => encoded(info.encode()),
+                None => Reply::error(StandardResponseCode::InvalidStorageId),
+            },
+            StandardCommandCode::GetObjectHandles | StandardCommandCode::GetNumObjects => {
+                if param(0) != 0xFFFF_FFFF && !self.storages.contains_key(&param(0)) {
+                    return Reply::error(StandardResponseCode::InvalidStorageId);
+                }
+                let handles = self.find_objects(param(0), param(1), param(2));
+                if code == StandardCommandCode::GetNumObjects {
+                    return Reply::ok().with_params(&[handles.len() as u32]);
+                }
+                let mut out = vec![];
+                out.write_u32::<LittleEndian>(handles.len() as u32).ok();
+                for handle in handles {
+                    out.write_u32::<LittleEndian>(handle).ok();
+                }
+                Reply::data(out)
+            }
+            StandardCommandCode::GetObjectInfo
+            | StandardCommandCode::GetObject
+            | StandardCommandCode::GetThumb
+            | StandardCommandCode::GetPartialObject => {
+                let object = match self.objects.get(&param(0)) {
+                    Some(object) => object,
+                    None => return Reply::error(StandardResponseCode::InvalidObjectHandle),
+                };
+                match code {
+                    StandardCommandCode::GetObjectInfo => encoded(object.info.encode()),
+                    StandardCommandCode::GetObject => Reply::data(object.data.clone()),
+                    StandardCommandCode::GetThumb => match &object.thumb {
+                        Some(thumb) => Reply::data(thumb.clone()),
+                        None => Reply::error(StandardResponseCode::NoThumbnailPresent),
+                    },
+                    _ => {
+                        let start = min(param(1) as usize, object.data.len());
+                        let end = min(start.saturating_add(param(2) as usize), object.data.len());
+                        let data = object.data[start..end].to_vec();
+                        let len = data.len() as u32;
+                        Reply::data(data).with_params(&[len])
+                    }
+                }
+            }
+            StandardCommandCode::DeleteObject => {
+                let handles = match param(0) {
+                    0xFFFF_FFFF => self.objects.keys().copied().collect(),
+                    handle => vec![handle],
+                };
+                let mut response = StandardResponseCode::Ok;
+                for handle in handles {
+                    // objects of a folder deleted earlier in the loop are gone already
+                    if param(0) == 0xFFFF_FFFF && !self.objects.contains_key(&handle) {
+                        continue;
+                    }
+                    match self.delete(handle) {
+                        StandardResponseCode::Ok => {}
+                        StandardResponseCode::ObjectWriteProtected if param(0) == 0xFFFF_FFFF => {
+                            response = StandardResponseCode::PartialDeletion
+                        }
+                        code => response = code,
+                    }
+                }
+                Reply::error(response)
+            }
+            StandardCommandCode::SendObjectInfo => {
+                let mut info = match data.map(ObjectInfo::decode) {
+                    Some(Ok(info)) => info,
+                    _ => return Reply::error(MtpResponseCode::InvalidDataset),
+                };
+                let storage_id = match param(0) {
+                    0 => self.storages.keys().next().copied().unwrap_or(0),
+                    id => id,
+                };
+                if !self.storages.contains_key(&storage_id) {
+                    return Reply::error(StandardResponseCode::InvalidStorageId);
+                }
+                let parent = match param(1) {
+                    0xFFFF_FFFF => 0,
+                    parent => parent,
+                };
+                if parent != 0 && !self.objects.contains_key(&parent) {
+                    return Reply::error(StandardResponseCode::InvalidParentObject);
+                }
+                info.StorageID = storage_id;
+                info.ParentObject = parent;
+                let is_folder = StandardObjectFormatCode::is_association(info.ObjectFormat);
+                let handle = self.add_object(info, vec![]);
+                // folders have no data phase to wait for
+                self.sent_info = if is_folder { None } else { Some(handle) };
+                Reply::ok().with_params(&[storage_id, param(1), handle])
+            }
+            StandardCommandCode::SendObject => {
+                match self.sent_info.take().and_then(|h| self.objects.get_mut(&h)) {
+                    Some(object) => {
+                        object.data = data.unwrap_or_default().to_vec();
+                        object.info.ObjectCompressedSize = object.data.len() as u32;
+                        Reply::ok()
+                    }
+                    None => Reply::error(StandardResponseCode::NoValidObjectInfo),
+                }
+            }
+            StandardCommandCode::GetDevicePropDesc
+            | StandardCommandCode::GetDevicePropValue
+            | StandardCommandCode::SetDevicePropValue
+            | StandardCommandCode::ResetDevicePropValue => {
+                let prop_code = param(0) as PropertyCode;
+                let prop = match self.properties.get_mut(&prop_code) {
+                    Some(prop) => prop,
+                    None => return Reply::error(StandardResponseCode::DevicePropNotSupported),
+                };
+                match code {
+                    StandardCommandCode::GetDevicePropDesc => encoded(prop.encode()),
+                    StandardCommandCode::GetDevicePropValue => encoded(prop.current.encode()),
+                    _ if prop.get_set == 0 => Reply::error(StandardResponseCode::AccessDenied),
+                    StandardCommandCode::SetDevicePropValue => {
+                        let mut cur = Cursor::new(data.unwrap_or_default());
+                        match DataType::read_type(prop.data_type, &mut cur) {
+                            Ok(value) => prop.current = value,
+                            Err(_) => {
+                                return Reply::error(StandardResponseCode::InvalidDevicePropFormat)
+                            }
+                        }
+                        self.queue_event(StandardEventCode::DevicePropChanged, &[param(0)]);
+                        Reply::ok()
+                    }
+                    _ => {
+                        prop.current = prop.factory_default.clone();
+                        self.queue_event(StandardEventCode::DevicePropChanged, &[param(0)]);
+                        Reply::ok()
+                    }
+                }
+            }
+            _ => Reply::error(StandardResponseCode::OperationNotSupported),
+        }
+    }
+
+    fn poll_event(&mut self) -> Option<PtpEvent> {
+        self.events.pop_front()
+    }
+}
+
+/// Transport serving a `MockDevice` through the regular container framing, with the
+/// transfer semantics of USB bulk pipes. Timeouts are ignored, an empty event channel
+/// times out immediately.
+pub struct MockTransport {
+    device: MockDevice,
+    // bytes written by the initiator, not yet forming a complete container
+    written: Vec<u8>,
+    // the command waiting for a possible data phase, run on the next read
+    command: Option<(ContainerInfo, Vec<u32>, Option<Vec<u8>>)>,
+    // containers for the initiator, the first one partly read up to `read_pos`
+    replies: VecDeque<Vec<u8>>,
+    read_pos: usize,
+    zlp_pending: bool,
+}
+
+impl MockTransport {
+    pub fn new(device: MockDevice) -> MockTransport {
+        MockTransport {
+            device,
+            written: vec![],
+            command: None,
+            replies: VecDeque::new(),
+            read_pos: 0,
+            zlp_pending: false,
+        }
+    }
+
+    /// A camera with an open session on `device`.
+    pub fn open(device: MockDevice) -> Result<MockCamera, Error> {
+        let mut camera = Camera::with_transport(MockTransport::new(device));
+        camera.open_session(None)?;
+        Ok(camera)
+    }
+
+    pub fn device(&self) -> &MockDevice {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut MockDevice {
+        &mut self.device
+    }
+
+    fn container(kind: ContainerType, code: u16, tid: u32, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(CONTAINER_INFO_SIZE + payload.len());
+        ContainerInfo {
+            payload_len: payload.len(),
+            kind,
+            code,
+            tid,
+        }
+        .write(&mut buf);
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    fn run_command(&mut self) {
+        let (info, params, data) = match self.command.take() {
+            Some(command) => command,
+            None => return,
+        };
+        let reply = self.device.operation(info.code, &params, data.as_deref());
+        if let Some(data) = reply.data {
+            self.replies.push_back(Self::container(
+                ContainerType::Data,
+                info.code,
+                info.tid,
+                &data,
+            ));
+        }
+        let mut payload = vec![];
+        for p in reply.params {
+            payload.write_u32::<LittleEndian>(p).ok();
+        }
+        self.replies.push_back(Self::container(
+            ContainerType::Response,
+            reply.code,
+            info.tid,
+            &payload,
+        ));
+    }
+}
+
+impl Transport for MockTransport {
+    fn write(&mut self, buf: &[u8], _timeout: Duration) -> Result<(), Error> {
+        self.written.extend_from_slice(buf);
+        while self.written.len() >= CONTAINER_INFO_SIZE {
+            let len = LittleEndian::read_u32(&self.written) as usize;
+            if self.written.len() < len {
+                break;
+            }
+            let rest = self.written.split_off(len);
+            let container = std::mem::replace(&mut self.written, rest);
+            let info = ContainerInfo::parse(&container[..])?;
+            let payload = container[CONTAINER_INFO_SIZE..].to_vec();
+
+            match info.kind {
+                ContainerType::Command => {
+                    self.run_command();
+                    let params = payload.chunks(4).map(LittleEndian::read_u32).collect();
+                    self.command = Some((info, params, None));
+                }
+                ContainerType::Data => match self.command {
+                    Some((ref command, _, ref mut data)) if command.tid == info.tid => {
+                        *data = Some(payload)
+                    }
+                    _ => {
+                        return Err(Error::Malformed(format!(
+                            "Data container for transaction {} without its command",
+                            info.tid
+                        )))
+                    }
+                },
+                kind => {
+                    return Err(Error::Malformed(format!(
+                        "Unexpected {:?} container from the initiator",
+                        kind
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize, Error> {
+        if self.zlp_pending {
+            self.zlp_pending = false;
+            return Ok(0);
+        }
+        self.run_command();
+
+        let container = match self.replies.front() {
+            Some(container) => container,
+            None => return Err(rusb::Error::Timeout.into()),
+        };
+        let n = min(buf.len(), container.len() - self.read_pos);
+        buf[..n].copy_from_slice(&container[self.read_pos..self.read_pos + n]);
+        self.read_pos += n;
+        if self.read_pos == container.len() {
+            self.replies.pop_front();
+            self.read_pos = 0;
+            self.zlp_pending = n == buf.len();
+        }
+        Ok(n)
+    }
+
+    fn read_event(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize, Error> {
+        let event = self.device.poll_event().ok_or(rusb::Error::Timeout)?;
+        let mut payload = vec![];
+        for p in &event.params {
+            payload.write_u32::<LittleEndian>(*p).ok();
+        }
+        let container = Self::container(ContainerType::Event, event.code, event.tid, &payload);
+        let n = min(buf.len(), container.len());
+        buf[..n].copy_from_slice(&container[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, Quirks};
+
+    const STORAGE: u32 = 0x0001_0001;
+
+    fn device() -> MockDevice {
+        let mut device = MockDevice::new(DeviceInfo {
+            Manufacturer: "libptp".to_owned(),
+            Model: "Mock".to_owned(),
+            ..Default::default()
+        });
+        device.add_storage(
+            STORAGE,
+            StorageInfo {
+                VolumeLabel: "Card".to_owned(),
+                ..Default::default()
+            },
+        );
+        device
+    }
+
+    fn image(name: &str) -> ObjectInfo {
+        ObjectInfo {
+            StorageID: STORAGE,
+            ObjectFormat: StandardObjectFormatCode::ExifJpeg,
+            Filename: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn session() {
+        let mut camera = Camera::with_transport(MockTransport::new(device()));
+        assert_eq!(camera.get_device_info(None).unwrap().Model, "Mock");
+        assert!(matches!(
+            camera.get_storageids(None),
+            Err(Error::Response(StandardResponseCode::SessionNotOpen))
+        ));
+
+        camera.open_session(None).unwrap();
+        assert!(camera.transport().device().is_session_open());
+        assert_eq!(camera.get_storageids(None).unwrap(), vec![STORAGE]);
+        camera.close_session(None).unwrap();
+        assert!(!camera.transport().device().is_session_open());
+    }
+
+    #[test]
+    fn get_objects() {
+        let mut device = device();
+        let data: Vec<u8> = (0..2000u32).map(|i| i as u8).collect();
+        let first = device.add_object(image("A.JPG"), data.clone());
+        let second = device.add_object(image("B.JPG"), vec![1, 2, 3]);
+        let mut camera = MockTransport::open(device).unwrap();
+
+        assert_eq!(
+            camera
+                .get_objecthandles(STORAGE, 0xFFFF_FFFF, None, None)
+                .unwrap(),
+            vec![first, second]
+        );
+        assert_eq!(
+            camera.get_objectinfo(second, None).unwrap().Filename,
+            "B.JPG"
+        );
+        assert_eq!(camera.get_object(first, None).unwrap(), data);
+        assert_eq!(
+            camera.get_partialobject(first, 100, 50, None).unwrap(),
+            &data[100..150]
+        );
+        // a window past the end is cut short
+        assert_eq!(
+            camera.get_partialobject(first, 1990, 50, None).unwrap(),
+            &data[1990..]
+        );
+        assert!(matches!(
+            camera.get_object(42, None),
+            Err(Error::Response(StandardResponseCode::InvalidObjectHandle))
+        ));
+    }
+
+    #[test]
+    fn data_phase_over_the_limit() {
+        let mut device = device();
+        let handle = device.add_object(image("A.JPG"), vec![0; 2000]);
+        let mut camera = crate::CameraBuilder::new()
+            .max_data_len(1000)
+            .build(MockTransport::new(device));
+        camera.open_session(None).unwrap();
+        assert!(matches!(
+            camera.get_object(handle, None),
+            Err(Error::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn send_and_delete() {
+        let mut camera = MockTransport::open(device()).unwrap();
+        let handle = camera
+            .send_object_info(0xFFFF_FFFF, &image("NEW.JPG"), None)
+            .unwrap();
+        camera.send_object(&[9; 700], None).unwrap();
+
+        let info = camera.get_objectinfo(handle, None).unwrap();
+        assert_eq!(info.Filename, "NEW.JPG");
+        assert_eq!(info.ObjectCompressedSize, 700);
+        assert_eq!(camera.get_object(handle, None).unwrap(), vec![9; 700]);
+
+        camera.delete_object(handle, None).unwrap();
+        assert!(camera
+            .get_objecthandles(STORAGE, 0xFFFF_FFFF, None, None)
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            camera.delete_object(handle, None),
+            Err(Error::Response(StandardResponseCode::InvalidObjectHandle))
+        ));
+    }
+
+    #[test]
+    fn send_object_after_deleting_its_info() {
+        let mut camera = MockTransport::open(device()).unwrap();
+        let handle = camera
+            .send_object_info(0xFFFF_FFFF, &image("NEW.JPG"), None)
+            .unwrap();
+        camera.delete_object(handle, None).unwrap();
+        assert!(matches!(
+            camera.send_object(&[1, 2, 3], None),
+            Err(Error::Response(StandardResponseCode::NoValidObjectInfo))
+        ));
+    }
+
+    #[test]
+    fn delete_protected() {
+        let mut device = device();
+        let handle = device.add_object(
+            ObjectInfo {
+                ProtectionStatus: 0x0001,
+                ..image("KEEP.JPG")
+            },
+            vec![0; 10],
+        );
+        let mut camera = MockTransport::open(device).unwrap();
+        assert!(matches!(
+            camera.delete_object(handle, None),
+            Err(Error::WriteProtected)
+        ));
+        assert!(camera.transport().device().objects.contains_key(&handle));
+    }
+
+    #[test]
+    fn download_with_zero_window() {
+        let mut device = device();
+        let handle = device.add_object(image("IMG_0001.JPG"), vec![7; 5]);
+        let mut camera = MockTransport::open(device).unwrap();
+        let mut download = crate::ResumableDownload::new(handle, Some(5)).with_window(0);
+        let mut data = vec![];
+        download.run(&mut camera, &mut data, None).unwrap();
+        assert_eq!(data, [7; 5]);
+    }
+
+    #[test]
+    fn object_cache_after_move() {
+        let mut device = device();
+        let folder = device.add_object(
+            ObjectInfo {
+                StorageID: STORAGE,
+                ObjectFormat: StandardObjectFormatCode::Association,
+                Filename: "DCIM".to_owned(),
+                ..Default::default()
+            },
+            vec![],
+        );
+        let handle = device.add_object(image("IMG_0001.JPG"), vec![]);
+        let mut camera = MockTransport::open(device).unwrap();
+        let mut cache = crate::ObjectCache::new();
+        assert!(cache
+            .object_handles(&mut camera, STORAGE, folder, None)
+            .unwrap()
+            .is_empty());
+
+        let objects = &mut camera.transport_mut().device_mut().objects;
+        objects.get_mut(&handle).unwrap().info.ParentObject = folder;
+        cache.handle_event(&PtpEvent {
+            code: StandardEventCode::ObjectInfoChanged,
+            params: vec![handle],
+            tid: 0,
+        });
+        let handles = cache.object_handles(&mut camera, STORAGE, folder, None);
+        assert_eq!(handles.unwrap(), [handle]);
+        let handles = cache.object_handles(&mut camera, STORAGE, 0xFFFF_FFFF, None);
+        assert_eq!(handles.unwrap(), [folder]);
+    }
+
+    // a device sending the response of an operation ahead of its data phase
+    struct ResponseFirst(MockTransport);
+
+    impl Transport for ResponseFirst {
+        fn write(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Error> {
+            self.0.write(buf, timeout)
+        }
+
+        fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, Error> {
+            self.0.run_command();
+            let data_first = self
+                .0
+                .replies
+                .front()
+                .is_some_and(|c| LittleEndian::read_u16(&c[4..]) == ContainerType::Data as u16);
+            if self.0.read_pos == 0 && data_first && self.0.replies.len() > 1 {
+                self.0.replies.swap(0, 1);
+            }
+            self.0.read(buf, timeout)
+        }
+    }
+
+    #[test]
+    fn response_before_data() {
+        let mut camera = Camera::with_transport(ResponseFirst(MockTransport::new(device())));
+        camera.set_quirks(Quirks {
+            response_before_data: true,
+            ..Default::default()
+        });
+        camera.open_session(None).unwrap();
+        assert_eq!(camera.get_device_info(None).unwrap().Model, "Mock");
+        // operations without a data phase still end with their response
+        camera.close_session(None).unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+use super::Error;
+use std::future::Future;
+use std::time::Duration;
+
+mod mock;
+mod record;
+mod stream;
+mod usb;
+
+pub use self::mock::{MockCamera, MockDevice, MockObject, MockTransport};
+pub use self::record::{
+    read_recording, RecordingTransport, ReplayTransport, Transfer, TransferDirection,
+};
+pub use self::stream::StreamTransport;
+pub use self::usb::{DeviceStatus, UsbTransport};
+
+/// A byte pipe carrying PTP containers between the initiator and the responder.
+///
+/// Implementations follow USB bulk semantics: a `read` returns the data of a
+/// single transfer and never crosses a container boundary, and a transfer that
+/// exactly fills the supplied buffer at the end of a container is followed by
+/// a zero-length read.
+pub trait Transport {
+    /// Send a chunk of an outgoing container.
+    fn write(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Error>;
+
+    /// Receive the next chunk of an incoming container into `buf`, returning
+    /// the number of bytes read.
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, Error>;
+
+    /// Receive one Event container from the event channel (the interrupt endpoint on USB).
+    /// Transports without an event channel return `NotSupported`.
+    fn read_event(&mut self, _buf: &mut [u8], _timeout: Duration) -> Result<usize, Error> {
+        Err(rusb::Error::NotSupported.into())
+    }
+
+    /// Ask the responder to abort transaction `tid` and discard whatever it already queued.
+    /// Transports without out-of-band signalling return `NotSupported`.
+    fn cancel(&mut self, _tid: u32, _timeout: Duration) -> Result<(), Error> {
+        Err(rusb::Error::NotSupported.into())
+    }
+
+    /// Bring the pipes back to a usable state after a transfer failed, e.g. clear stalled
+    /// endpoints. Transports that can't recover return `NotSupported`.
+    fn recover(&mut self, _timeout: Duration) -> Result<(), Error> {
+        Err(rusb::Error::NotSupported.into())
+    }
+
+    /// Release any resources held on the device side (e.g. a claimed interface).
+    fn release(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Asynchronous counterpart of [`Transport`], used by [`AsyncCamera`](crate::AsyncCamera).
+///
+/// This is the only point where an async runtime plugs into the library: implement it
+/// on top of the runtime's own IO types (tokio, smol, async-std, ...) and the rest of
+/// the protocol stays runtime-agnostic. Timeouts are left to the runtime as well.
+/// The same bulk semantics as for [`Transport`] apply.
+///
+/// The methods return `impl Future` (or are implemented as `async fn`), which needs
+/// Rust 1.75.
+pub trait AsyncTransport {
+    /// Send a chunk of an outgoing container.
+    fn write<'a>(&'a mut self, buf: &'a [u8]) -> impl Future<Output = Result<(), Error>> + 'a;
+
+    /// Receive the next chunk of an incoming container into `buf`, returning
+    /// the number of bytes read.
+    fn read<'a>(&'a mut self, buf: &'a mut [u8])
+        -> impl Future<Output = Result<usize, Error>> + 'a;
+}
@@ -0,0 +1,283 @@
+use super::Transport;
+use crate::Error;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 8] = b"PTPREC\x00\x01";
+
+/// Direction of a recorded transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TransferDirection {
+    /// Sent to the device.
+    Out = 0,
+    /// Received from the device on the bulk pipe.
+    In = 1,
+    /// Received from the device on the event channel.
+    Event = 2,
+}
+
+impl TransferDirection {
+    fn from_u8(v: u8) -> Option<TransferDirection> {
+        use self::TransferDirection::*;
+        match v {
+            0 => Some(Out),
+            1 => Some(In),
+            2 => Some(Event),
+            _ => None,
+        }
+    }
+}
+
+/// One transfer of a recording, see `RecordingTransport`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    pub direction: TransferDirection,
+    /// Time since the recording started.
+    pub time: Duration,
+    pub data: Vec<u8>,
+}
+
+/// Read the transfers of a recording made by `RecordingTransport`, e.g. to dump them.
+pub fn read_recording<R: io::Read>(mut r: R) -> Result<Vec<Transfer>, Error> {
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(Error::Malformed("Not a libptp recording".to_string()));
+    }
+
+    let mut transfers = vec![];
+    loop {
+        let direction = match r.read_u8() {
+            Ok(v) => TransferDirection::from_u8(v).ok_or_else(|| {
+                Error::Malformed(format!("Invalid transfer direction {} in recording", v))
+            })?,
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(transfers),
+            Err(e) => return Err(e.into()),
+        };
+        let time = Duration::from_micros(r.read_u64::<LittleEndian>()?);
+        let len = r.read_u32::<LittleEndian>()? as usize;
+        let mut data = vec![0; len];
+        r.read_exact(&mut data)?;
+        transfers.push(Transfer {
+            direction,
+            time,
+            data,
+        });
+    }
+}
+
+/// Transport recording every transfer of another one, with its direction and time, for
+/// `ReplayTransport` to play back later.
+///
+/// Only successful transfers are recorded. Cancel and recover requests are passed on
+/// without being recorded.
+pub struct RecordingTransport<T: Transport, W: io::Write> {
+    inner: T,
+    writer: W,
+    start: Instant,
+}
+
+impl<T: Transport, W: io::Write> RecordingTransport<T, W> {
+    pub fn new(inner: T, mut writer: W) -> Result<RecordingTransport<T, W>, Error> {
+        writer.write_all(MAGIC)?;
+        Ok(RecordingTransport {
+            inner,
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    pub fn into_inner(self) -> (T, W) {
+        (self.inner, self.writer)
+    }
+
+    fn record(&mut self, direction: TransferDirection, data: &[u8]) -> Result<(), Error> {
+        self.writer.write_u8(direction as u8)?;
+        self.writer
+            .write_u64::<LittleEndian>(self.start.elapsed().as_micros() as u64)?;
+        self.writer.write_u32::<LittleEndian>(data.len() as u32)?;
+        self.writer.write_all(data)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<T: Transport> RecordingTransport<T, File> {
+    /// Record into a new file at `path`.
+    pub fn create<P: AsRef<Path>>(inner: T, path: P) -> Result<RecordingTransport<T, File>, Error> {
+        RecordingTransport::new(inner, File::create(path)?)
+    }
+}
+
+impl<T: Transport, W: io::Write> Transport for RecordingTransport<T, W> {
+    fn write(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Error> {
+        self.inner.write(buf, timeout)?;
+        self.record(TransferDirection::Out, buf)
+    }
+
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, Error> {
+        let n = self.inner.read(buf, timeout)?;
+        self.record(TransferDirection::In, &buf[..n])?;
+        Ok(n)
+    }
+
+    fn read_event(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, Error> {
+        let n = self.inner.read_event(buf, timeout)?;
+        self.record(TransferDirection::Event, &buf[..n])?;
+        Ok(n)
+    }
+
+    fn cancel(&mut self, tid: u32, timeout: Duration) -> Result<(), Error> {
+        self.inner.cancel(tid, timeout)
+    }
+
+    fn recover(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.inner.recover(timeout)
+    }
+
+    fn release(&mut self) -> Result<(), Error> {
+        self.inner.release()
+    }
+}
+
+/// Transport playing back a recording, so a session captured on someone else's camera
+/// goes through the parsing stack again.
+///
+/// Reads return the recorded incoming transfers in order and fail with `Timeout` once
+/// they run out; the event channel times out when no recorded event is left. Writes are
+/// compared with the recorded outgoing transfers, and differences only logged, as the
+/// replaying code may legitimately differ from the one recorded.
+pub struct ReplayTransport {
+    outgoing: VecDeque<Vec<u8>>,
+    incoming: VecDeque<Vec<u8>>,
+    events: VecDeque<Vec<u8>>,
+}
+
+impl ReplayTransport {
+    pub fn new(transfers: Vec<Transfer>) -> ReplayTransport {
+        let mut replay = ReplayTransport {
+            outgoing: VecDeque::new(),
+            incoming: VecDeque::new(),
+            events: VecDeque::new(),
+        };
+        for transfer in transfers {
+            match transfer.direction {
+                TransferDirection::Out => replay.outgoing.push_back(transfer.data),
+                TransferDirection::In => replay.incoming.push_back(transfer.data),
+                TransferDirection::Event => replay.events.push_back(transfer.data),
+            }
+        }
+        replay
+    }
+
+    /// Play back the recording in the file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ReplayTransport, Error> {
+        let file = BufReader::new(File::open(path)?);
+        Ok(ReplayTransport::new(read_recording(file)?))
+    }
+
+    /// Whether all recorded incoming transfers have been read.
+    pub fn is_finished(&self) -> bool {
+        self.incoming.is_empty()
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn write(&mut self, buf: &[u8], _timeout: Duration) -> Result<(), Error> {
+        match self.outgoing.pop_front() {
+            Some(ref recorded) if recorded[..] == *buf => {}
+            Some(recorded) => warn!(
+                "Replay: wrote {} bytes starting {:02x?}, the recording has {} bytes starting {:02x?}",
+                buf.len(),
+                &buf[..min(buf.len(), 16)],
+                recorded.len(),
+                &recorded[..min(recorded.len(), 16)]
+            ),
+            None => warn!("Replay: wrote {} bytes past the recording", buf.len()),
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize, Error> {
+        let transfer = self.incoming.front_mut().ok_or(rusb::Error::Timeout)?;
+        let n = min(buf.len(), transfer.len());
+        buf[..n].copy_from_slice(&transfer[..n]);
+        // a smaller buffer than when recording gets the rest on the next read
+        if n == transfer.len() {
+            self.incoming.pop_front();
+        } else {
+            transfer.drain(..n);
+        }
+        Ok(n)
+    }
+
+    fn read_event(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize, Error> {
+        let event = self.events.pop_front().ok_or(rusb::Error::Timeout)?;
+        let n = min(buf.len(), event.len());
+        buf[..n].copy_from_slice(&event[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Camera, DeviceInfo, MockDevice, MockTransport, ObjectInfo, StorageInfo};
+
+    const STORAGE: u32 = 0x0001_0001;
+
+    fn device() -> MockDevice {
+        let mut device = MockDevice::new(DeviceInfo {
+            Manufacturer: "libptp".to_owned(),
+            Model: "Mock".to_owned(),
+            ..Default::default()
+        });
+        device.add_storage(STORAGE, StorageInfo::default());
+        device.add_object(
+            ObjectInfo {
+                StorageID: STORAGE,
+                Filename: "IMG_0001.JPG".to_owned(),
+                ..Default::default()
+            },
+            vec![0xFF, 0xD8, 0xFF, 0xD9],
+        );
+        device
+    }
+
+    // the device info, handles and first object of a session
+    fn session<T: Transport>(camera: &mut Camera<T>) -> (String, Vec<u32>, Vec<u8>) {
+        camera.open_session(None).unwrap();
+        let model = camera.get_device_info(None).unwrap().Model;
+        let handles = camera
+            .get_objecthandles(STORAGE, 0xFFFF_FFFF, None, None)
+            .unwrap();
+        let data = camera.get_object(handles[0], None).unwrap();
+        camera.close_session(None).unwrap();
+        (model, handles, data)
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut recording = vec![];
+        let recorded = {
+            let transport = RecordingTransport::new(MockTransport::new(device()), &mut recording);
+            session(&mut Camera::with_transport(transport.unwrap()))
+        };
+        assert_eq!(recorded.0, "Mock");
+        assert_eq!(recorded.2, [0xFF, 0xD8, 0xFF, 0xD9]);
+
+        let transfers = read_recording(&recording[..]).unwrap();
+        assert!(transfers
+            .iter()
+            .any(|t| t.direction == TransferDirection::Out));
+        let mut camera = Camera::with_transport(ReplayTransport::new(transfers));
+        assert_eq!(session(&mut camera), recorded);
+        assert!(camera.transport().is_finished());
+    }
+}
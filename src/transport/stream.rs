@@ -0,0 +1,80 @@
+use super::Transport;
+use crate::Error;
+use byteorder::{ByteOrder, LittleEndian};
+use std::cmp::min;
+use std::io;
+use std::time::Duration;
+
+/// Transport running PTP containers over an arbitrary byte stream, e.g. a TCP
+/// bridge or a usbip-style tunnel.
+///
+/// Streams have no notion of transfers, so container boundaries are recovered
+/// from the length field of each header. Per-call timeouts are ignored; configure
+/// them on the underlying stream instead (e.g. `TcpStream::set_read_timeout`).
+pub struct StreamTransport<R: io::Read, W: io::Write> {
+    reader: R,
+    writer: W,
+    /// bytes left in the container currently being read
+    remaining: usize,
+    /// the last read ended a container with a full buffer, emulate the trailing ZLP
+    zlp_pending: bool,
+}
+
+impl<R: io::Read, W: io::Write> StreamTransport<R, W> {
+    pub fn new(reader: R, writer: W) -> StreamTransport<R, W> {
+        StreamTransport {
+            reader,
+            writer,
+            remaining: 0,
+            zlp_pending: false,
+        }
+    }
+
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+impl<R: io::Read, W: io::Write> Transport for StreamTransport<R, W> {
+    fn write(&mut self, buf: &[u8], _timeout: Duration) -> Result<(), Error> {
+        self.writer.write_all(buf)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize, Error> {
+        if self.zlp_pending {
+            self.zlp_pending = false;
+            return Ok(0);
+        }
+
+        let mut n = 0;
+        if self.remaining == 0 {
+            // start of a new container, its length field tells where it ends
+            if buf.len() < 4 {
+                return Err(Error::Malformed(format!(
+                    "Read buffer of {} bytes can't hold a container length",
+                    buf.len()
+                )));
+            }
+            self.reader.read_exact(&mut buf[..4])?;
+            let len = LittleEndian::read_u32(&buf[..4]) as usize;
+            if len < 4 {
+                return Err(Error::Malformed(format!(
+                    "Invalid container length {}",
+                    len
+                )));
+            }
+            self.remaining = len - 4;
+            n = 4;
+        }
+
+        let count = min(buf.len() - n, self.remaining);
+        self.reader.read_exact(&mut buf[n..n + count])?;
+        self.remaining -= count;
+        n += count;
+
+        self.zlp_pending = self.remaining == 0 && n == buf.len();
+        Ok(n)
+    }
+}
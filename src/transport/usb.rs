@@ -0,0 +1,216 @@
+use super::Transport;
+use crate::{Error, Read, ResponseCode, StandardEventCode, StandardResponseCode};
+use byteorder::{LittleEndian, WriteBytesExt};
+use rusb::{constants, UsbContext};
+use std::io::Cursor;
+use std::thread;
+use std::time::Duration;
+
+/// Still image class specific control requests.
+const CLASS_REQUEST_CANCEL: u8 = 0x64;
+const CLASS_REQUEST_DEVICE_RESET: u8 = 0x66;
+const CLASS_REQUEST_GET_DEVICE_STATUS: u8 = 0x67;
+
+/// Result of the still image class Get Device Status request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceStatus {
+    /// Ok, DeviceBusy, TransactionCancelled, ... see StandardResponseCode.
+    pub code: ResponseCode,
+    /// For a stalled device, the addresses of the halted endpoints.
+    pub params: Vec<u32>,
+}
+
+impl DeviceStatus {
+    pub fn decode(buf: &[u8]) -> Result<DeviceStatus, Error> {
+        let mut cur = Cursor::new(buf);
+        let len = cur.read_ptp_u16()? as usize;
+        let code = cur.read_ptp_u16()?;
+        let mut params = vec![];
+        while cur.position() as usize + 4 <= len.min(buf.len()) {
+            params.push(cur.read_ptp_u32()?);
+        }
+        Ok(DeviceStatus { code, params })
+    }
+}
+
+/// Transport over the bulk endpoints of a USB still image class interface.
+pub struct UsbTransport<C: UsbContext> {
+    iface: u8,
+    ep_in: u8,
+    ep_out: u8,
+    // absent on some devices, which then have no event channel
+    ep_int: Option<u8>,
+    handle: rusb::DeviceHandle<C>,
+}
+
+impl<C: UsbContext> UsbTransport<C> {
+    pub fn new(device: &rusb::Device<C>) -> Result<UsbTransport<C>, Error> {
+        let config_desc = device.active_config_descriptor()?;
+
+        let interface_desc = config_desc
+            .interfaces()
+            .flat_map(|i| i.descriptors())
+            .find(|x| x.class_code() == constants::LIBUSB_CLASS_IMAGE)
+            .ok_or(rusb::Error::NotFound)?;
+
+        debug!("Found interface {}", interface_desc.interface_number());
+
+        let handle = device.open()?;
+
+        handle.claim_interface(interface_desc.interface_number())?;
+
+        let find_endpoint = |direction, transfer_type| {
+            interface_desc
+                .endpoint_descriptors()
+                .find(|ep| ep.direction() == direction && ep.transfer_type() == transfer_type)
+                .map(|x| x.address())
+                .ok_or(rusb::Error::NotFound)
+        };
+
+        Ok(UsbTransport {
+            iface: interface_desc.interface_number(),
+            ep_in: find_endpoint(rusb::Direction::In, rusb::TransferType::Bulk)?,
+            ep_out: find_endpoint(rusb::Direction::Out, rusb::TransferType::Bulk)?,
+            ep_int: find_endpoint(rusb::Direction::In, rusb::TransferType::Interrupt).ok(),
+            handle,
+        })
+    }
+
+    pub fn handle(&self) -> &rusb::DeviceHandle<C> {
+        &self.handle
+    }
+
+    pub fn reset(&mut self) -> Result<(), Error> {
+        self.handle.reset()?;
+        Ok(())
+    }
+
+    // read and throw away anything left in the bulk-in pipe until it goes quiet
+    fn flush_in(&mut self) -> Result<(), Error> {
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            match self
+                .handle
+                .read_bulk(self.ep_in, &mut buf, Duration::from_millis(100))
+            {
+                Ok(n) => trace!("flushed {} bytes", n),
+                Err(rusb::Error::Timeout) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn class_request_type(direction: rusb::Direction) -> u8 {
+        rusb::request_type(
+            direction,
+            rusb::RequestType::Class,
+            rusb::Recipient::Interface,
+        )
+    }
+
+    /// Issue the class Get Device Status request.
+    pub fn get_device_status(&mut self, timeout: Duration) -> Result<DeviceStatus, Error> {
+        let mut buf = [0u8; 64];
+        let n = self.handle.read_control(
+            Self::class_request_type(rusb::Direction::In),
+            CLASS_REQUEST_GET_DEVICE_STATUS,
+            0,
+            self.iface as u16,
+            &mut buf,
+            timeout,
+        )?;
+        let status = DeviceStatus::decode(&buf[..n])?;
+        trace!("device status {:?}", status);
+        Ok(status)
+    }
+
+    /// Issue the class Device Reset request, returning the device to its idle state
+    /// without re-enumerating it on the bus.
+    pub fn device_reset(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.handle.write_control(
+            Self::class_request_type(rusb::Direction::Out),
+            CLASS_REQUEST_DEVICE_RESET,
+            0,
+            self.iface as u16,
+            &[],
+            timeout,
+        )?;
+        Ok(())
+    }
+
+    // poll Get Device Status until the device leaves the busy state
+    fn wait_ready(&mut self, timeout: Duration) -> Result<DeviceStatus, Error> {
+        for _ in 0..50 {
+            let status = self.get_device_status(timeout)?;
+            if status.code != StandardResponseCode::DeviceBusy {
+                return Ok(status);
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        Err(Error::Response(StandardResponseCode::DeviceBusy))
+    }
+
+    pub fn clear_halt(&mut self) -> Result<(), Error> {
+        self.handle.clear_halt(self.ep_in)?;
+        self.handle.clear_halt(self.ep_out)?;
+        if let Some(ep_int) = self.ep_int {
+            self.handle.clear_halt(ep_int)?;
+        }
+        Ok(())
+    }
+}
+
+impl<C: UsbContext> Transport for UsbTransport<C> {
+    fn write(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Error> {
+        self.handle.write_bulk(self.ep_out, buf, timeout)?;
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, Error> {
+        Ok(self.handle.read_bulk(self.ep_in, buf, timeout)?)
+    }
+
+    fn read_event(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, Error> {
+        let ep_int = self.ep_int.ok_or(rusb::Error::NotSupported)?;
+        Ok(self.handle.read_interrupt(ep_int, buf, timeout)?)
+    }
+
+    fn cancel(&mut self, tid: u32, timeout: Duration) -> Result<(), Error> {
+        let mut data = Vec::with_capacity(6);
+        data.write_u16::<LittleEndian>(StandardEventCode::CancelTransaction)
+            .ok();
+        data.write_u32::<LittleEndian>(tid).ok();
+
+        self.handle.write_control(
+            Self::class_request_type(rusb::Direction::Out),
+            CLASS_REQUEST_CANCEL,
+            0,
+            self.iface as u16,
+            &data,
+            timeout,
+        )?;
+
+        self.flush_in()?;
+        self.wait_ready(timeout)?;
+        Ok(())
+    }
+
+    fn recover(&mut self, timeout: Duration) -> Result<(), Error> {
+        // the device reports the endpoints it stalled, only clear those
+        let status = self.wait_ready(timeout)?;
+        for ep in &status.params {
+            debug!("clearing halt on endpoint 0x{:02x}", ep);
+            self.handle.clear_halt(*ep as u8)?;
+        }
+        let status = self.wait_ready(timeout)?;
+        if status.code != StandardResponseCode::Ok {
+            return Err(Error::Response(status.code));
+        }
+        Ok(())
+    }
+
+    fn release(&mut self) -> Result<(), Error> {
+        self.handle.release_interface(self.iface)?;
+        Ok(())
+    }
+}
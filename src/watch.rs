@@ -0,0 +1,179 @@
+use super::{Camera, CancelToken, Error, ObjectInfo, StandardEventCode, Transport};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// What `Camera::watch` does when a file of the same name is already in the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    Overwrite,
+    /// Leave the existing file and don't download the object.
+    Skip,
+    /// Download to `name_1.ext`, `name_2.ext` and so on, whichever is free first.
+    Rename,
+}
+
+/// Options of `Camera::watch`.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    collision: CollisionPolicy,
+    delete_after_download: bool,
+    cancel: Option<CancelToken>,
+    poll_interval: Duration,
+    timeout: Option<Duration>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> WatchOptions {
+        WatchOptions {
+            collision: CollisionPolicy::Rename,
+            delete_after_download: false,
+            cancel: None,
+            poll_interval: Duration::from_millis(200),
+            timeout: None,
+        }
+    }
+}
+
+impl WatchOptions {
+    pub fn new() -> WatchOptions {
+        WatchOptions::default()
+    }
+
+    /// What to do when the file already exists, `CollisionPolicy::Rename` by default.
+    pub fn with_collision(mut self, collision: CollisionPolicy) -> WatchOptions {
+        self.collision = collision;
+        self
+    }
+
+    /// Delete each object from the camera once it is downloaded.
+    pub fn with_delete_after_download(mut self, delete: bool) -> WatchOptions {
+        self.delete_after_download = delete;
+        self
+    }
+
+    /// Stop watching from another thread. Without a token, the watch only ends on error.
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> WatchOptions {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// How long each wait for an event lasts, which bounds how quickly a cancellation is
+    /// noticed. 200ms by default.
+    pub fn with_poll_interval(mut self, interval: Duration) -> WatchOptions {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Timeout of the commands downloading and deleting the objects.
+    pub fn with_timeout(mut self, timeout: Duration) -> WatchOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+// where to save `name` in `dest`, `None` to skip it
+fn destination(dest: &Path, name: &str, collision: CollisionPolicy) -> Option<PathBuf> {
+    let path = dest.join(name);
+    if !path.exists() {
+        return Some(path);
+    }
+    match collision {
+        CollisionPolicy::Overwrite => Some(path),
+        CollisionPolicy::Skip => None,
+        CollisionPolicy::Rename => {
+            let (stem, ext) = match name.rfind('.') {
+                Some(i) if i > 0 => name.split_at(i),
+                _ => (name, ""),
+            };
+            (1..)
+                .map(|n| dest.join(format!("{}_{}{}", stem, n, ext)))
+                .find(|p| !p.exists())
+        }
+    }
+}
+
+impl<T: Transport> Camera<T> {
+    /// Download each object the camera reports with ObjectAdded into `dest` as it
+    /// appears, e.g. the shots of a tethered session, calling `on_file` with the path
+    /// and ObjectInfo of each file saved. Folders are ignored.
+    ///
+    /// Runs until the cancel token of `options` is set, returning the number of files
+    /// saved. An object failing with an error response, e.g. because it was deleted in
+    /// the meantime, is logged and skipped; any other error ends the watch.
+    pub fn watch<P: AsRef<Path>, F: FnMut(&Path, &ObjectInfo)>(
+        &mut self,
+        dest: P,
+        options: &WatchOptions,
+        mut on_file: F,
+    ) -> Result<usize, Error> {
+        let dest = dest.as_ref();
+        let mut saved = 0;
+
+        while !options
+            .cancel
+            .as_ref()
+            .is_some_and(CancelToken::is_cancelled)
+        {
+            let mut events = self.drain_pending_events();
+            events.extend(self.poll_event(Some(options.poll_interval))?);
+
+            for event in events {
+                if event.code != StandardEventCode::ObjectAdded {
+                    continue;
+                }
+                let handle = match event.object_handle() {
+                    Some(handle) => handle,
+                    None => continue,
+                };
+                match self.watch_download(handle, dest, options) {
+                    Ok(Some((path, info))) => {
+                        saved += 1;
+                        on_file(&path, &info);
+                    }
+                    Ok(None) => {}
+                    Err(Error::Response(code)) => {
+                        warn!("watch: skipping object 0x{:08x}: 0x{:04x}", handle, code)
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(saved)
+    }
+
+    fn watch_download(
+        &mut self,
+        handle: u32,
+        dest: &Path,
+        options: &WatchOptions,
+    ) -> Result<Option<(PathBuf, ObjectInfo)>, Error> {
+        let info = self.get_objectinfo(handle, options.timeout)?;
+        if info.is_folder() {
+            return Ok(None);
+        }
+        // the name comes from the device, only keep its last component
+        let name = match Path::new(&info.Filename)
+            .file_name()
+            .and_then(|n| n.to_str())
+        {
+            Some(name) => name.to_owned(),
+            None => {
+                warn!("watch: skipping object with file name {:?}", info.Filename);
+                return Ok(None);
+            }
+        };
+        let path = match destination(dest, &name, options.collision) {
+            Some(path) => path,
+            None => {
+                debug!("watch: {} already exists, skipping", name);
+                return Ok(None);
+            }
+        };
+
+        self.get_object_to_file(handle, &path, |_, _| {}, options.timeout)?;
+        if options.delete_after_download {
+            self.delete_object(handle, options.timeout)?;
+        }
+        Ok(Some((path, info)))
+    }
+}
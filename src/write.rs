@@ -0,0 +1,93 @@
+use super::Error;
+use byteorder::{LittleEndian, WriteBytesExt};
+
+pub trait PtpWrite: WriteBytesExt {
+    fn write_ptp_u8(&mut self, v: u8) -> Result<(), Error> {
+        Ok(self.write_u8(v)?)
+    }
+
+    fn write_ptp_i8(&mut self, v: i8) -> Result<(), Error> {
+        Ok(self.write_i8(v)?)
+    }
+
+    fn write_ptp_u16(&mut self, v: u16) -> Result<(), Error> {
+        Ok(self.write_u16::<LittleEndian>(v)?)
+    }
+
+    fn write_ptp_i16(&mut self, v: i16) -> Result<(), Error> {
+        Ok(self.write_i16::<LittleEndian>(v)?)
+    }
+
+    fn write_ptp_u32(&mut self, v: u32) -> Result<(), Error> {
+        Ok(self.write_u32::<LittleEndian>(v)?)
+    }
+
+    fn write_ptp_i32(&mut self, v: i32) -> Result<(), Error> {
+        Ok(self.write_i32::<LittleEndian>(v)?)
+    }
+
+    fn write_ptp_u64(&mut self, v: u64) -> Result<(), Error> {
+        Ok(self.write_u64::<LittleEndian>(v)?)
+    }
+
+    fn write_ptp_i64(&mut self, v: i64) -> Result<(), Error> {
+        Ok(self.write_i64::<LittleEndian>(v)?)
+    }
+
+    fn write_ptp_u128(&mut self, v: u128) -> Result<(), Error> {
+        Ok(self.write_u128::<LittleEndian>(v)?)
+    }
+
+    fn write_ptp_i128(&mut self, v: i128) -> Result<(), Error> {
+        Ok(self.write_i128::<LittleEndian>(v)?)
+    }
+
+    #[inline(always)]
+    fn write_ptp_vec<T, U: Fn(&mut Self, &T) -> Result<(), Error>>(
+        &mut self,
+        items: &[T],
+        func: U,
+    ) -> Result<(), Error> {
+        self.write_u32::<LittleEndian>(items.len() as u32)?;
+        items.iter().try_for_each(|item| func(self, item))
+    }
+
+    fn write_ptp_u16_vec(&mut self, v: &[u16]) -> Result<(), Error> {
+        self.write_ptp_vec(v, |cur, item| cur.write_ptp_u16(*item))
+    }
+
+    fn write_ptp_u32_vec(&mut self, v: &[u32]) -> Result<(), Error> {
+        self.write_ptp_vec(v, |cur, item| cur.write_ptp_u32(*item))
+    }
+
+    /// Write a PTP string: a u8 count of UCS-2 code units, including the trailing
+    /// null, followed by the code units. An empty string is a single zero count.
+    ///
+    /// Strings over 254 characters or with characters outside the Basic Multilingual
+    /// Plane, which have no UCS-2 representation, are rejected.
+    fn write_ptp_str(&mut self, s: &str) -> Result<(), Error> {
+        if s.is_empty() {
+            return Ok(self.write_u8(0)?);
+        }
+        if let Some(c) = s.chars().find(|c| c.len_utf16() > 1) {
+            return Err(Error::Malformed(format!(
+                "Character {:?} can't be represented in a PTP string",
+                c
+            )));
+        }
+        let data: Vec<u16> = s.encode_utf16().collect();
+        if data.len() > 254 {
+            return Err(Error::Malformed(format!(
+                "String of {} characters doesn't fit a PTP string",
+                data.len()
+            )));
+        }
+        self.write_u8(data.len() as u8 + 1)?;
+        for unit in data {
+            self.write_u16::<LittleEndian>(unit)?;
+        }
+        Ok(self.write_u16::<LittleEndian>(0)?)
+    }
+}
+
+impl<W: WriteBytesExt + ?Sized> PtpWrite for W {}
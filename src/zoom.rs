@@ -0,0 +1,112 @@
+use super::{Camera, CanonPropCode, DataType, Error, PropInfo, StandardPropertyCode, Transport};
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// Zoom of compact cameras, driven through a zoom property whose positions are taken
+/// from its descriptor.
+pub struct ZoomControl<'a, T: Transport> {
+    camera: &'a mut Camera<T>,
+    info: PropInfo,
+    positions: Vec<u32>,
+    current: u32,
+}
+
+impl<'a, T: Transport> ZoomControl<'a, T> {
+    /// Zoom through the property `property_code`, which must have a Range or
+    /// Enumeration form.
+    pub fn new(
+        camera: &'a mut Camera<T>,
+        property_code: u16,
+        timeout: Option<Duration>,
+    ) -> Result<ZoomControl<'a, T>, Error> {
+        let info = camera.get_device_prop_desc(property_code, timeout)?;
+        let mut positions: Vec<u32> = info
+            .form
+            .iter()
+            .filter_map(|v| v.to_i128())
+            .filter_map(|v| u32::try_from(v).ok())
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        if positions.is_empty() {
+            return Err(Error::Malformed(format!(
+                "Property 0x{:04x} has no zoom positions",
+                property_code
+            )));
+        }
+        let current = info
+            .current
+            .to_i128()
+            .and_then(|v| u32::try_from(v).ok())
+            .unwrap_or(positions[0]);
+        Ok(ZoomControl {
+            camera,
+            info,
+            positions,
+            current,
+        })
+    }
+
+    /// Zoom through the standard DigitalZoom property.
+    pub fn digital(
+        camera: &'a mut Camera<T>,
+        timeout: Option<Duration>,
+    ) -> Result<ZoomControl<'a, T>, Error> {
+        ZoomControl::new(camera, StandardPropertyCode::DigitalZoom, timeout)
+    }
+
+    /// Optical zoom of Canon PowerShot bodies, which only accept it in release control
+    /// mode (InitiateReleaseControl).
+    pub fn canon_powershot(
+        camera: &'a mut Camera<T>,
+        timeout: Option<Duration>,
+    ) -> Result<ZoomControl<'a, T>, Error> {
+        ZoomControl::new(camera, CanonPropCode::Zoom, timeout)
+    }
+
+    /// The widest and the longest position.
+    pub fn range(&self) -> RangeInclusive<u32> {
+        self.positions[0]..=self.positions[self.positions.len() - 1]
+    }
+
+    /// The current position, as last read or set.
+    pub fn position(&self) -> u32 {
+        self.current
+    }
+
+    /// Move to the allowed position nearest to `position`, returning it.
+    pub fn set_zoom(&mut self, position: u32, timeout: Option<Duration>) -> Result<u32, Error> {
+        let target = *self
+            .positions
+            .iter()
+            .min_by_key(|p| p.abs_diff(position))
+            .unwrap_or(&self.current);
+        let value: DataType = self.info.current.with_i128(target as i128).ok_or_else(|| {
+            Error::Malformed(format!(
+                "Zoom position {} doesn't fit {:?}",
+                target, self.info.current
+            ))
+        })?;
+        self.camera
+            .set_device_prop_value(self.info.property_code, &value, timeout)?;
+        self.current = target;
+        Ok(target)
+    }
+
+    /// Move one position towards tele, staying put at the end of the range.
+    pub fn zoom_in(&mut self, timeout: Option<Duration>) -> Result<u32, Error> {
+        match self.positions.iter().find(|p| **p > self.current) {
+            Some(&next) => self.set_zoom(next, timeout),
+            None => Ok(self.current),
+        }
+    }
+
+    /// Move one position towards wide, staying put at the start of the range.
+    pub fn zoom_out(&mut self, timeout: Option<Duration>) -> Result<u32, Error> {
+        match self.positions.iter().rev().find(|p| **p < self.current) {
+            Some(&prev) => self.set_zoom(prev, timeout),
+            None => Ok(self.current),
+        }
+    }
+}